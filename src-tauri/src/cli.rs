@@ -3,6 +3,7 @@
 
 use clap::{Parser, Subcommand};
 use colored::*;
+use serde::Serialize;
 use std::path::PathBuf;
 use std::process;
 
@@ -21,6 +22,10 @@ use validator::{PackageValidator, ValidationError, ValidationWarning};
 #[command(version = "0.1.0")]
 #[command(about = "Random Prompt Generator CLI - Package validation and rendering", long_about = None)]
 struct Cli {
+    /// Output format; json, jsonl and yaml print only data (messages go to stderr)
+    #[arg(short, long, global = true, value_enum, default_value = "text")]
+    format: OutputFormat,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -49,15 +54,19 @@ enum Commands {
         path: PathBuf,
     },
 
-    /// Render a prompt section
+    /// Render a prompt section or rulebook
     Render {
         /// Path to the package file (YAML or JSON)
         #[arg(value_name = "FILE")]
         path: PathBuf,
 
         /// Prompt section to render (format: namespace:section)
-        #[arg(value_name = "SECTION")]
-        section: String,
+        #[arg(value_name = "SECTION", required_unless_present = "rulebook")]
+        section: Option<String>,
+
+        /// Render from a rulebook instead, with its batch variety (format: namespace:rulebook)
+        #[arg(short, long, conflicts_with = "section")]
+        rulebook: Option<String>,
 
         /// Seed for deterministic rendering
         #[arg(short, long, default_value = "42")]
@@ -66,25 +75,214 @@ enum Commands {
         /// Number of renders to generate
         #[arg(short, long, default_value = "1")]
         count: usize,
+
+        /// Show how each prompt was produced (references, candidates, rules, context writes)
+        #[arg(short, long)]
+        trace: bool,
+
+        /// Ignore all tag filters ("chaos mode")
+        #[arg(long)]
+        bypass_filters: bool,
+
+        /// Log render progress to stderr (-v: phases, -vv: also selections, rules and decisions)
+        #[arg(short, long, action = clap::ArgAction::Count)]
+        verbose: u8,
     },
+
+    /// Enumerate every output of a prompt section with its probability
+    Enumerate {
+        /// Path to the package file (YAML or JSON)
+        #[arg(value_name = "FILE")]
+        path: PathBuf,
+
+        /// Prompt section to enumerate (format: namespace:section)
+        #[arg(value_name = "SECTION")]
+        section: String,
+
+        /// Only count combinations and distinct outputs, and list unreachable values
+        #[arg(short, long)]
+        count: bool,
+
+        /// Stop after this many combinations
+        #[arg(short, long, default_value = "10000")]
+        limit: usize,
+
+        /// Ignore all tag filters ("chaos mode")
+        #[arg(long)]
+        bypass_filters: bool,
+    },
+
+    /// Show how likely each datatype value is to be chosen
+    Probabilities {
+        /// Path to the package file (YAML or JSON)
+        #[arg(value_name = "FILE")]
+        path: PathBuf,
+
+        /// Prompt section to analyze (format: namespace:section)
+        #[arg(value_name = "SECTION", required_unless_present = "rulebook")]
+        section: Option<String>,
+
+        /// Analyze a rulebook's entry points instead (format: namespace:rulebook)
+        #[arg(short, long, conflicts_with = "section")]
+        rulebook: Option<String>,
+
+        /// Stop after this many combinations
+        #[arg(short, long, default_value = "10000")]
+        limit: usize,
+
+        /// Ignore all tag filters ("chaos mode")
+        #[arg(long)]
+        bypass_filters: bool,
+    },
+
+    /// Render many seeds and report value frequencies, duplicates and failures
+    Stats {
+        /// Path to the package file (YAML or JSON)
+        #[arg(value_name = "FILE")]
+        path: PathBuf,
+
+        /// Prompt section to sample (format: namespace:section)
+        #[arg(value_name = "SECTION", required_unless_present = "rulebook")]
+        section: Option<String>,
+
+        /// Sample a rulebook instead (format: namespace:rulebook)
+        #[arg(short, long, conflicts_with = "section")]
+        rulebook: Option<String>,
+
+        /// Number of seeds to render
+        #[arg(short = 'n', long, default_value = "1000")]
+        samples: usize,
+
+        /// First seed
+        #[arg(short, long, default_value = "0")]
+        seed: u64,
+
+        /// Ignore all tag filters ("chaos mode")
+        #[arg(long)]
+        bypass_filters: bool,
+    },
+}
+
+/// How a command prints its results
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum OutputFormat {
+    /// Human-readable output
+    Text,
+    /// A single JSON document
+    Json,
+    /// One JSON object per line (per render, path or validation issue)
+    Jsonl,
+    /// A single YAML document
+    Yaml,
+}
+
+impl OutputFormat {
+    fn is_text(self) -> bool {
+        self == OutputFormat::Text
+    }
+}
+
+/// Print a value as a machine-readable document (one line for jsonl)
+fn emit<T: Serialize + ?Sized>(format: OutputFormat, value: &T) -> Result<(), i32> {
+    let printed = match format {
+        OutputFormat::Jsonl => serde_json::to_string(value).map_err(|e| e.to_string()),
+        OutputFormat::Yaml => serde_yaml::to_string(value).map_err(|e| e.to_string()),
+        OutputFormat::Json | OutputFormat::Text => {
+            serde_json::to_string_pretty(value).map_err(|e| e.to_string())
+        }
+    };
+
+    match printed {
+        Ok(printed) => {
+            println!("{}", printed.trim_end());
+            Ok(())
+        }
+        Err(e) => {
+            print_failure("Failed to serialize output", &e);
+            Err(1)
+        }
+    }
+}
+
+/// Print a list: one line per item for jsonl, a single document otherwise
+fn emit_list<T: Serialize>(format: OutputFormat, items: &[T]) -> Result<(), i32> {
+    if format == OutputFormat::Jsonl {
+        items.iter().try_for_each(|item| emit(format, item))
+    } else {
+        emit(format, items)
+    }
+}
+
+/// Print an error to stderr (stdout is kept for results)
+fn print_failure(title: &str, detail: &dyn std::fmt::Display) {
+    eprintln!("{} {}", "Error:".red().bold(), title.red());
+    eprintln!("  {}", detail.to_string().bright_red());
 }
 
 fn main() {
     let cli = Cli::parse();
+    let format = cli.format;
 
     let result = match cli.command {
         Commands::Validate {
             path,
             warnings,
             verbose,
-        } => validate_command(path, warnings, verbose),
-        Commands::Info { path } => info_command(path),
+        } => validate_command(path, warnings, verbose, format),
+        Commands::Info { path } => info_command(path, format),
         Commands::Render {
             path,
             section,
+            rulebook,
             seed,
             count,
-        } => render_command(path, section, seed, count),
+            trace,
+            bypass_filters,
+            verbose,
+        } => {
+            let target = match rulebook {
+                Some(rulebook) => RenderTarget::Rulebook(rulebook),
+                None => RenderTarget::Section(section.unwrap_or_default()),
+            };
+            let options = RenderOptions {
+                seed,
+                count,
+                trace,
+                bypass_filters,
+                verbose,
+            };
+            render_command(path, target, options, format)
+        }
+        Commands::Enumerate {
+            path,
+            section,
+            count,
+            limit,
+            bypass_filters,
+        } => enumerate_command(path, section, count, limit, bypass_filters, format),
+        Commands::Probabilities {
+            path,
+            section,
+            rulebook,
+            limit,
+            bypass_filters,
+        } => probabilities_command(path, section, rulebook, limit, bypass_filters, format),
+        Commands::Stats {
+            path,
+            section,
+            rulebook,
+            samples,
+            seed,
+            bypass_filters,
+        } => stats_command(
+            path,
+            section,
+            rulebook,
+            samples,
+            seed,
+            bypass_filters,
+            format,
+        ),
     };
 
     if let Err(exit_code) = result {
@@ -93,18 +291,28 @@ fn main() {
 }
 
 /// Validate command - check package for errors and warnings
-fn validate_command(path: PathBuf, show_warnings: bool, verbose: bool) -> Result<(), i32> {
+fn validate_command(
+    path: PathBuf,
+    show_warnings: bool,
+    verbose: bool,
+    format: OutputFormat,
+) -> Result<(), i32> {
+    let text = format.is_text();
+    let verbose = verbose && text;
+
     // Header
-    println!("{}", "=".repeat(60).bright_blue());
-    println!("{} {}", "Validating:".bright_cyan().bold(), path.display());
-    println!("{}", "=".repeat(60).bright_blue());
-    println!();
+    if text {
+        println!("{}", "=".repeat(60).bright_blue());
+        println!("{} {}", "Validating:".bright_cyan().bold(), path.display());
+        println!("{}", "=".repeat(60).bright_blue());
+        println!();
+    }
 
     // M9 Phase 2.7: Load package with dependencies
     if verbose {
         println!(
             "{} Loading package with dependencies...",
-            "â†’".bright_blue()
+            "->".bright_blue()
         );
     }
 
@@ -120,11 +328,11 @@ fn validate_command(path: PathBuf, show_warnings: bool, verbose: bool) -> Result
     let (package, dependencies) = match resolver.load_package_with_deps(&path) {
         Ok((pkg, deps)) => {
             if verbose {
-                println!("{} Package loaded successfully", "âœ“".green());
+                println!("{} Package loaded successfully", "ok".green());
                 if !deps.is_empty() {
                     println!(
                         "  {} Dependencies loaded: {}",
-                        "â†’".bright_blue(),
+                        "->".bright_blue(),
                         deps.len()
                     );
                     for (dep_id, dep_pkg) in &deps {
@@ -139,25 +347,53 @@ fn validate_command(path: PathBuf, show_warnings: bool, verbose: bool) -> Result
             (pkg, deps)
         }
         Err(e) => {
-            println!("{} {}", "âœ—".red().bold(), "Failed to load package".red());
-            println!("  {}", format!("{}", e).bright_red());
+            print_failure("Failed to load package", &e);
             return Err(1);
         }
     };
 
     // Run validation with dependencies
     if verbose {
-        println!("{} Running validation...", "â†’".bright_blue());
+        println!("{} Running validation...", "->".bright_blue());
     }
 
     let result = PackageValidator::validate_with_dependencies(&package, &dependencies);
 
+    if !text {
+        let errors: Vec<Issue<ValidationError>> = result
+            .errors
+            .iter()
+            .map(|e| Issue::new("error", e))
+            .collect();
+        let warnings: Vec<Issue<ValidationWarning>> = result
+            .warnings
+            .iter()
+            .map(|w| Issue::new("warning", w))
+            .collect();
+
+        if format == OutputFormat::Jsonl {
+            emit_list(format, &errors)?;
+            emit_list(format, &warnings)?;
+        } else {
+            emit(
+                format,
+                &ValidationReport {
+                    path: path.display().to_string(),
+                    valid: result.is_valid(),
+                    errors,
+                    warnings,
+                },
+            )?;
+        }
+        return if result.is_valid() { Ok(()) } else { Err(1) };
+    }
+
     println!();
 
     // Display results
     if result.is_valid() {
         // Success!
-        println!("{}", "âœ“ VALIDATION PASSED".green().bold());
+        println!("{}", "VALIDATION PASSED".green().bold());
         println!();
 
         if show_warnings && result.has_warnings() {
@@ -165,7 +401,7 @@ fn validate_command(path: PathBuf, show_warnings: bool, verbose: bool) -> Result
         }
 
         // Summary
-        println!("{}", "â”€".repeat(60).bright_black());
+        println!("{}", "-".repeat(60).bright_black());
         println!("{} {}", "Result:".bright_cyan(), "VALID".green().bold());
 
         if result.has_warnings() {
@@ -174,12 +410,12 @@ fn validate_command(path: PathBuf, show_warnings: bool, verbose: bool) -> Result
             println!("{} {}", "Warnings:".bright_black(), "0".bright_black());
         }
 
-        println!("{}", "â”€".repeat(60).bright_black());
+        println!("{}", "-".repeat(60).bright_black());
 
         Ok(())
     } else {
         // Failed validation
-        println!("{}", "âœ— VALIDATION FAILED".red().bold());
+        println!("{}", "VALIDATION FAILED".red().bold());
         println!();
 
         display_errors(&result.errors, verbose);
@@ -191,7 +427,7 @@ fn validate_command(path: PathBuf, show_warnings: bool, verbose: bool) -> Result
 
         // Summary
         println!();
-        println!("{}", "â”€".repeat(60).bright_black());
+        println!("{}", "-".repeat(60).bright_black());
         println!("{} {}", "Result:".bright_cyan(), "INVALID".red().bold());
         println!("{} {}", "Errors:".red(), result.errors.len());
 
@@ -199,12 +435,40 @@ fn validate_command(path: PathBuf, show_warnings: bool, verbose: bool) -> Result
             println!("{} {}", "Warnings:".yellow(), result.warnings.len());
         }
 
-        println!("{}", "â”€".repeat(60).bright_black());
+        println!("{}", "-".repeat(60).bright_black());
 
         Err(1)
     }
 }
 
+/// Validation results, for machine-readable output
+#[derive(Serialize)]
+struct ValidationReport<'v> {
+    path: String,
+    valid: bool,
+    errors: Vec<Issue<'v, ValidationError>>,
+    warnings: Vec<Issue<'v, ValidationWarning>>,
+}
+
+/// A validation error or warning with its message
+#[derive(Serialize)]
+struct Issue<'v, T> {
+    severity: &'static str,
+    message: String,
+    #[serde(flatten)]
+    detail: &'v T,
+}
+
+impl<'v, T: std::fmt::Display> Issue<'v, T> {
+    fn new(severity: &'static str, detail: &'v T) -> Self {
+        Issue {
+            severity,
+            message: detail.to_string(),
+            detail,
+        }
+    }
+}
+
 /// Display validation errors
 fn display_errors(errors: &[ValidationError], verbose: bool) {
     println!("{} ({})", "Errors".red().bold(), errors.len());
@@ -246,26 +510,31 @@ fn display_warnings(warnings: &[ValidationWarning], verbose: bool) {
 }
 
 /// Info command - display package information
-fn info_command(path: PathBuf) -> Result<(), i32> {
-    println!("{}", "=".repeat(60).bright_blue());
-    println!(
-        "{} {}",
-        "Package Information:".bright_cyan().bold(),
-        path.display()
-    );
-    println!("{}", "=".repeat(60).bright_blue());
-    println!();
+fn info_command(path: PathBuf, format: OutputFormat) -> Result<(), i32> {
+    if format.is_text() {
+        println!("{}", "=".repeat(60).bright_blue());
+        println!(
+            "{} {}",
+            "Package Information:".bright_cyan().bold(),
+            path.display()
+        );
+        println!("{}", "=".repeat(60).bright_blue());
+        println!();
+    }
 
     // Load package
     let package = match load_package(&path) {
         Ok(pkg) => pkg,
         Err(e) => {
-            println!("{} {}", "âœ—".red().bold(), "Failed to load package".red());
-            println!("  {}", format!("{}", e).bright_red());
+            print_failure("Failed to load package", &e);
             return Err(1);
         }
     };
 
+    if !format.is_text() {
+        return emit(format, &PackageInfo::new(&package));
+    }
+
     // Display info
     println!(
         "{} {} v{}",
@@ -295,31 +564,32 @@ fn info_command(path: PathBuf) -> Result<(), i32> {
 
     for (ns_id, namespace) in &package.namespaces {
         println!();
-        println!(
-            "  {} {}",
-            "â””â”€".bright_blue(),
-            ns_id.bright_white().bold()
-        );
+        println!("  {} {}", "`-".bright_blue(), ns_id.bright_white().bold());
         println!(
             "     {} {} datatype(s)",
-            "â”œâ”€".bright_black(),
+            "|-".bright_black(),
             namespace.datatypes.len()
         );
         println!(
             "     {} {} promptsection(s)",
-            "â”œâ”€".bright_black(),
+            "|-".bright_black(),
             namespace.prompt_sections.len()
         );
         println!(
             "     {} {} separator set(s)",
-            "â”œâ”€".bright_black(),
+            "|-".bright_black(),
             namespace.separator_sets.len()
         );
         println!(
             "     {} {} rule(s)",
-            "â””â”€".bright_black(),
+            "|-".bright_black(),
             namespace.rules.len()
         );
+        println!(
+            "     {} {} rulebook(s)",
+            "`-".bright_black(),
+            namespace.rulebooks.len()
+        );
     }
 
     println!();
@@ -333,7 +603,7 @@ fn info_command(path: PathBuf) -> Result<(), i32> {
         for dep in &package.dependencies {
             println!(
                 "  {} {} (v{})",
-                "â””â”€".bright_blue(),
+                "`-".bright_blue(),
                 dep.package,
                 dep.version
             );
@@ -341,93 +611,218 @@ fn info_command(path: PathBuf) -> Result<(), i32> {
     }
 
     println!();
-    println!("{}", "â”€".repeat(60).bright_black());
+    println!("{}", "-".repeat(60).bright_black());
 
     Ok(())
 }
 
-/// Render command - render a prompt section
-fn render_command(path: PathBuf, section: String, seed: u64, count: usize) -> Result<(), i32> {
-    use parser::load_package_with_dependencies;
-    use renderer::Renderer;
+/// Package summary, for machine-readable output
+#[derive(Serialize)]
+struct PackageInfo<'p> {
+    id: &'p str,
+    version: &'p str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'p str>,
+    authors: &'p [String],
+    namespaces: Vec<NamespaceInfo<'p>>,
+    dependencies: &'p [core::Dependency],
+}
 
-    println!("{}", "=".repeat(60).bright_blue());
-    println!(
-        "{} {}",
-        "Rendering:".bright_cyan().bold(),
-        section.bright_white()
-    );
-    println!("{}", "=".repeat(60).bright_blue());
-    println!();
+#[derive(Serialize)]
+struct NamespaceInfo<'p> {
+    id: &'p str,
+    datatypes: usize,
+    prompt_sections: usize,
+    separator_sets: usize,
+    rules: usize,
+    rulebooks: usize,
+}
 
-    // M8.5 Blocker 2 Phase 2: Load package with dependencies
-    println!(
-        "{} Loading package from {}",
-        "â†’".bright_blue(),
-        path.display()
-    );
+impl<'p> PackageInfo<'p> {
+    fn new(package: &'p core::Package) -> Self {
+        PackageInfo {
+            id: &package.id,
+            version: &package.version,
+            description: package.metadata.description.as_deref(),
+            authors: &package.metadata.authors,
+            namespaces: package
+                .namespaces
+                .iter()
+                .map(|(id, namespace)| NamespaceInfo {
+                    id,
+                    datatypes: namespace.datatypes.len(),
+                    prompt_sections: namespace.prompt_sections.len(),
+                    separator_sets: namespace.separator_sets.len(),
+                    rules: namespace.rules.len(),
+                    rulebooks: namespace.rulebooks.len(),
+                })
+                .collect(),
+            dependencies: &package.dependencies,
+        }
+    }
+}
+
+/// What the render command renders
+enum RenderTarget {
+    Section(String),
+    Rulebook(String),
+}
+
+/// Options of the render command
+struct RenderOptions {
+    seed: u64,
+    count: usize,
+    trace: bool,
+    bypass_filters: bool,
+    verbose: u8,
+}
+
+/// A render and the rulebook entry point it used, for machine-readable output
+#[derive(Serialize)]
+struct RenderRecord {
+    #[serde(flatten)]
+    result: renderer::engine::RenderResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entry_point: Option<String>,
+}
+
+/// Render command - render a prompt section or rulebook
+fn render_command(
+    path: PathBuf,
+    target: RenderTarget,
+    options: RenderOptions,
+    format: OutputFormat,
+) -> Result<(), i32> {
+    use parser::load_package_with_dependencies;
+    use renderer::{Renderer, RulebookBatch};
+
+    let RenderOptions {
+        seed,
+        count,
+        trace,
+        bypass_filters,
+        verbose,
+    } = options;
+    let text = format.is_text();
+
+    if text {
+        let (label, name) = match &target {
+            RenderTarget::Section(section) => ("Rendering:", section),
+            RenderTarget::Rulebook(rulebook) => ("Rendering rulebook:", rulebook),
+        };
+        println!("{}", "=".repeat(60).bright_blue());
+        println!("{} {}", label.bright_cyan().bold(), name.bright_white());
+        println!("{}", "=".repeat(60).bright_blue());
+        println!();
+
+        // M8.5 Blocker 2 Phase 2: Load package with dependencies
+        println!(
+            "{} Loading package from {}",
+            "->".bright_blue(),
+            path.display()
+        );
+    }
 
     let loaded = match load_package_with_dependencies(&path) {
-        Ok(loaded) => {
-            println!("{} Package loaded", "âœ“".green());
-            if !loaded.dependencies.is_empty() {
-                println!(
-                    "{} {} dependencies loaded",
-                    "âœ“".green(),
-                    loaded.dependencies.len()
-                );
-            }
-            loaded
-        }
+        Ok(loaded) => loaded,
         Err(e) => {
-            println!("{} {}", "âœ—".red().bold(), "Failed to load package".red());
-            println!("  {}", format!("{}", e).bright_red());
+            print_failure("Failed to load package", &e);
             return Err(1);
         }
     };
 
-    println!();
+    if text {
+        println!("{} Package loaded", "ok".green());
+        if !loaded.dependencies.is_empty() {
+            println!(
+                "{} {} dependencies loaded",
+                "ok".green(),
+                loaded.dependencies.len()
+            );
+        }
+        println!();
+    }
+
+    let observer = CliObserver { level: verbose };
+
+    // Rulebook renders share the rulebook's batch variety, as in the
+    // render_from_rulebook_batch Tauri commands
+    let mut batch = RulebookBatch::new(seed);
+    let mut records = Vec::new();
 
     // Render
     for i in 0..count {
-        let current_seed = seed + i as u64;
+        let current_seed = batch.seed(i);
 
-        if count > 1 {
+        if text && count > 1 {
             println!(
                 "{} {} (Seed: {})",
                 format!("#{}", i + 1).bright_blue().bold(),
-                "â”€".repeat(54).bright_black(),
+                "-".repeat(54).bright_black(),
                 current_seed.to_string().bright_black()
             );
-        } else {
+        } else if text {
             println!("{} {}", "Seed:".bright_cyan(), current_seed);
         }
 
         // M8.5 Blocker 2 Phase 2: Use new_with_dependencies
-        let renderer =
-            Renderer::new_with_dependencies(&loaded.package, &loaded.dependencies, current_seed);
-
-        match renderer.render(&section) {
-            Ok(result) => {
-                println!();
-                println!("  {}", result.output.bright_white().bold());
-                println!();
-
-                if count == 1 {
-                    println!("{}", "â”€".repeat(60).bright_black());
-                    println!("{} {:.2}ms", "Render time:".bright_cyan(), 0.0); // TODO: actual timing
-                }
-            }
+        let renderer = |seed| {
+            Renderer::new_with_dependencies(&loaded.package, &loaded.dependencies, seed)
+                .with_trace(trace)
+                .with_bypass_filters(bypass_filters)
+                .with_observer(&observer)
+        };
+
+        let result = match &target {
+            RenderTarget::Section(section) => renderer(current_seed)
+                .render(section)
+                .map(|result| (result, None)),
+            RenderTarget::Rulebook(rulebook) => batch
+                .render(i, rulebook, renderer)
+                .map(|(result, entry_point)| (result, Some(entry_point))),
+        };
+
+        let (result, entry_point) = match result {
+            Ok(result) => result,
             Err(e) => {
-                println!();
-                println!(
-                    "  {} {}",
-                    "âœ—".red().bold(),
-                    format!("Render error: {}", e).red()
-                );
-                println!();
+                if text {
+                    println!();
+                }
+                print_failure(&format!("Render failed (seed {})", current_seed), &e);
                 return Err(1);
             }
+        };
+
+        if !text {
+            let record = RenderRecord {
+                result,
+                entry_point,
+            };
+            // jsonl streams one line per render; json and yaml print one list at the end
+            if format == OutputFormat::Jsonl {
+                emit(format, &record)?;
+            } else {
+                records.push(record);
+            }
+            continue;
+        }
+
+        if let Some(entry_point) = entry_point {
+            println!("{} {}", "Entry point:".bright_cyan(), entry_point);
+        }
+        println!();
+        println!("  {}", result.output.bright_white().bold());
+        println!();
+
+        if let Some(section_trace) = &result.trace {
+            println!("{}", "Trace:".bright_cyan());
+            display_trace(section_trace, 1);
+            println!();
+        }
+
+        if count == 1 {
+            println!("{}", "-".repeat(60).bright_black());
+            println!("{} {:.2}ms", "Render time:".bright_cyan(), 0.0); // TODO: actual timing
         }
 
         if i < count - 1 {
@@ -435,16 +830,554 @@ fn render_command(path: PathBuf, section: String, seed: u64, count: usize) -> Re
         }
     }
 
+    if !text {
+        if format != OutputFormat::Jsonl {
+            emit(format, &records)?;
+        }
+        return Ok(());
+    }
+
     if count > 1 {
         println!();
-        println!("{}", "â”€".repeat(60).bright_black());
+        println!("{}", "-".repeat(60).bright_black());
         println!(
             "{} {}",
             "Total:".bright_cyan(),
             format!("{} prompts rendered", count).bright_white()
         );
-        println!("{}", "â”€".repeat(60).bright_black());
+        println!("{}", "-".repeat(60).bright_black());
+    }
+
+    Ok(())
+}
+
+/// Enumerate command - list every output of a section, or count them
+fn enumerate_command(
+    path: PathBuf,
+    section: String,
+    count_only: bool,
+    limit: usize,
+    bypass_filters: bool,
+    format: OutputFormat,
+) -> Result<(), i32> {
+    use parser::load_package_with_dependencies;
+    use renderer::enumerate::PathResult;
+    use renderer::Renderer;
+
+    let text = format.is_text();
+
+    if text {
+        println!("{}", "=".repeat(60).bright_blue());
+        println!(
+            "{} {}",
+            "Enumerating:".bright_cyan().bold(),
+            section.bright_white()
+        );
+        println!("{}", "=".repeat(60).bright_blue());
+        println!();
+    }
+
+    let loaded = match load_package_with_dependencies(&path) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            print_failure("Failed to load package", &e);
+            return Err(1);
+        }
+    };
+
+    // The seed is irrelevant: every outcome is walked
+    let renderer = Renderer::new_with_dependencies(&loaded.package, &loaded.dependencies, 0)
+        .with_bypass_filters(bypass_filters);
+
+    let enumeration = match renderer.enumerate(&section) {
+        Ok(enumeration) => enumeration,
+        Err(e) => {
+            print_failure("Render failed", &e);
+            return Err(1);
+        }
+    };
+
+    if count_only {
+        let summary = enumeration.summarize(Some(limit));
+        if !text {
+            return emit(format, &summary);
+        }
+
+        println!(
+            "{} {}",
+            "Combinations:".bright_cyan(),
+            (summary.paths + summary.failures)
+                .to_string()
+                .bright_white()
+        );
+        println!(
+            "{} {}",
+            "Distinct outputs:".bright_cyan(),
+            summary.outputs.len().to_string().bright_white()
+        );
+        if summary.failures > 0 {
+            println!(
+                "{} {} ({:.2}% of renders)",
+                "Failing:".bright_cyan(),
+                summary.failures.to_string().red(),
+                summary.failure_probability * 100.0
+            );
+        }
+
+        if !summary.unreachable.is_empty() {
+            println!();
+            println!("{}", "Unreachable values:".yellow().bold());
+            for (reference, values) in &summary.unreachable {
+                println!(
+                    "  {} {}",
+                    format!("{}:", reference).bright_cyan(),
+                    values.join(", ")
+                );
+            }
+        }
+
+        if summary.truncated {
+            println!();
+            println!(
+                "{} stopped after {} combinations; totals are lower bounds",
+                "Note:".yellow(),
+                limit
+            );
+        }
+        return Ok(());
+    }
+
+    let mut total = 0;
+    let mut paths = Vec::new();
+    for path in enumeration {
+        if total == limit {
+            if text {
+                println!();
+                println!("{} stopped after {} combinations", "Note:".yellow(), limit);
+            } else {
+                eprintln!("{} stopped after {} combinations", "Note:".yellow(), limit);
+            }
+            break;
+        }
+        total += 1;
+
+        // jsonl streams one line per combination; json and yaml print one list at the end
+        if !text {
+            if format == OutputFormat::Jsonl {
+                emit(format, &path)?;
+            } else {
+                paths.push(path);
+            }
+            continue;
+        }
+
+        let percent = format!("{:>7.3}%", path.probability * 100.0).bright_black();
+        match path.result {
+            PathResult::Output(output) => println!("{}  {}", percent, output.bright_white()),
+            PathResult::Error(error) => {
+                println!("{}  {} {}", percent, "failed:".red().bold(), error.red())
+            }
+        }
+    }
+
+    if !text {
+        if format != OutputFormat::Jsonl {
+            emit(format, &paths)?;
+        }
+        return Ok(());
+    }
+
+    println!();
+    println!("{}", "-".repeat(60).bright_black());
+    println!(
+        "{} {}",
+        "Total:".bright_cyan(),
+        format!("{} combinations", total).bright_white()
+    );
+
+    Ok(())
+}
+
+/// Probabilities command - exact chance of each datatype value being chosen
+fn probabilities_command(
+    path: PathBuf,
+    section: Option<String>,
+    rulebook: Option<String>,
+    limit: usize,
+    bypass_filters: bool,
+    format: OutputFormat,
+) -> Result<(), i32> {
+    use parser::load_package_with_dependencies;
+    use renderer::Renderer;
+
+    let target = rulebook.clone().or(section).unwrap_or_default();
+
+    if format.is_text() {
+        println!("{}", "=".repeat(60).bright_blue());
+        println!(
+            "{} {}",
+            "Probabilities:".bright_cyan().bold(),
+            target.bright_white()
+        );
+        println!("{}", "=".repeat(60).bright_blue());
+        println!();
+    }
+
+    let loaded = match load_package_with_dependencies(&path) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            print_failure("Failed to load package", &e);
+            return Err(1);
+        }
+    };
+
+    let renderer = Renderer::new_with_dependencies(&loaded.package, &loaded.dependencies, 0)
+        .with_bypass_filters(bypass_filters);
+
+    let report = if rulebook.is_some() {
+        renderer.rulebook_value_probabilities(&target, Some(limit))
+    } else {
+        renderer.value_probabilities(&target, Some(limit))
+    };
+    let report = match report {
+        Ok(report) => report,
+        Err(e) => {
+            print_failure("Render failed", &e);
+            return Err(1);
+        }
+    };
+
+    if !format.is_text() {
+        return emit(format, &report);
+    }
+
+    if rulebook.is_some() {
+        println!("{}", "Entry points:".bright_cyan().bold());
+        for (section, probability) in &report.sections {
+            println!("  {:>7.3}%  {}", probability * 100.0, section);
+        }
+        println!();
+    }
+
+    for (datatype, values) in &report.datatypes {
+        println!("{}", datatype.bright_cyan().bold());
+        for value in values {
+            let percent = format!("{:>7.3}%", value.probability * 100.0);
+            let weight = format!("(weight {})", value.weight).bright_black();
+            if value.probability == 0.0 {
+                println!(
+                    "  {}  {} {} {}",
+                    percent.yellow(),
+                    value.text,
+                    weight,
+                    "never chosen".yellow()
+                );
+            } else {
+                println!("  {}  {} {}", percent, value.text.bright_white(), weight);
+            }
+        }
+        println!();
+    }
+
+    println!("{}", "-".repeat(60).bright_black());
+    let never = report.zero_probability().len();
+    if never > 0 {
+        println!(
+            "{} {}",
+            "Never chosen:".yellow(),
+            format!("{} value(s)", never).yellow()
+        );
+    }
+    if report.failure_probability > 0.0 {
+        println!(
+            "{} {:.2}% of renders",
+            "Failing:".bright_cyan(),
+            report.failure_probability * 100.0
+        );
+    }
+    if report.truncated {
+        println!(
+            "{} stopped after {} combinations; probabilities are lower bounds",
+            "Note:".yellow(),
+            limit
+        );
+    }
+
+    Ok(())
+}
+
+/// Stats command - sample many seeds and report what came out
+fn stats_command(
+    path: PathBuf,
+    section: Option<String>,
+    rulebook: Option<String>,
+    samples: usize,
+    seed: u64,
+    bypass_filters: bool,
+    format: OutputFormat,
+) -> Result<(), i32> {
+    use parser::load_package_with_dependencies;
+    use renderer::Renderer;
+
+    let target = rulebook.clone().or(section).unwrap_or_default();
+    if format.is_text() {
+        println!("{}", "=".repeat(60).bright_blue());
+        println!(
+            "{} {}",
+            "Stats:".bright_cyan().bold(),
+            target.bright_white()
+        );
+        println!("{}", "=".repeat(60).bright_blue());
+        println!();
+    }
+
+    let loaded = match load_package_with_dependencies(&path) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            print_failure("Failed to load package", &e);
+            return Err(1);
+        }
+    };
+
+    let renderer = Renderer::new_with_dependencies(&loaded.package, &loaded.dependencies, seed)
+        .with_bypass_filters(bypass_filters);
+
+    let report = if rulebook.is_some() {
+        match renderer.sample_rulebook(&target, samples) {
+            Ok(report) => report,
+            Err(e) => {
+                print_failure("Render failed", &e);
+                return Err(1);
+            }
+        }
+    } else {
+        renderer.sample(&target, samples)
+    };
+
+    if !format.is_text() {
+        return emit(format, &report);
+    }
+
+    println!(
+        "{} {} (seeds {}..{})",
+        "Samples:".bright_cyan(),
+        report.samples.to_string().bright_white(),
+        report.first_seed,
+        report.first_seed.wrapping_add(report.samples as u64)
+    );
+    println!();
+
+    let percent = |count: usize, total: usize| {
+        if total == 0 {
+            0.0
+        } else {
+            count as f64 * 100.0 / total as f64
+        }
+    };
+
+    if rulebook.is_some() {
+        println!("{}", "Entry points:".bright_cyan().bold());
+        for (section, count) in &report.sections {
+            println!(
+                "  {:>7.2}%  {:>6}  {}",
+                percent(*count, report.samples),
+                count,
+                section
+            );
+        }
+        println!();
+    }
+
+    for (reference, values) in &report.references {
+        let total: usize = values.values().sum();
+        println!("{}", reference.bright_cyan().bold());
+        for (text, count) in values {
+            println!(
+                "  {:>7.2}%  {:>6}  {}",
+                percent(*count, total),
+                count.to_string().bright_black(),
+                text.bright_white()
+            );
+        }
+        println!();
+    }
+
+    println!("{}", "-".repeat(60).bright_black());
+    println!(
+        "{} {}",
+        "Distinct outputs:".bright_cyan(),
+        report.distinct_outputs.to_string().bright_white()
+    );
+    println!(
+        "{} {:.2}%",
+        "Duplicate rate:".bright_cyan(),
+        report.duplicate_rate * 100.0
+    );
+    if report.failures == 0 {
+        println!("{} {}", "Failures:".bright_cyan(), "none".green());
+    } else {
+        println!(
+            "{} {} ({:.2}%)",
+            "Failures:".bright_cyan(),
+            report.failures.to_string().red(),
+            report.failure_rate * 100.0
+        );
+        for (kind, count) in &report.failures_by_kind {
+            println!("  {} {}", format!("{}:", kind).red(), count);
+        }
     }
 
     Ok(())
 }
+
+/// Render observer that logs progress to stderr (keeps stdout for prompts)
+struct CliObserver {
+    level: u8,
+}
+
+impl renderer::observer::RenderObserver for CliObserver {
+    fn phase_started(
+        &self,
+        promptsection: &str,
+        phase: renderer::observer::RenderPhase,
+        depth: usize,
+    ) {
+        if self.level >= 1 {
+            eprintln!(
+                "{}{} {} {}",
+                "  ".repeat(depth),
+                "===".bright_black(),
+                phase.to_string().bright_blue(),
+                promptsection.bright_black()
+            );
+        }
+    }
+
+    fn values_selected(
+        &self,
+        reference: &str,
+        target: &str,
+        values: &[renderer::selector::SelectedValue],
+    ) {
+        if self.level >= 2 {
+            let texts: Vec<&str> = values.iter().map(|v| v.text.as_str()).collect();
+            eprintln!(
+                "    {} {} -> {}: {}",
+                "select".bright_cyan(),
+                reference,
+                target.bright_black(),
+                texts.join(", ")
+            );
+        }
+    }
+
+    fn rule_executed(&self, rule: &str, outcome: &rules::processor::RuleOutcome) {
+        if self.level >= 2 {
+            eprintln!(
+                "    {} {}: {}",
+                "rule".magenta(),
+                rule,
+                describe_outcome(outcome)
+            );
+        }
+    }
+
+    fn decision_output(&self, decision: &str, key: &str) {
+        if self.level >= 2 {
+            eprintln!("    {} {} -> {}", "decision".magenta(), decision, key);
+        }
+    }
+}
+
+/// Describe what a rule did, for trace and verbose output
+fn describe_outcome(outcome: &rules::processor::RuleOutcome) -> ColoredString {
+    use rules::processor::RuleOutcome;
+
+    match outcome {
+        RuleOutcome::Applied { key } => format!("applied -> {}", key).green(),
+        RuleOutcome::Shadowed { key } => format!("shadowed ({} already set)", key).yellow(),
+        RuleOutcome::ConditionFalse => "condition false".bright_black(),
+        RuleOutcome::ReferenceMissing => "reference not selected".bright_black(),
+    }
+}
+
+/// Display a render trace as an indented tree
+fn display_trace(trace: &renderer::trace::SectionTrace, indent: usize) {
+    let pad = "  ".repeat(indent);
+    println!(
+        "{}{} {} {}",
+        pad,
+        "section".bright_blue(),
+        trace.promptsection.bright_white().bold(),
+        format!("(seed {})", trace.seed).bright_black()
+    );
+
+    for reference in &trace.references {
+        let filter = reference
+            .filter
+            .as_ref()
+            .map(|f| format!(" where {}", f))
+            .unwrap_or_default();
+        println!(
+            "{}  {} {} -> {}{} {}",
+            pad,
+            "ref".bright_cyan(),
+            reference.name.bright_white(),
+            reference.target,
+            filter.yellow(),
+            format!("[{} candidate(s)]", reference.candidates).bright_black()
+        );
+        if let Some(fallback) = &reference.fallback {
+            println!(
+                "{}    {} {}",
+                pad,
+                "filter matched nothing, fallback:".yellow(),
+                fallback
+            );
+        }
+
+        match &reference.section {
+            Some(section) => display_trace(section, indent + 2),
+            None => {
+                for chosen in &reference.chosen {
+                    let tags = if chosen.tags.is_empty() {
+                        String::new()
+                    } else {
+                        let mut tags: Vec<String> = chosen
+                            .tags
+                            .iter()
+                            .map(|(k, v)| format!("{}={}", k, v))
+                            .collect();
+                        tags.sort();
+                        format!(" {{{}}}", tags.join(", "))
+                    };
+                    println!(
+                        "{}    = {} {}{}",
+                        pad,
+                        chosen.text.green(),
+                        format!("(weight {})", chosen.weight).bright_black(),
+                        tags.bright_black()
+                    );
+                }
+            }
+        }
+    }
+
+    for rule in &trace.rules {
+        let outcome = describe_outcome(&rule.outcome);
+        println!("{}  {} {}: {}", pad, "rule".magenta(), rule.rule, outcome);
+    }
+
+    for write in &trace.context_writes {
+        println!(
+            "{}  {} {} = {} {}",
+            pad,
+            "context".magenta(),
+            write.key.bright_white(),
+            write.value.green(),
+            format!("({})", write.source).bright_black()
+        );
+    }
+
+    println!("{}  {} {}", pad, "output:".bright_cyan(), trace.output);
+}
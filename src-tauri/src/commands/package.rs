@@ -70,19 +70,19 @@ pub async fn create_package(
     namespace_id: String,
 ) -> Result<Package, String> {
     use crate::core::{Namespace, PackageMetadata};
-    use std::collections::HashMap;
+    use indexmap::IndexMap;
 
-    let mut namespaces = HashMap::new();
+    let mut namespaces = IndexMap::new();
     namespaces.insert(
         namespace_id.clone(),
         Namespace {
-            id: namespace_id,
-            datatypes: HashMap::new(),
-            prompt_sections: HashMap::new(),
-            separator_sets: HashMap::new(),
-            rules: HashMap::new(),
-            decisions: Vec::new(),     // M7: Initialize empty decisions
-            rulebooks: HashMap::new(), // M9: Initialize empty rulebooks
+            id: namespace_id.clone(),
+            datatypes: IndexMap::new(),
+            prompt_sections: IndexMap::new(),
+            separator_sets: IndexMap::new(),
+            rules: IndexMap::new(),
+            decisions: Vec::new(),      // M7: Initialize empty decisions
+            rulebooks: IndexMap::new(), // M9: Initialize empty rulebooks
         },
     );
 
@@ -96,6 +96,7 @@ pub async fn create_package(
             bypass_filters: false,
         },
         namespaces,
+        default_namespace: Some(namespace_id),
         dependencies: Vec::new(),
     })
 }
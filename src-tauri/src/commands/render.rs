@@ -2,9 +2,17 @@
 // M9 Task 1.5: Added rulebook rendering commands
 // M9 Phase 3: Added dependencies support for cross-package rendering
 // Bridge between Vue frontend and Rust rendering engine
+//
+// Single-render commands take an optional `trace` flag; when set, the result
+// carries a structured render trace (see renderer::trace).
+// All render commands take an optional `bypass_filters` flag ("chaos mode"):
+// when set, every tag filter is ignored.
+// Commands with dependencies render package-qualified copies of their inputs
+// (see parser::qualify_references).
 
 use crate::core::Package;
-use crate::renderer::Renderer;
+use crate::parser::qualify_references;
+use crate::renderer::{Renderer, RulebookBatch};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -14,8 +22,12 @@ pub async fn render_prompt(
     package: Package,
     promptsection: String,
     seed: u64,
+    trace: Option<bool>,
+    bypass_filters: Option<bool>,
 ) -> Result<RenderResult, String> {
-    let renderer = Renderer::new(&package, seed);
+    let renderer = Renderer::new(&package, seed)
+        .with_trace(trace.unwrap_or(false))
+        .with_bypass_filters(bypass_filters.unwrap_or(false));
     renderer
         .render(&promptsection)
         .map_err(|e| format!("Render error: {}", e))
@@ -28,8 +40,14 @@ pub async fn render_prompt_with_dependencies(
     dependencies: HashMap<String, Package>,
     promptsection: String,
     seed: u64,
+    trace: Option<bool>,
+    bypass_filters: Option<bool>,
 ) -> Result<RenderResult, String> {
-    let renderer = Renderer::new_with_dependencies(&package, &dependencies, seed);
+    let (package, dependencies) =
+        qualify_references(&package, &dependencies).map_err(|e| format!("Render error: {}", e))?;
+    let renderer = Renderer::new_with_dependencies(&package, &dependencies, seed)
+        .with_trace(trace.unwrap_or(false))
+        .with_bypass_filters(bypass_filters.unwrap_or(false));
     renderer
         .render(&promptsection)
         .map_err(|e| format!("Render error: {}", e))
@@ -41,8 +59,12 @@ pub async fn render_from_rulebook(
     package: Package,
     rulebook_ref: String,
     seed: u64,
+    trace: Option<bool>,
+    bypass_filters: Option<bool>,
 ) -> Result<RenderResult, String> {
-    let renderer = Renderer::new(&package, seed);
+    let renderer = Renderer::new(&package, seed)
+        .with_trace(trace.unwrap_or(false))
+        .with_bypass_filters(bypass_filters.unwrap_or(false));
     renderer
         .render_from_rulebook(&rulebook_ref)
         .map_err(|e| format!("Render error: {}", e))
@@ -55,8 +77,14 @@ pub async fn render_from_rulebook_with_dependencies(
     dependencies: HashMap<String, Package>,
     rulebook_ref: String,
     seed: u64,
+    trace: Option<bool>,
+    bypass_filters: Option<bool>,
 ) -> Result<RenderResult, String> {
-    let renderer = Renderer::new_with_dependencies(&package, &dependencies, seed);
+    let (package, dependencies) =
+        qualify_references(&package, &dependencies).map_err(|e| format!("Render error: {}", e))?;
+    let renderer = Renderer::new_with_dependencies(&package, &dependencies, seed)
+        .with_trace(trace.unwrap_or(false))
+        .with_bypass_filters(bypass_filters.unwrap_or(false));
     renderer
         .render_from_rulebook(&rulebook_ref)
         .map_err(|e| format!("Render error: {}", e))
@@ -69,18 +97,16 @@ pub async fn render_from_rulebook_batch(
     rulebook_ref: String,
     count: usize,
     start_seed: u64,
+    bypass_filters: Option<bool>,
 ) -> Result<BatchRenderResponse, String> {
     let mut results = Vec::new();
-    let mut used_entry_points = Vec::new();
+    let mut batch = RulebookBatch::new(start_seed);
 
     for i in 0..count {
-        let seed = start_seed.wrapping_add(i as u64);
-        let renderer = Renderer::new(&package, seed);
-
-        match renderer
-            .render_from_rulebook_with_options(&rulebook_ref, Some(&mut used_entry_points))
-        {
-            Ok(result) => {
+        match batch.render(i, &rulebook_ref, |seed| {
+            Renderer::new(&package, seed).with_bypass_filters(bypass_filters.unwrap_or(false))
+        }) {
+            Ok((result, _)) => {
                 results.push(BatchRenderResult {
                     output: result.output,
                     seed: result.seed,
@@ -104,18 +130,19 @@ pub async fn render_from_rulebook_batch_with_dependencies(
     rulebook_ref: String,
     count: usize,
     start_seed: u64,
+    bypass_filters: Option<bool>,
 ) -> Result<BatchRenderResponse, String> {
+    let (package, dependencies) =
+        qualify_references(&package, &dependencies).map_err(|e| format!("Render error: {}", e))?;
     let mut results = Vec::new();
-    let mut used_entry_points = Vec::new();
+    let mut batch = RulebookBatch::new(start_seed);
 
     for i in 0..count {
-        let seed = start_seed.wrapping_add(i as u64);
-        let renderer = Renderer::new_with_dependencies(&package, &dependencies, seed);
-
-        match renderer
-            .render_from_rulebook_with_options(&rulebook_ref, Some(&mut used_entry_points))
-        {
-            Ok(result) => {
+        match batch.render(i, &rulebook_ref, |seed| {
+            Renderer::new_with_dependencies(&package, &dependencies, seed)
+                .with_bypass_filters(bypass_filters.unwrap_or(false))
+        }) {
+            Ok((result, _)) => {
                 results.push(BatchRenderResult {
                     output: result.output,
                     seed: result.seed,
@@ -150,5 +177,103 @@ pub use crate::renderer::engine::RenderResult;
 
 #[cfg(test)]
 mod tests {
-    // Tests will use the renderer tests directly
+    // Render logic is tested with the renderer; these check the commands agree
+    // with the CLI
+    use super::*;
+    use crate::parser::{load_package, load_package_with_dependencies};
+
+    const PACKAGE: &str = r#"
+id: test.batch
+version: 1.0.0
+metadata:
+  name: Batch
+  authors: []
+namespaces:
+  test:
+    id: test
+    datatypes:
+      colors:
+        name: colors
+        values:
+          - text: red
+          - text: blue
+    prompt_sections:
+      one:
+        name: one
+        template: "one {colors}"
+      two:
+        name: two
+        template: "two {colors}"
+      three:
+        name: three
+        template: "three {colors}"
+    rulebooks:
+      book:
+        name: book
+        entry_points:
+          - prompt_section: test:one
+          - prompt_section: test:two
+          - prompt_section: test:three
+        batch_variety: BATCH_VARIETY
+"#;
+
+    #[test]
+    fn test_rulebook_batch_matches_cli() {
+        for batch_variety in [true, false] {
+            let dir = tempfile::TempDir::new().unwrap();
+            let path = dir.path().join("batch.yaml");
+            let yaml = PACKAGE.replace("BATCH_VARIETY", &batch_variety.to_string());
+            std::fs::write(&path, yaml).unwrap();
+
+            // What `rpg-cli render batch.yaml --rulebook test:book --seed 100 --count 6` renders
+            let loaded = load_package_with_dependencies(&path).unwrap();
+            let mut batch = RulebookBatch::new(100);
+            let cli: Vec<(String, u64, String)> = (0..6)
+                .map(|i| {
+                    let (result, entry_point) = batch
+                        .render(i, "test:book", |seed| {
+                            Renderer::new_with_dependencies(
+                                &loaded.package,
+                                &loaded.dependencies,
+                                seed,
+                            )
+                        })
+                        .unwrap();
+                    (result.output, result.seed, entry_point)
+                })
+                .collect();
+
+            let response =
+                tauri::async_runtime::block_on(render_from_rulebook_batch_with_dependencies(
+                    load_package(&path).unwrap(),
+                    HashMap::new(),
+                    "test:book".to_string(),
+                    6,
+                    100,
+                    None,
+                ))
+                .unwrap();
+            let commands: Vec<(String, u64)> = response
+                .results
+                .into_iter()
+                .map(|result| (result.output, result.seed))
+                .collect();
+            let expected: Vec<(String, u64)> = cli
+                .iter()
+                .map(|(output, seed, _)| (output.clone(), *seed))
+                .collect();
+            assert_eq!(commands, expected, "batch_variety: {}", batch_variety);
+
+            for (output, _, entry_point) in &cli {
+                let section = output.split(' ').next().unwrap();
+                assert_eq!(entry_point, &format!("test:{}", section));
+            }
+            if batch_variety {
+                let mut first: Vec<&String> = cli[..3].iter().map(|(_, _, entry)| entry).collect();
+                first.sort();
+                first.dedup();
+                assert_eq!(first.len(), 3);
+            }
+        }
+    }
 }
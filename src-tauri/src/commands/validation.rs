@@ -1,6 +1,7 @@
 // M7 Phase 3: Validation Commands
 
 use crate::core::models::Package;
+use crate::renderer::transforms::TransformRegistry;
 use crate::validator::{PackageValidator, ValidationError};
 use serde::Serialize;
 use tauri::command;
@@ -31,11 +32,51 @@ impl From<ValidationError> for ErrorInfo {
                 location: Some(defined_in),
                 suggestion,
             },
+            ValidationError::UnresolvableReference {
+                reference,
+                defined_in,
+                reason,
+            } => ErrorInfo {
+                message: format!("Reference '{}' can't be resolved: {}", reference, reason),
+                location: Some(defined_in),
+                suggestion: Some(
+                    "Prefix the reference with its package id (package-id/namespace:name)"
+                        .to_string(),
+                ),
+            },
             ValidationError::CircularReference { chain } => ErrorInfo {
                 message: format!("Circular reference detected: {}", chain),
                 location: None,
                 suggestion: Some("Break the circular dependency".to_string()),
             },
+            ValidationError::CircularExtends { chain } => ErrorInfo {
+                message: format!("Circular extends detected: {}", chain),
+                location: None,
+                suggestion: Some("Remove one of the extends links in the chain".to_string()),
+            },
+            ValidationError::InvalidTemplate {
+                promptsection,
+                reason,
+            } => ErrorInfo {
+                message: format!("Invalid template in {}: {}", promptsection, reason),
+                location: Some(promptsection),
+                suggestion: Some("Check template syntax (unclosed or empty braces)".to_string()),
+            },
+            ValidationError::UnknownTransform {
+                transform,
+                reference,
+                promptsection,
+            } => ErrorInfo {
+                message: format!(
+                    "Unknown transform '{}' on '{}' in {}",
+                    transform, reference, promptsection
+                ),
+                location: Some(promptsection),
+                suggestion: Some(format!(
+                    "Use one of: {}",
+                    TransformRegistry::builtin().names().join(", ")
+                )),
+            },
             ValidationError::InvalidTagFilter { expression, reason } => ErrorInfo {
                 message: format!("Invalid tag filter '{}': {}", expression, reason),
                 location: None,
@@ -75,6 +116,11 @@ impl From<ValidationError> for ErrorInfo {
                 location: Some(rule_name),
                 suggestion: None,
             },
+            ValidationError::InvalidDecision { decision, reason } => ErrorInfo {
+                message: format!("Invalid decision '{}': {}", decision, reason),
+                location: Some(decision),
+                suggestion: None,
+            },
             ValidationError::DuplicateId { id, namespace } => ErrorInfo {
                 message: format!("Duplicate ID '{}' in namespace '{}'", id, namespace),
                 location: Some(namespace.clone()),
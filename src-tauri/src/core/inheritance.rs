@@ -0,0 +1,296 @@
+// Datatype inheritance
+// Resolves `extends` chains across namespaces and dependency packages
+
+use crate::core::lookup::{find_namespace, LookupError};
+use crate::core::models::{Datatype, DatatypeValue, Package};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum InheritanceError {
+    #[error("Datatype not found: {0}")]
+    NotFound(String),
+
+    #[error("Circular extends: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+
+    #[error("{0}")]
+    Lookup(#[from] LookupError),
+}
+
+/// Split an extends target into (namespace, name)
+///
+/// Bare names resolve in the namespace of the extending datatype. The
+/// namespace keeps any package prefix ("package-id/namespace").
+pub fn parse_extends_target(target: &str, current_namespace: &str) -> (String, String) {
+    match target.split_once(':') {
+        Some((namespace, name)) => (namespace.to_string(), name.to_string()),
+        None => (current_namespace.to_string(), target.to_string()),
+    }
+}
+
+/// Find a datatype by namespace and name in the package, then its dependencies
+///
+/// See `lookup::find_namespace` for package prefixes and ambiguity.
+pub fn find_datatype<'a>(
+    package: &'a Package,
+    dependencies: Option<&'a HashMap<String, Package>>,
+    namespace: &str,
+    name: &str,
+) -> Result<Option<&'a Datatype>, LookupError> {
+    let found = find_namespace(package, dependencies, namespace, name, |ns| {
+        ns.datatypes.contains_key(name)
+    })?;
+    Ok(found.and_then(|(_, ns)| ns.datatypes.get(name)))
+}
+
+/// Resolve the effective values of a datatype, including inherited ones
+///
+/// Inherited values come first (with `override_tags` applied on top of their
+/// tags), followed by the datatype's own values. An own value with the same
+/// text as an inherited one replaces it.
+pub fn resolve_datatype_values(
+    package: &Package,
+    dependencies: Option<&HashMap<String, Package>>,
+    namespace: &str,
+    name: &str,
+) -> Result<Vec<DatatypeValue>, InheritanceError> {
+    let mut chain = Vec::new();
+    resolve_recursive(package, dependencies, namespace, name, &mut chain)
+}
+
+fn resolve_recursive(
+    package: &Package,
+    dependencies: Option<&HashMap<String, Package>>,
+    namespace: &str,
+    name: &str,
+    chain: &mut Vec<String>,
+) -> Result<Vec<DatatypeValue>, InheritanceError> {
+    let full_name = format!("{}:{}", namespace, name);
+    if chain.contains(&full_name) {
+        let mut cycle = chain.clone();
+        cycle.push(full_name);
+        return Err(InheritanceError::Cycle(cycle));
+    }
+
+    let datatype = find_datatype(package, dependencies, namespace, name)?
+        .ok_or_else(|| InheritanceError::NotFound(full_name.clone()))?;
+
+    let Some(parent) = &datatype.extends else {
+        return Ok(datatype.values.clone());
+    };
+
+    chain.push(full_name);
+    let (parent_ns, parent_name) = parse_extends_target(parent, namespace);
+    let mut values = resolve_recursive(package, dependencies, &parent_ns, &parent_name, chain)?;
+    chain.pop();
+
+    for value in &mut values {
+        for (tag, tag_value) in &datatype.override_tags {
+            value.tags.insert(tag.clone(), tag_value.clone());
+        }
+    }
+
+    for own in &datatype.values {
+        match values.iter_mut().find(|v| v.text == own.text) {
+            Some(inherited) => *inherited = own.clone(),
+            None => values.push(own.clone()),
+        }
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::models::{Namespace, PackageMetadata};
+    use indexmap::IndexMap;
+    use serde_json::json;
+
+    fn value(text: &str, tags: &[(&str, serde_json::Value)]) -> DatatypeValue {
+        DatatypeValue {
+            text: text.to_string(),
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+            weight: 1.0,
+        }
+    }
+
+    fn datatype(name: &str, extends: Option<&str>, values: Vec<DatatypeValue>) -> Datatype {
+        Datatype {
+            name: name.to_string(),
+            values,
+            extends: extends.map(str::to_string),
+            override_tags: IndexMap::new(),
+        }
+    }
+
+    fn package(id: &str, namespace: &str, datatypes: Vec<Datatype>) -> Package {
+        let mut namespaces = IndexMap::new();
+        namespaces.insert(
+            namespace.to_string(),
+            Namespace {
+                id: namespace.to_string(),
+                datatypes: datatypes
+                    .into_iter()
+                    .map(|dt| (dt.name.clone(), dt))
+                    .collect(),
+                prompt_sections: IndexMap::new(),
+                separator_sets: IndexMap::new(),
+                rules: IndexMap::new(),
+                decisions: Vec::new(),
+                rulebooks: IndexMap::new(),
+            },
+        );
+
+        Package {
+            id: id.to_string(),
+            version: "1.0.0".to_string(),
+            metadata: PackageMetadata {
+                name: id.to_string(),
+                description: None,
+                authors: Vec::new(),
+                bypass_filters: false,
+            },
+            namespaces,
+            default_namespace: None,
+            dependencies: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_no_extends_returns_own_values() {
+        let pkg = package(
+            "test",
+            "test",
+            vec![datatype("colors", None, vec![value("red", &[])])],
+        );
+
+        let values = resolve_datatype_values(&pkg, None, "test", "colors").unwrap();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].text, "red");
+    }
+
+    #[test]
+    fn test_extends_merges_parent_values_with_overrides() {
+        let mut child = datatype(
+            "fantasy_creatures",
+            Some("creatures"),
+            vec![value("dragon", &[("can_fly", json!(true))])],
+        );
+        child
+            .override_tags
+            .insert("magical".to_string(), json!(true));
+
+        let pkg = package(
+            "test",
+            "test",
+            vec![
+                datatype(
+                    "creatures",
+                    None,
+                    vec![
+                        value("cat", &[("magical", json!(false))]),
+                        value("dog", &[]),
+                    ],
+                ),
+                child,
+            ],
+        );
+
+        let values = resolve_datatype_values(&pkg, None, "test", "fantasy_creatures").unwrap();
+        let texts: Vec<&str> = values.iter().map(|v| v.text.as_str()).collect();
+        assert_eq!(texts, vec!["cat", "dog", "dragon"]);
+
+        // Overrides apply to inherited values only
+        assert_eq!(values[0].tags.get("magical"), Some(&json!(true)));
+        assert_eq!(values[1].tags.get("magical"), Some(&json!(true)));
+        assert_eq!(values[2].tags.get("magical"), None);
+    }
+
+    #[test]
+    fn test_own_value_replaces_inherited() {
+        let mut own = value("cat", &[("size", json!("huge"))]);
+        own.weight = 5.0;
+
+        let pkg = package(
+            "test",
+            "test",
+            vec![
+                datatype(
+                    "creatures",
+                    None,
+                    vec![value("cat", &[]), value("dog", &[])],
+                ),
+                datatype("big_creatures", Some("test:creatures"), vec![own]),
+            ],
+        );
+
+        let values = resolve_datatype_values(&pkg, None, "test", "big_creatures").unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].text, "cat");
+        assert_eq!(values[0].weight, 5.0);
+        assert_eq!(values[0].tags.get("size"), Some(&json!("huge")));
+    }
+
+    #[test]
+    fn test_extends_across_dependency() {
+        let base = package(
+            "base.pkg",
+            "base",
+            vec![datatype("animals", None, vec![value("swan", &[])])],
+        );
+        let main = package(
+            "main.pkg",
+            "main",
+            vec![datatype(
+                "mutants",
+                Some("base:animals"),
+                vec![value("griffin", &[])],
+            )],
+        );
+
+        let mut deps = HashMap::new();
+        deps.insert(base.id.clone(), base);
+
+        let values = resolve_datatype_values(&main, Some(&deps), "main", "mutants").unwrap();
+        let texts: Vec<&str> = values.iter().map(|v| v.text.as_str()).collect();
+        assert_eq!(texts, vec!["swan", "griffin"]);
+    }
+
+    #[test]
+    fn test_extends_cycle_detected() {
+        let pkg = package(
+            "test",
+            "test",
+            vec![
+                datatype("a", Some("b"), vec![]),
+                datatype("b", Some("test:a"), vec![]),
+            ],
+        );
+
+        let result = resolve_datatype_values(&pkg, None, "test", "a");
+        assert_eq!(
+            result.unwrap_err(),
+            InheritanceError::Cycle(vec![
+                "test:a".to_string(),
+                "test:b".to_string(),
+                "test:a".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_missing_parent() {
+        let pkg = package("test", "test", vec![datatype("a", Some("missing"), vec![])]);
+
+        let result = resolve_datatype_values(&pkg, None, "test", "a");
+        assert_eq!(
+            result.unwrap_err(),
+            InheritanceError::NotFound("test:missing".to_string())
+        );
+    }
+}
@@ -0,0 +1,184 @@
+// Component lookup
+// Resolves namespaces across a package and its dependencies
+//
+// A namespace may carry a package prefix: "package-id/namespace". Without a
+// prefix the main package wins; otherwise the namespace must be provided by
+// exactly one dependency, or the lookup is ambiguous.
+
+use crate::core::models::{Namespace, Package};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum LookupError {
+    #[error("Package not found: '{0}'")]
+    PackageNotFound(String),
+
+    #[error(
+        "Ambiguous reference '{reference}': provided by packages {}; qualify it as 'package-id/{reference}'",
+        .packages.join(", ")
+    )]
+    Ambiguous {
+        reference: String,
+        packages: Vec<String>,
+    },
+}
+
+/// Split an optional package prefix off a namespace
+///
+/// "test.base/common" -> (Some("test.base"), "common"), "common" -> (None, "common")
+pub fn split_package(namespace: &str) -> (Option<&str>, &str) {
+    match namespace.split_once('/') {
+        Some((package_id, namespace)) => (Some(package_id), namespace),
+        None => (None, namespace),
+    }
+}
+
+/// Find the package and namespace providing a component
+///
+/// `has` decides whether a namespace contains the component (e.g. a datatype
+/// of a given name). Returns `Ok(None)` when nothing provides it.
+pub fn find_namespace<'a>(
+    package: &'a Package,
+    dependencies: Option<&'a HashMap<String, Package>>,
+    namespace: &str,
+    name: &str,
+    has: impl Fn(&Namespace) -> bool,
+) -> Result<Option<(&'a Package, &'a Namespace)>, LookupError> {
+    let (package_id, namespace) = split_package(namespace);
+    let provided_by = |owner: &'a Package| {
+        owner
+            .namespaces
+            .get(namespace)
+            .filter(|ns| has(ns))
+            .map(|ns| (owner, ns))
+    };
+
+    if let Some(package_id) = package_id {
+        let owner = if package_id == package.id {
+            Some(package)
+        } else {
+            dependencies.and_then(|deps| deps.get(package_id))
+        };
+        let owner = owner.ok_or_else(|| LookupError::PackageNotFound(package_id.to_string()))?;
+        return Ok(provided_by(owner));
+    }
+
+    // Main package namespaces take precedence over dependencies
+    if let Some(found) = provided_by(package) {
+        return Ok(Some(found));
+    }
+
+    let mut found: Vec<(&Package, &Namespace)> = dependencies
+        .into_iter()
+        .flat_map(|deps| deps.values())
+        .filter_map(provided_by)
+        .collect();
+
+    if found.len() > 1 {
+        let mut packages: Vec<String> = found.iter().map(|(pkg, _)| pkg.id.clone()).collect();
+        packages.sort();
+        return Err(LookupError::Ambiguous {
+            reference: format!("{}:{}", namespace, name),
+            packages,
+        });
+    }
+
+    Ok(found.pop())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::models::PackageMetadata;
+    use indexmap::IndexMap;
+
+    fn package(id: &str, namespaces: &[&str]) -> Package {
+        Package {
+            id: id.to_string(),
+            version: "1.0.0".to_string(),
+            metadata: PackageMetadata {
+                name: id.to_string(),
+                description: None,
+                authors: Vec::new(),
+                bypass_filters: false,
+            },
+            namespaces: namespaces
+                .iter()
+                .map(|ns| {
+                    (
+                        ns.to_string(),
+                        Namespace {
+                            id: ns.to_string(),
+                            datatypes: IndexMap::new(),
+                            prompt_sections: IndexMap::new(),
+                            separator_sets: IndexMap::new(),
+                            rules: IndexMap::new(),
+                            decisions: Vec::new(),
+                            rulebooks: IndexMap::new(),
+                        },
+                    )
+                })
+                .collect(),
+            default_namespace: None,
+            dependencies: Vec::new(),
+        }
+    }
+
+    fn owner(
+        main: &Package,
+        deps: &HashMap<String, Package>,
+        namespace: &str,
+    ) -> Result<Option<String>, LookupError> {
+        find_namespace(main, Some(deps), namespace, "colors", |_| true)
+            .map(|found| found.map(|(pkg, _)| pkg.id.clone()))
+    }
+
+    #[test]
+    fn test_split_package() {
+        assert_eq!(split_package("a.b/common"), (Some("a.b"), "common"));
+        assert_eq!(split_package("common"), (None, "common"));
+    }
+
+    #[test]
+    fn test_main_package_wins() {
+        let main = package("main", &["common"]);
+        let mut deps = HashMap::new();
+        deps.insert("dep.a".to_string(), package("dep.a", &["common"]));
+        deps.insert("dep.b".to_string(), package("dep.b", &["common"]));
+
+        assert_eq!(
+            owner(&main, &deps, "common").unwrap().as_deref(),
+            Some("main")
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_and_prefixed() {
+        let main = package("main", &["scenes"]);
+        let mut deps = HashMap::new();
+        deps.insert("dep.a".to_string(), package("dep.a", &["common"]));
+        deps.insert("dep.b".to_string(), package("dep.b", &["common"]));
+
+        assert_eq!(
+            owner(&main, &deps, "common"),
+            Err(LookupError::Ambiguous {
+                reference: "common:colors".to_string(),
+                packages: vec!["dep.a".to_string(), "dep.b".to_string()],
+            })
+        );
+        assert_eq!(
+            owner(&main, &deps, "dep.b/common").unwrap().as_deref(),
+            Some("dep.b")
+        );
+        assert_eq!(owner(&main, &deps, "dep.a/scenes").unwrap(), None);
+        assert_eq!(
+            owner(&main, &deps, "main/scenes").unwrap().as_deref(),
+            Some("main")
+        );
+        assert_eq!(
+            owner(&main, &deps, "missing/common"),
+            Err(LookupError::PackageNotFound("missing".to_string()))
+        );
+    }
+}
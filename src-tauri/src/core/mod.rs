@@ -1,5 +1,7 @@
 // M2: Core module - Data models and types
 
+pub mod inheritance;
+pub mod lookup;
 pub mod models;
 pub mod rulebook;
 pub mod version; // M9: Version management
@@ -1,8 +1,8 @@
 // M2: Foundation - Core Data Models
 // Based on M1 decisions (DEC-0001, DEC-0002, DEC-0003)
 
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
 /// Package - Root container for RPG content
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,13 +17,30 @@ pub struct Package {
     pub metadata: PackageMetadata,
 
     /// Namespaces defined in this package
-    pub namespaces: HashMap<String, Namespace>,
+    pub namespaces: IndexMap<String, Namespace>,
+
+    /// Namespace used for unqualified references (see `default_namespace_id`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_namespace: Option<String>,
 
     /// Package dependencies
     #[serde(default)]
     pub dependencies: Vec<Dependency>,
 }
 
+impl Package {
+    /// Namespace for unqualified references ("scene" rather than "ns:scene")
+    ///
+    /// The declared `default_namespace` wins; otherwise the alphabetically
+    /// first namespace id, whatever order the namespaces were declared in.
+    pub fn default_namespace_id(&self) -> Option<&str> {
+        if let Some(declared) = &self.default_namespace {
+            return Some(declared);
+        }
+        self.namespaces.keys().min().map(String::as_str)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PackageMetadata {
     pub name: String,
@@ -31,6 +48,8 @@ pub struct PackageMetadata {
     pub authors: Vec<String>,
 
     /// Optional: Bypass all tag filtering in this package (for absurdist packages)
+    /// Filters on this package's datatypes are ignored, also when another
+    /// package references them
     #[serde(default)]
     pub bypass_filters: bool,
 }
@@ -63,20 +82,19 @@ pub struct Namespace {
 
     /// Datatypes defined in this namespace
     #[serde(default)]
-    pub datatypes: HashMap<String, Datatype>,
+    pub datatypes: IndexMap<String, Datatype>,
 
     /// Prompt sections (templates)
     #[serde(default)]
-    pub prompt_sections: HashMap<String, PromptSection>,
+    pub prompt_sections: IndexMap<String, PromptSection>,
 
     /// Separator sets for list formatting
     #[serde(default)]
-    pub separator_sets: HashMap<String, SeparatorSet>,
+    pub separator_sets: IndexMap<String, SeparatorSet>,
 
-    /// Rules for coordination (M1 Pattern 1, 2)
-    /// Changed from Vec to HashMap to support rule referencing
+    /// Rules for coordination (M1 Pattern 1, 2), keyed by rule ID
     #[serde(default)]
-    pub rules: HashMap<String, Rule>,
+    pub rules: IndexMap<String, Rule>,
 
     /// Decisions for complex logic (M1 Pattern 3)
     #[serde(default)]
@@ -84,7 +102,7 @@ pub struct Namespace {
 
     /// Rulebooks - Entry point wrappers for rendering (M9)
     #[serde(default)]
-    pub rulebooks: HashMap<String, crate::core::rulebook::Rulebook>,
+    pub rulebooks: IndexMap<String, crate::core::rulebook::Rulebook>,
 }
 
 /// Datatype - Collection of selectable values with tags
@@ -98,7 +116,7 @@ pub struct Datatype {
 
     /// Optional: Override tags when extending
     #[serde(default)]
-    pub override_tags: HashMap<String, serde_json::Value>,
+    pub override_tags: IndexMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -108,7 +126,7 @@ pub struct DatatypeValue {
 
     /// Tags for coordination (M1: article, plural, gender, etc.)
     #[serde(default)]
-    pub tags: HashMap<String, serde_json::Value>,
+    pub tags: IndexMap<String, serde_json::Value>,
 
     /// Optional weight for selection probability
     #[serde(default = "default_weight")]
@@ -129,7 +147,7 @@ pub struct PromptSection {
 
     /// Reference definitions
     #[serde(default)]
-    pub references: HashMap<String, Reference>,
+    pub references: IndexMap<String, Reference>,
 }
 
 /// Reference - How to select values in a template
@@ -157,6 +175,86 @@ pub struct Reference {
     /// Whether to enforce uniqueness in multi-selection (M5 Phase 3+4)
     #[serde(default)]
     pub unique: bool,
+
+    /// What to do when the filter matches no values
+    #[serde(default, skip_serializing_if = "Fallback::is_error")]
+    pub fallback: Fallback,
+}
+
+/// Fallback policy for a reference whose filter matches no values
+///
+/// YAML: `fallback: unfiltered`, `fallback: empty` or
+/// `fallback: { default: "plain stone" }`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "FallbackRepr", into = "FallbackRepr")]
+pub enum Fallback {
+    /// Fail the render (the default)
+    #[default]
+    Error,
+
+    /// Select from all values, ignoring the filter
+    Unfiltered,
+
+    /// Select nothing (the reference renders as empty text)
+    Empty,
+
+    /// Use this text as the value
+    Default(String),
+}
+
+impl Fallback {
+    pub fn is_error(&self) -> bool {
+        *self == Fallback::Error
+    }
+}
+
+impl std::fmt::Display for Fallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Fallback::Error => write!(f, "error"),
+            Fallback::Unfiltered => write!(f, "unfiltered"),
+            Fallback::Empty => write!(f, "empty"),
+            Fallback::Default(text) => write!(f, "default \"{}\"", text),
+        }
+    }
+}
+
+/// Serialized form of `Fallback`: a policy name or `{ default: "text" }`
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum FallbackRepr {
+    Policy(String),
+    Default { default: String },
+}
+
+impl TryFrom<FallbackRepr> for Fallback {
+    type Error = String;
+
+    fn try_from(repr: FallbackRepr) -> Result<Self, String> {
+        match repr {
+            FallbackRepr::Policy(policy) => match policy.as_str() {
+                "error" => Ok(Fallback::Error),
+                "unfiltered" => Ok(Fallback::Unfiltered),
+                "empty" => Ok(Fallback::Empty),
+                other => Err(format!(
+                    "unknown fallback '{}' (expected error, unfiltered, empty or {{ default: \"text\" }})",
+                    other
+                )),
+            },
+            FallbackRepr::Default { default } => Ok(Fallback::Default(default)),
+        }
+    }
+}
+
+impl From<Fallback> for FallbackRepr {
+    fn from(fallback: Fallback) -> Self {
+        match fallback {
+            Fallback::Error => FallbackRepr::Policy("error".to_string()),
+            Fallback::Unfiltered => FallbackRepr::Policy("unfiltered".to_string()),
+            Fallback::Empty => FallbackRepr::Policy("empty".to_string()),
+            Fallback::Default(default) => FallbackRepr::Default { default },
+        }
+    }
 }
 
 fn default_min() -> usize {
@@ -177,7 +275,7 @@ pub struct SeparatorSet {
 }
 
 /// Rule - Simple coordination logic (M1 Pattern 1, 2)
-/// Stored in HashMap<String, Rule> where key is the rule ID
+/// Keyed by rule ID in `Namespace::rules`
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Rule {
     /// Field to check (triggers the rule)
@@ -192,6 +290,19 @@ pub struct Rule {
 
     /// Value to write (literal or expression)
     pub value: String,
+
+    /// Execution priority - higher runs earlier (default 0)
+    /// With "first contribution wins", an earlier rule takes precedence
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub priority: i32,
+
+    /// Rules that must run before this one ("rule_id" in the same namespace, or "namespace:rule_id")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<String>,
+}
+
+fn is_zero(value: &i32) -> bool {
+    *value == 0
 }
 
 /// Decision - Complex reusable logic (M1 Pattern 3)
@@ -201,10 +312,15 @@ pub struct Decision {
     pub name: String,
 
     /// Input parameters
-    pub inputs: HashMap<String, String>, // name -> type
+    pub inputs: IndexMap<String, String>, // name -> type
 
     /// Output parameters
-    pub outputs: HashMap<String, String>, // name -> type
+    pub outputs: IndexMap<String, String>, // name -> type
+
+    /// Input sources (e.g., "ref:creature.tags.size", "context.prompt.mood")
+    /// Inputs without a binding are bound by name from selected refs, then context
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub bindings: IndexMap<String, String>, // input name -> source expression
 
     /// Processor type and implementation
     pub processor: Processor,
@@ -229,7 +345,7 @@ pub enum Processor {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConditionalRule {
     pub condition: String,
-    pub output: HashMap<String, serde_json::Value>,
+    pub output: IndexMap<String, serde_json::Value>,
 }
 
 #[cfg(test)]
@@ -240,7 +356,7 @@ mod tests {
     fn test_datatype_value_default_weight() {
         let value = DatatypeValue {
             text: "test".to_string(),
-            tags: HashMap::new(),
+            tags: IndexMap::new(),
             weight: default_weight(),
         };
         assert_eq!(value.weight, 1.0);
@@ -255,8 +371,76 @@ mod tests {
             max: default_max(),
             separator: None,
             unique: false,
+            fallback: Fallback::Error,
         };
         assert_eq!(reference.min, 1);
         assert_eq!(reference.max, 1);
     }
+
+    #[test]
+    fn test_reference_fallback_yaml() {
+        let fallback = |yaml: &str| {
+            serde_yaml::from_str::<Reference>(&format!("target: colors\n{}", yaml))
+                .unwrap()
+                .fallback
+        };
+
+        assert_eq!(fallback(""), Fallback::Error);
+        assert_eq!(fallback("fallback: unfiltered"), Fallback::Unfiltered);
+        assert_eq!(fallback("fallback: empty"), Fallback::Empty);
+        assert_eq!(
+            fallback("fallback:\n  default: plain stone"),
+            Fallback::Default("plain stone".to_string())
+        );
+        assert!(serde_yaml::from_str::<Reference>("target: colors\nfallback: maybe").is_err());
+
+        let mut reference: Reference = serde_yaml::from_str("target: colors").unwrap();
+        assert!(!serde_yaml::to_string(&reference)
+            .unwrap()
+            .contains("fallback"));
+        reference.fallback = Fallback::Default("plain stone".to_string());
+        let yaml = serde_yaml::to_string(&reference).unwrap();
+        assert_eq!(
+            serde_yaml::from_str::<Reference>(&yaml).unwrap().fallback,
+            reference.fallback
+        );
+    }
+
+    #[test]
+    fn test_default_namespace_id() {
+        let namespace = |id: &str| Namespace {
+            id: id.to_string(),
+            datatypes: IndexMap::new(),
+            prompt_sections: IndexMap::new(),
+            separator_sets: IndexMap::new(),
+            rules: IndexMap::new(),
+            decisions: Vec::new(),
+            rulebooks: IndexMap::new(),
+        };
+        let mut package = Package {
+            id: "test".to_string(),
+            version: "1.0.0".to_string(),
+            metadata: PackageMetadata {
+                name: "Test".to_string(),
+                description: None,
+                authors: Vec::new(),
+                bypass_filters: false,
+            },
+            namespaces: ["scenes", "common", "zoo"]
+                .into_iter()
+                .map(|id| (id.to_string(), namespace(id)))
+                .collect(),
+            default_namespace: None,
+            dependencies: Vec::new(),
+        };
+
+        assert_eq!(package.default_namespace_id(), Some("common"));
+
+        package.default_namespace = Some("scenes".to_string());
+        assert_eq!(package.default_namespace_id(), Some("scenes"));
+
+        package.namespaces.clear();
+        package.default_namespace = None;
+        assert_eq!(package.default_namespace_id(), None);
+    }
 }
@@ -1,5 +1,5 @@
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
 
 /// Represents an entry point in a rulebook with its weight
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -35,7 +35,7 @@ pub struct Rulebook {
 
     /// Optional context defaults to set before rendering
     #[serde(default)]
-    pub context_defaults: HashMap<String, String>,
+    pub context_defaults: IndexMap<String, String>,
 }
 
 impl Rulebook {
@@ -104,7 +104,7 @@ mod tests {
                 },
             ],
             batch_variety: false,
-            context_defaults: HashMap::new(),
+            context_defaults: IndexMap::new(),
         };
 
         assert!(rulebook.validate().is_ok());
@@ -120,7 +120,7 @@ mod tests {
                 weight: 1.0,
             }],
             batch_variety: false,
-            context_defaults: HashMap::new(),
+            context_defaults: IndexMap::new(),
         };
 
         assert!(rulebook.validate().is_err());
@@ -137,7 +137,7 @@ mod tests {
             description: "".to_string(),
             entry_points: vec![],
             batch_variety: false,
-            context_defaults: HashMap::new(),
+            context_defaults: IndexMap::new(),
         };
 
         assert!(rulebook.validate().is_err());
@@ -157,7 +157,7 @@ mod tests {
                 weight: -1.0,
             }],
             batch_variety: false,
-            context_defaults: HashMap::new(),
+            context_defaults: IndexMap::new(),
         };
 
         assert!(rulebook.validate().is_err());
@@ -180,7 +180,7 @@ mod tests {
                 },
             ],
             batch_variety: false,
-            context_defaults: HashMap::new(),
+            context_defaults: IndexMap::new(),
         };
 
         assert!(rulebook.validate().is_err());
@@ -371,4 +371,76 @@ mod tests {
         assert!(!paths.is_empty());
         assert!(paths.iter().any(|p| p.contains("local.yaml")));
     }
+
+    #[test]
+    fn test_loaded_references_stay_as_written() {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("dep.yaml"),
+            r#"
+id: probe.dep
+version: 1.0.0
+metadata:
+  name: Dep
+  authors: []
+namespaces:
+  common:
+    id: common
+    datatypes:
+      colors:
+        name: colors
+        values:
+          - text: red
+"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("main.yaml"),
+            r#"
+id: probe.main
+version: 1.0.0
+metadata:
+  name: Main
+  authors: []
+namespaces:
+  scenes:
+    id: scenes
+    datatypes:
+      shades:
+        name: shades
+        extends: common:colors
+        values: []
+    prompt_sections:
+      scene:
+        name: scene
+        template: "{color}"
+        references:
+          color:
+            target: common:colors
+dependencies:
+  - package: probe.dep
+    version: 1.0.0
+    path: ./dep.yaml
+"#,
+        )
+        .unwrap();
+
+        let mut resolver = DependencyResolver::new(vec![]);
+        let (package, deps) = resolver
+            .load_package_with_deps(&dir.path().join("main.yaml"))
+            .unwrap();
+        assert!(deps.contains_key("probe.dep"));
+
+        // What the editor saves back must match what the author wrote
+        let saved = package_loader::parse_yaml(&serde_yaml::to_string(&package).unwrap()).unwrap();
+        let scenes = &saved.namespaces["scenes"];
+        assert_eq!(
+            scenes.prompt_sections["scene"].references["color"].target,
+            "common:colors"
+        );
+        assert_eq!(
+            scenes.datatypes["shades"].extends.as_deref(),
+            Some("common:colors")
+        );
+    }
 }
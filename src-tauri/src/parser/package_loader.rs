@@ -2,7 +2,10 @@
 // Implements serde-based deserialization with validation
 // M8.5 Blocker 2 Phase 2: Load packages with dependencies
 
-use crate::core::Package;
+use crate::core::inheritance::parse_extends_target;
+use crate::core::lookup::{find_namespace, LookupError};
+use crate::core::{Namespace, Package};
+use crate::renderer::template_parser::Template;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
@@ -50,6 +53,9 @@ pub struct LoadedPackage {
 /// 4. `./test-packages` directory
 /// 5. `./packages` directory
 ///
+/// The packages are ready to render: cross-package references are pinned to
+/// their providing package (see `qualify_references`), so don't save them.
+///
 /// Example:
 /// ```yaml
 /// dependencies:
@@ -78,6 +84,8 @@ pub fn load_package_with_dependencies<P: AsRef<Path>>(path: P) -> Result<LoadedP
     let (package, dependencies) = resolver
         .load_package_with_deps(path)
         .map_err(|e| ParserError::Validation(e.to_string()))?;
+    let (package, dependencies) = qualify_references(&package, &dependencies)
+        .map_err(|e| ParserError::Validation(e.to_string()))?;
 
     Ok(LoadedPackage {
         package,
@@ -157,6 +165,116 @@ fn normalize_references(package: &mut Package) {
     }
 }
 
+/// Copies of a package and its dependencies with cross-package references
+/// pinned to the package that provides them
+///
+/// Rewrites "namespace:name" reference targets and datatype `extends` to
+/// "package-id/namespace:name" when the component comes from a dependency.
+/// Implicit and inline template references are written out as references
+/// first (see `Template::effective_references`), so they are pinned too.
+/// Inside a dependency, references to its own components are pinned to it, so
+/// a main-package namespace of the same name can't shadow them. Main-package
+/// components take precedence and are left as written.
+///
+/// The copies are for rendering and validation only: they no longer match what
+/// the author wrote, so never hand them to the editor or save them.
+///
+/// Fails if a bare namespace is provided by more than one dependency.
+pub fn qualify_references(
+    package: &Package,
+    dependencies: &HashMap<String, Package>,
+) -> std::result::Result<(Package, HashMap<String, Package>), LookupError> {
+    let mut qualified = package.clone();
+    qualify_package(&mut qualified, package, dependencies)?;
+
+    let mut qualified_deps = dependencies.clone();
+    for dependency in qualified_deps.values_mut() {
+        qualify_package(dependency, package, dependencies)?;
+    }
+
+    Ok((qualified, qualified_deps))
+}
+
+fn qualify_package(
+    package: &mut Package,
+    main: &Package,
+    deps: &HashMap<String, Package>,
+) -> std::result::Result<(), LookupError> {
+    // Dependencies resolve their own components first
+    let owner = deps.get(&package.id).filter(|_| package.id != main.id);
+
+    for (ns_id, namespace) in &mut package.namespaces {
+        for promptsection in namespace.prompt_sections.values_mut() {
+            if let Ok(template) = Template::parse(&promptsection.template) {
+                promptsection.references =
+                    template.effective_references(&promptsection.references, ns_id);
+            }
+
+            for reference in promptsection.references.values_mut() {
+                if reference.target.starts_with("context:") {
+                    continue;
+                }
+                let (target_ns, target_name) = parse_extends_target(&reference.target, ns_id);
+                if let Some(target) =
+                    qualified_target(&target_ns, &target_name, owner, main, deps, |ns, name| {
+                        ns.datatypes.contains_key(name) || ns.prompt_sections.contains_key(name)
+                    })?
+                {
+                    reference.target = target;
+                }
+            }
+        }
+
+        for datatype in namespace.datatypes.values_mut() {
+            let Some(parent) = &datatype.extends else {
+                continue;
+            };
+            let (target_ns, target_name) = parse_extends_target(parent, ns_id);
+            if let Some(target) =
+                qualified_target(&target_ns, &target_name, owner, main, deps, |ns, name| {
+                    ns.datatypes.contains_key(name)
+                })?
+            {
+                datatype.extends = Some(target);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The package-qualified form of a target, if it needs one
+fn qualified_target(
+    namespace: &str,
+    name: &str,
+    owner: Option<&Package>,
+    main: &Package,
+    deps: &HashMap<String, Package>,
+    has: impl Fn(&Namespace, &str) -> bool,
+) -> std::result::Result<Option<String>, LookupError> {
+    // Already qualified
+    if namespace.contains('/') {
+        return Ok(None);
+    }
+
+    if let Some(owner) = owner {
+        if owner
+            .namespaces
+            .get(namespace)
+            .is_some_and(|ns| has(ns, name))
+        {
+            return Ok(Some(format!("{}/{}:{}", owner.id, namespace, name)));
+        }
+    }
+
+    match find_namespace(main, Some(deps), namespace, name, |ns| has(ns, name))? {
+        Some((provider, _)) if provider.id != main.id => {
+            Ok(Some(format!("{}/{}:{}", provider.id, namespace, name)))
+        }
+        _ => Ok(None),
+    }
+}
+
 /// Basic package validation
 /// More comprehensive validation will be added in M6
 fn validate_package(package: &Package) -> Result<()> {
@@ -233,4 +351,131 @@ namespaces:
         assert_eq!(package.id, "test.package");
         assert_eq!(package.version, "1.0.0");
     }
+
+    fn package_yaml(id: &str, namespace: &str, extra: &str) -> Package {
+        parse_yaml(&format!(
+            r#"
+id: {id}
+version: 1.0.0
+metadata:
+  name: {id}
+  authors: []
+namespaces:
+  {namespace}:
+    id: {namespace}
+    datatypes:
+      colors:
+        name: colors
+        values:
+          - text: red
+{extra}
+"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_qualify_references() {
+        let scenes = r#"
+    prompt_sections:
+      scene:
+        name: scene
+        template: "{color}"
+        references:
+          color:
+            target: common:colors
+"#;
+        let main = package_yaml("main", "scenes", scenes);
+        let mut deps = HashMap::new();
+        deps.insert("dep.a".to_string(), package_yaml("dep.a", "common", ""));
+
+        let (qualified, _) = qualify_references(&main, &deps).unwrap();
+        let reference =
+            &qualified.namespaces["scenes"].prompt_sections["scene"].references["color"];
+        assert_eq!(reference.target, "dep.a/common:colors");
+
+        // The original is left as written
+        let reference = &main.namespaces["scenes"].prompt_sections["scene"].references["color"];
+        assert_eq!(reference.target, "common:colors");
+
+        // Main package components stay unqualified
+        let mut main = package_yaml("main", "scenes", scenes);
+        main.namespaces
+            .get_mut("scenes")
+            .unwrap()
+            .prompt_sections
+            .get_mut("scene")
+            .unwrap()
+            .references
+            .get_mut("color")
+            .unwrap()
+            .target = "scenes:colors".to_string();
+        let (qualified, _) = qualify_references(&main, &deps).unwrap();
+        let reference =
+            &qualified.namespaces["scenes"].prompt_sections["scene"].references["color"];
+        assert_eq!(reference.target, "scenes:colors");
+    }
+
+    #[test]
+    fn test_qualify_implicit_references() {
+        use crate::renderer::Renderer;
+
+        // The dependency's `{colors}` is an implicit reference to common:colors
+        let scene = r#"
+    prompt_sections:
+      scene:
+        name: scene
+        template: "{colors}"
+"#;
+        let main = package_yaml("main", "common", "");
+        let mut deps = HashMap::new();
+        let mut dep = package_yaml("dep.a", "common", scene);
+        dep.namespaces["common"].datatypes["colors"].values[0].text = "green".to_string();
+        deps.insert("dep.a".to_string(), dep);
+
+        let (qualified, qualified_deps) = qualify_references(&main, &deps).unwrap();
+        let reference = &qualified_deps["dep.a"].namespaces["common"].prompt_sections["scene"]
+            .references["colors"];
+        assert_eq!(reference.target, "dep.a/common:colors");
+
+        // The main package's common:colors doesn't shadow the dependency's own
+        let output = Renderer::new_with_dependencies(&qualified, &qualified_deps, 1)
+            .render("dep.a/common:scene")
+            .unwrap()
+            .output;
+        assert_eq!(output, "green");
+    }
+
+    #[test]
+    fn test_qualify_references_ambiguous() {
+        let scenes = r#"
+    prompt_sections:
+      scene:
+        name: scene
+        template: "{color}"
+        references:
+          color:
+            target: common:colors
+"#;
+        let mut main = package_yaml("main", "scenes", scenes);
+        let mut deps = HashMap::new();
+        deps.insert("dep.a".to_string(), package_yaml("dep.a", "common", ""));
+        deps.insert("dep.b".to_string(), package_yaml("dep.b", "common", ""));
+
+        let err = qualify_references(&main, &deps).unwrap_err();
+        assert!(matches!(err, LookupError::Ambiguous { .. }));
+
+        // An explicit package prefix resolves the ambiguity
+        main.namespaces
+            .get_mut("scenes")
+            .unwrap()
+            .prompt_sections
+            .get_mut("scene")
+            .unwrap()
+            .references
+            .get_mut("color")
+            .unwrap()
+            .target = "dep.b/common:colors".to_string();
+        assert!(qualify_references(&main, &deps).is_ok());
+    }
 }
@@ -0,0 +1,149 @@
+// Automatic Articles
+// Resolves {a/an} placeholders from the word rendered after them
+//
+// Phase 3 writes into an ArticleBuffer: plain text, article placeholders and
+// the positions where values with an explicit `article` tag start. Once the
+// whole template is rendered, each placeholder takes the article of the next
+// word; an explicit tag on the value starting that word wins.
+
+use crate::renderer::transforms::{capitalize, indefinite_article};
+
+/// Rendered text with pending article placeholders
+#[derive(Debug, Default)]
+pub struct ArticleBuffer {
+    text: String,
+    /// Offsets of `{a/an}` placeholders and whether they are capitalized
+    placeholders: Vec<(usize, bool)>,
+    /// Offsets where a value with an explicit `article` tag starts
+    tagged: Vec<(usize, String)>,
+}
+
+impl ArticleBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append plain text
+    pub fn push_str(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    /// Append a rendered value, remembering its explicit article (if any)
+    pub fn push_value(&mut self, text: &str, article: Option<&str>) {
+        if let Some(article) = article {
+            let start = self.text.len() + (text.len() - text.trim_start().len());
+            self.tagged.push((start, article.to_string()));
+        }
+        self.text.push_str(text);
+    }
+
+    /// Append an article placeholder, resolved by `finish`
+    pub fn push_article(&mut self, capitalized: bool) {
+        self.placeholders.push((self.text.len(), capitalized));
+    }
+
+    /// Explicit article of the value the text starts with
+    ///
+    /// Lets a parent template's `{a/an}` honor the tag of a nested
+    /// promptsection's first value.
+    pub fn leading_article(&self) -> Option<&str> {
+        let start = self.word_start(0)?;
+        if self.placeholders.iter().any(|(pos, _)| *pos <= start) {
+            return None;
+        }
+        self.tag_at(start)
+    }
+
+    /// Resolve the placeholders and return the final text
+    ///
+    /// A placeholder with no word after it renders nothing.
+    pub fn finish(self) -> String {
+        let articles: Vec<(usize, String)> = self
+            .placeholders
+            .iter()
+            .map(|&(pos, capitalized)| {
+                let article = match self.word_start(pos) {
+                    Some(start) => self
+                        .tag_at(start)
+                        .unwrap_or_else(|| indefinite_article(&self.text[start..])),
+                    None => "",
+                };
+                let article = if capitalized {
+                    capitalize(article)
+                } else {
+                    article.to_string()
+                };
+                (pos, article)
+            })
+            .collect();
+
+        // Insert back to front so earlier offsets stay valid
+        let mut text = self.text;
+        for (pos, article) in articles.into_iter().rev() {
+            text.insert_str(pos, &article);
+        }
+        text
+    }
+
+    /// Offset of the first non-whitespace character at or after `pos`
+    fn word_start(&self, pos: usize) -> Option<usize> {
+        self.text[pos..]
+            .char_indices()
+            .find(|(_, c)| !c.is_whitespace())
+            .map(|(i, _)| pos + i)
+    }
+
+    fn tag_at(&self, start: usize) -> Option<&str> {
+        self.tagged
+            .iter()
+            .rev()
+            .find(|(pos, _)| *pos == start)
+            .map(|(_, article)| article.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_articles_from_next_word() {
+        let mut buffer = ArticleBuffer::new();
+        buffer.push_article(true);
+        buffer.push_str(" ");
+        buffer.push_value("orange", None);
+        buffer.push_str(" cat and ");
+        buffer.push_article(false);
+        buffer.push_str(" ");
+        buffer.push_value("red, blue and green", None);
+        buffer.push_str(" ball");
+
+        assert_eq!(
+            buffer.finish(),
+            "An orange cat and a red, blue and green ball"
+        );
+    }
+
+    #[test]
+    fn test_explicit_tag_wins() {
+        let mut buffer = ArticleBuffer::new();
+        buffer.push_article(false);
+        buffer.push_str(" ");
+        buffer.push_value("historic", Some("an"));
+        buffer.push_str(" moment");
+
+        assert_eq!(buffer.leading_article(), None);
+        assert_eq!(buffer.finish(), "an historic moment");
+    }
+
+    #[test]
+    fn test_leading_article_and_trailing_placeholder() {
+        let mut buffer = ArticleBuffer::new();
+        buffer.push_value("  hour", Some("an"));
+        buffer.push_str(" later, ");
+        buffer.push_article(false);
+
+        assert_eq!(buffer.leading_article(), Some("an"));
+        assert_eq!(buffer.finish(), "  hour later, ");
+    }
+}
@@ -3,10 +3,19 @@
 // M4: Added Rules execution in Phase 2
 
 use crate::context::Context;
-use crate::core::{Package, PromptSection};
-use crate::renderer::selector::{SelectedValue, Selector};
+use crate::core::lookup::find_namespace;
+use crate::core::{Fallback, Namespace, Package, PromptSection, Reference, SeparatorSet};
+use crate::renderer::articles::ArticleBuffer;
+use crate::renderer::observer::{RenderObserver, RenderPhase, SilentObserver};
+use crate::renderer::seeded_random::derive_seed;
+use crate::renderer::selector::{SelectedValue, SelectionError, Selector};
 use crate::renderer::template_parser::{Template, TemplateToken};
-use crate::rules::RulesProcessor;
+use crate::renderer::trace::{ChosenValue, ContextWrite, ReferenceTrace, RuleTrace, SectionTrace};
+use crate::renderer::transforms::TransformRegistry;
+use crate::rules::expression::{Expr, Value as ExprValue};
+use crate::rules::processor::{lookup_path, order_rules, scoped_rules, RuleOutcome};
+use crate::rules::{DecisionProcessor, RulesProcessor};
+use indexmap::IndexMap;
 use std::collections::HashMap;
 use thiserror::Error;
 
@@ -24,6 +33,12 @@ pub enum RenderError {
     #[error("Reference not selected: {0}")]
     ReferenceNotSelected(String),
 
+    #[error("Separator set not found: '{0}' (referenced by '{1}')")]
+    SeparatorNotFound(String, String),
+
+    #[error("Unknown transform: '{0}' (applied to '{1}')")]
+    UnknownTransform(String, String),
+
     #[error("Rule execution error: {0}")]
     RuleExecution(#[from] crate::rules::RuleError),
 
@@ -32,12 +47,33 @@ pub enum RenderError {
 
     #[error("Maximum recursion depth ({0}) exceeded for promptsection: {1}")]
     MaxRecursionDepth(usize, String),
+
+    #[error("{0}")]
+    Lookup(#[from] crate::core::lookup::LookupError),
+}
+
+impl RenderError {
+    /// Name of the variant, for grouping failures (e.g. "Selection")
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RenderError::TemplateParse(_) => "TemplateParse",
+            RenderError::Selection(_) => "Selection",
+            RenderError::PromptSectionNotFound(_) => "PromptSectionNotFound",
+            RenderError::ReferenceNotSelected(_) => "ReferenceNotSelected",
+            RenderError::SeparatorNotFound(_, _) => "SeparatorNotFound",
+            RenderError::UnknownTransform(_, _) => "UnknownTransform",
+            RenderError::RuleExecution(_) => "RuleExecution",
+            RenderError::Context(_) => "Context",
+            RenderError::MaxRecursionDepth(_, _) => "MaxRecursionDepth",
+            RenderError::Lookup(_) => "Lookup",
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, RenderError>;
 
 /// Maximum nesting depth for promptsections (prevent infinite recursion)
-const MAX_RECURSION_DEPTH: usize = 10;
+pub(crate) const MAX_RECURSION_DEPTH: usize = 10;
 
 /// Result of rendering
 #[derive(Debug, Clone, serde::Serialize)]
@@ -51,6 +87,19 @@ pub struct RenderResult {
     /// Selected values (for debugging)
     #[serde(skip_serializing_if = "HashMap::is_empty")]
     pub selected_values: HashMap<String, String>,
+
+    /// Structured render trace (only when tracing is enabled)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace: Option<SectionTrace>,
+
+    /// Explicit `article` tag of the value the output starts with
+    /// (so a parent's `{a/an}` can honor it)
+    #[serde(skip)]
+    leading_article: Option<String>,
+
+    /// Context after rendering (handed back to the parent of a nested section)
+    #[serde(skip)]
+    context: Context,
 }
 
 /// Three-phase renderer
@@ -59,6 +108,12 @@ pub struct Renderer<'a> {
     package: &'a Package,
     dependencies: Option<&'a HashMap<String, Package>>,
     seed: u64,
+    trace: bool,
+    bypass_filters: bool,
+    observer: &'a dyn RenderObserver,
+    transforms: &'a TransformRegistry,
+    /// Forced probability gate outcomes by segment index (enumeration)
+    chance_outcomes: Option<&'a [bool]>,
 }
 
 impl<'a> Renderer<'a> {
@@ -69,6 +124,11 @@ impl<'a> Renderer<'a> {
             package,
             dependencies: None, // No dependencies
             seed,
+            trace: false,
+            bypass_filters: false,
+            observer: &SilentObserver,
+            transforms: TransformRegistry::builtin(),
+            chance_outcomes: None,
         }
     }
 
@@ -83,9 +143,106 @@ impl<'a> Renderer<'a> {
             package,
             dependencies: Some(dependencies),
             seed,
+            trace: false,
+            bypass_filters: false,
+            observer: &SilentObserver,
+            transforms: TransformRegistry::builtin(),
+            chance_outcomes: None,
+        }
+    }
+
+    /// Record a structured trace (see `RenderResult::trace`) while rendering
+    pub fn with_trace(mut self, enabled: bool) -> Self {
+        self.trace = enabled;
+        self
+    }
+
+    /// Ignore every tag filter ("chaos mode" previews)
+    ///
+    /// Without it, only filters on datatypes of packages with
+    /// `metadata.bypass_filters` are ignored.
+    pub fn with_bypass_filters(mut self, bypass: bool) -> Self {
+        self.bypass_filters = bypass;
+        self
+    }
+
+    /// Report progress to `observer` (the default observer is silent)
+    pub fn with_observer(mut self, observer: &'a dyn RenderObserver) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// Use `transforms` for pipe transforms (the default is the built-ins)
+    #[allow(dead_code)] // Part of public API
+    pub fn with_transforms(mut self, transforms: &'a TransformRegistry) -> Self {
+        self.transforms = transforms;
+        self
+    }
+
+    /// Renderer for a nested position in the render tree
+    ///
+    /// The child's seed is derived from this renderer's seed and `label`, so
+    /// each nested promptsection gets an independent but deterministic stream.
+    fn child(&self, label: &str) -> Renderer<'a> {
+        Renderer {
+            package: self.package,
+            dependencies: self.dependencies,
+            seed: derive_seed(self.seed, label),
+            trace: self.trace,
+            bypass_filters: self.bypass_filters,
+            observer: self.observer,
+            transforms: self.transforms,
+            chance_outcomes: None,
+        }
+    }
+
+    /// Seed this renderer draws from
+    pub(crate) fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The same renderer with another seed
+    pub(crate) fn with_seed(&self, seed: u64) -> Renderer<'a> {
+        Renderer {
+            package: self.package,
+            dependencies: self.dependencies,
+            seed,
+            trace: self.trace,
+            bypass_filters: self.bypass_filters,
+            observer: self.observer,
+            transforms: self.transforms,
+            chance_outcomes: self.chance_outcomes,
         }
     }
 
+    /// Renderer whose probability gates take the given outcomes instead of
+    /// drawing them (indexed like `TemplateToken::Chance::index`)
+    pub(crate) fn with_chance_outcomes<'b>(&self, outcomes: &'b [bool]) -> Renderer<'b>
+    where
+        'a: 'b,
+    {
+        Renderer {
+            package: self.package,
+            dependencies: self.dependencies,
+            seed: self.seed,
+            trace: self.trace,
+            bypass_filters: self.bypass_filters,
+            observer: self.observer,
+            transforms: self.transforms,
+            chance_outcomes: Some(outcomes),
+        }
+    }
+
+    /// Selector over this renderer's package and dependencies
+    pub(crate) fn selector(&self) -> Selector<'a> {
+        if let Some(deps) = self.dependencies {
+            Selector::new_with_dependencies(self.package, deps, self.seed)
+        } else {
+            Selector::new(self.package, self.seed)
+        }
+        .with_bypass_filters(self.bypass_filters)
+    }
+
     /// Render a promptsection by name
     ///
     /// Reference format: "section" or "namespace:section"
@@ -96,19 +253,26 @@ impl<'a> Renderer<'a> {
     /// Render a promptsection with initial context values
     ///
     /// M9: Used by rulebooks to apply context defaults
-    fn render_with_context(
+    pub(crate) fn render_with_context(
         &self,
         promptsection_ref: &str,
-        initial_context: Option<HashMap<String, String>>,
+        initial_context: Option<IndexMap<String, String>>,
     ) -> Result<RenderResult> {
-        self.render_with_depth_and_context(promptsection_ref, 0, initial_context)
+        self.render_with_depth_and_context(promptsection_ref, 0, Context::new(), initial_context)
     }
 
     /// Render a promptsection with recursion depth tracking (M5 Phase 1)
     ///
-    /// This allows nested promptsections while preventing infinite recursion
-    fn render_with_depth(&self, promptsection_ref: &str, depth: usize) -> Result<RenderResult> {
-        self.render_with_depth_and_context(promptsection_ref, depth, None)
+    /// This allows nested promptsections while preventing infinite recursion.
+    /// Nested sections start from the parent's context, so rulebook defaults
+    /// and earlier rule outputs reach their filters and rules too.
+    fn render_with_depth(
+        &self,
+        promptsection_ref: &str,
+        depth: usize,
+        context: Context,
+    ) -> Result<RenderResult> {
+        self.render_with_depth_and_context(promptsection_ref, depth, context, None)
     }
 
     /// Render a promptsection with recursion depth tracking and initial context
@@ -118,7 +282,8 @@ impl<'a> Renderer<'a> {
         &self,
         promptsection_ref: &str,
         depth: usize,
-        initial_context: Option<HashMap<String, String>>,
+        mut context: Context,
+        initial_context: Option<IndexMap<String, String>>,
     ) -> Result<RenderResult> {
         // Check recursion depth
         if depth > MAX_RECURSION_DEPTH {
@@ -134,26 +299,58 @@ impl<'a> Renderer<'a> {
         // Find namespace for this promptsection
         let namespace = self.find_namespace_for_promptsection(promptsection_ref)?;
 
-        // Create context
-        let mut context = Context::new();
+        let mut trace = self.trace.then(|| SectionTrace {
+            promptsection: promptsection_ref.to_string(),
+            seed: self.seed,
+            ..Default::default()
+        });
 
         // M9: Apply initial context values (e.g., from rulebook context_defaults)
         if let Some(defaults) = initial_context {
+            let mut defaults: Vec<(String, String)> = defaults.into_iter().collect();
+            defaults.sort();
             for (key, value) in defaults {
+                if let Some(trace) = trace.as_mut() {
+                    trace.context_writes.push(ContextWrite {
+                        key: key.clone(),
+                        value: value.clone(),
+                        source: "rulebook".to_string(),
+                    });
+                }
                 context.set(&key, value)?;
             }
         }
 
+        // Inline template parameters/filters override (or stand in for) YAML references
+        let parsed = Template::parse(&promptsection.template)?;
+        let references = parsed.effective_references(&promptsection.references, &namespace.id);
+
         // Three phases
-        let selected = self.phase_1_selection(promptsection, depth)?;
-        self.phase_2_enrichment(&mut context, &selected, namespace)?;
-        let output = self.phase_3_rendering(
-            &promptsection.template,
-            promptsection,
+        self.observer
+            .phase_started(promptsection_ref, RenderPhase::Selection, depth);
+        let (selected, deferred) =
+            self.phase_1_selection(&parsed, &references, &mut context, depth, trace.as_mut())?;
+
+        self.observer
+            .phase_started(promptsection_ref, RenderPhase::Enrichment, depth);
+        self.phase_2_enrichment(&mut context, &selected, namespace, trace.as_mut())?;
+
+        self.observer
+            .phase_started(promptsection_ref, RenderPhase::Rendering, depth);
+        let (output, leading_article) = self.phase_3_rendering(
+            &parsed,
+            &references,
             &selected,
+            &deferred,
             &context,
             namespace,
         )?;
+        self.observer
+            .section_rendered(promptsection_ref, &output, depth);
+
+        if let Some(trace) = trace.as_mut() {
+            trace.output = output.clone();
+        }
 
         // Build result - include selected values (flatten Vec to first item for display)
         let mut selected_values: HashMap<String, String> = selected
@@ -175,6 +372,9 @@ impl<'a> Renderer<'a> {
             output,
             seed: self.seed,
             selected_values,
+            trace,
+            leading_article,
+            context,
         })
     }
 
@@ -183,35 +383,42 @@ impl<'a> Renderer<'a> {
     /// M5 Phase 1: Can recursively render nested promptsections
     /// M5 Phase 3+4: Can select multiple values per reference
     /// M8.5 Blocker 1: Cross-reference filtering with dependency ordering
+    ///
+    /// `references` are the effective definitions (YAML plus inline parameters).
+    /// Filters see `context` as built up so far: it starts with the parent's
+    /// context and picks up the context writes of each nested promptsection.
+    ///
+    /// A reference used only inside optional segments may find nothing to
+    /// pick (e.g. its filter reads context the segment's condition checks);
+    /// that failure is returned as deferred and raised by phase 3 only if the
+    /// reference renders.
+    #[allow(clippy::type_complexity)]
     fn phase_1_selection(
         &self,
-        promptsection: &PromptSection,
+        parsed: &Template,
+        references: &IndexMap<String, Reference>,
+        context: &mut Context,
         depth: usize,
-    ) -> Result<HashMap<String, Vec<SelectedValue>>> {
-        // Parse template
-        let parsed = Template::parse(&promptsection.template)?;
-
+        mut trace: Option<&mut SectionTrace>,
+    ) -> Result<(
+        HashMap<String, Vec<SelectedValue>>,
+        HashMap<String, SelectionError>,
+    )> {
         // M8.5 Blocker 1 Part 2: Determine selection order based on dependencies
-        let selection_order = self.compute_selection_order(promptsection, &parsed)?;
+        let selection_order = self.compute_selection_order(references, parsed)?;
+        let optional = parsed.optional_references();
+        let mut deferred = HashMap::new();
 
         // M9 Phase 2.7: Create selector with dependencies if available
-        let mut selector = if let Some(deps) = self.dependencies {
-            Selector::new_with_dependencies(self.package, deps, self.seed)
-        } else {
-            Selector::new(self.package, self.seed)
-        };
-
-        // M8.5: Build selection context for cross-reference filtering
-        // Maps ref_name -> (text, tags) for already-selected values
-        let mut selection_context: HashMap<String, (String, HashMap<String, serde_json::Value>)> =
-            HashMap::new();
+        let mut selector = self.selector().with_context(std::mem::take(context));
 
         // Select value(s) for each reference in dependency order
+        // M8.5: Already-selected values (all of them) double as the cross-reference filter context
         let mut selected = HashMap::new();
 
         for ref_name in selection_order {
             // Look up the reference definition to get the target and parameters
-            let reference = promptsection.references.get(&ref_name).ok_or_else(|| {
+            let reference = references.get(&ref_name).ok_or_else(|| {
                 RenderError::ReferenceNotSelected(format!(
                     "Reference '{}' not defined in promptsection",
                     ref_name
@@ -226,26 +433,52 @@ impl<'a> Renderer<'a> {
             // Get filter from reference definition
             let filter = reference.filter.as_deref();
 
-            // M5 Phase 3+4: Use parameters from YAML Reference
+            // M5 Phase 3+4: Use parameters from the effective Reference
             let min = reference.min;
             let max = reference.max;
             let unique = reference.unique;
 
             // M5 Phase 1: Check if this is a nested promptsection reference
-            if self.is_promptsection_reference(&reference.target) {
-                // Render the nested promptsection recursively
-                let nested_result = self.render_with_depth(&reference.target, depth + 1)?;
+            if self.is_promptsection_reference(&reference.target)? {
+                // Render the nested promptsection recursively with its own derived seed
+                let mut nested_result = self
+                    .child(&format!("section:{}", ref_name))
+                    .render_with_depth(&reference.target, depth + 1, selector.context().clone())?;
+
+                // The nested section's rule outputs are visible to later references
+                selector.set_context(std::mem::take(&mut nested_result.context));
+
+                if let Some(trace) = trace.as_deref_mut() {
+                    trace.references.push(ReferenceTrace {
+                        name: ref_name.clone(),
+                        target: reference.target.clone(),
+                        filter: reference.filter.clone(),
+                        candidates: 1,
+                        chosen: vec![ChosenValue {
+                            text: nested_result.output.clone(),
+                            tags: IndexMap::new(),
+                            weight: 1.0,
+                        }],
+                        fallback: None,
+                        section: nested_result.trace.map(Box::new),
+                    });
+                }
 
                 // Store the rendered output as a selected value
+                // Nested sections have no tags of their own; an explicit article
+                // of their first value carries over for the parent's {a/an}
                 let selected_val = SelectedValue {
-                    text: nested_result.output.clone(),
-                    tags: HashMap::new(), // Nested sections don't have tags
+                    text: nested_result.output,
+                    tags: nested_result
+                        .leading_article
+                        .map(|article| IndexMap::from([("article".to_string(), article.into())]))
+                        .unwrap_or_default(),
                 };
 
-                // Add to selection context for cross-reference filtering
-                selection_context.insert(ref_name.clone(), (nested_result.output, HashMap::new()));
-
-                selected.insert(ref_name.clone(), vec![selected_val]);
+                let values = vec![selected_val];
+                self.observer
+                    .values_selected(&ref_name, &reference.target, &values);
+                selected.insert(ref_name.clone(), values);
             } else {
                 // M5 Phase 3+4: Determine how many values to select
                 let count = if min == max {
@@ -253,71 +486,133 @@ impl<'a> Renderer<'a> {
                 } else {
                     // Use selector's RNG to pick between min and max
                     use crate::renderer::seeded_random::SeededRandom;
-                    let mut temp_rng = SeededRandom::new(self.seed + ref_name.len() as u64);
+                    let mut temp_rng =
+                        SeededRandom::new(derive_seed(self.seed, &format!("count:{}", ref_name)));
                     temp_rng.gen_range(min..=max)
                 };
 
+                // Each reference picks from its own stream, so adding or
+                // removing a reference leaves the picks of the others alone
+                selector.reseed(derive_seed(self.seed, &format!("ref:{}", ref_name)));
+
                 // M8.5: Select values with cross-reference filtering support
-                let values = if count > 1 {
-                    selector.select_multiple(
-                        &reference.target,
-                        count,
-                        filter,
-                        unique,
-                        &selection_context,
-                    )?
-                } else if count == 1 {
-                    vec![selector.select_with_filter(
-                        &reference.target,
-                        filter,
-                        &selection_context,
-                    )?]
+                let select = |selector: &mut Selector, filter: Option<&str>| {
+                    if count > 1 {
+                        selector.select_multiple(
+                            &reference.target,
+                            count,
+                            filter,
+                            unique,
+                            &selected,
+                        )
+                    } else if count == 1 {
+                        selector
+                            .select_with_filter(&reference.target, filter, &selected)
+                            .map(|value| vec![value])
+                    } else {
+                        // count == 0, return empty vec
+                        Ok(Vec::new())
+                    }
+                };
+
+                // A filter that matches nothing falls back per the reference's policy
+                let mut fallback = None;
+                let picked = match (select(&mut selector, filter), &reference.fallback) {
+                    (Err(SelectionError::NoMatchingValues(_)), policy) if !policy.is_error() => {
+                        fallback = Some(policy.clone());
+                        match policy {
+                            Fallback::Unfiltered => select(&mut selector, None),
+                            Fallback::Default(text) => Ok(vec![SelectedValue {
+                                text: text.clone(),
+                                tags: IndexMap::new(),
+                            }]),
+                            Fallback::Empty | Fallback::Error => Ok(Vec::new()),
+                        }
+                    }
+                    (result, _) => result,
+                };
+                let values = match picked {
+                    Err(
+                        e @ (SelectionError::NoMatchingValues(_)
+                        | SelectionError::NotEnoughUniqueValues { .. }),
+                    ) if optional.contains(&ref_name) => {
+                        deferred.insert(ref_name, e);
+                        continue;
+                    }
+                    result => result?,
+                };
+
+                // Candidates are only computed for the trace (they don't consume randomness)
+                let candidates = if trace.is_some() {
+                    let filter = filter.filter(|_| fallback != Some(Fallback::Unfiltered));
+                    selector
+                        .candidates(&reference.target, filter, &selected)
+                        .unwrap_or_default()
                 } else {
-                    // count == 0, return empty vec
                     Vec::new()
                 };
 
-                // M8.5: Add first selected value to context for subsequent cross-reference filtering
-                if let Some(first_value) = values.first() {
-                    selection_context.insert(
-                        ref_name.clone(),
-                        (first_value.text.clone(), first_value.tags.clone()),
-                    );
+                if let Some(trace) = trace.as_deref_mut() {
+                    trace.references.push(ReferenceTrace {
+                        name: ref_name.clone(),
+                        target: reference.target.clone(),
+                        filter: reference.filter.clone(),
+                        candidates: candidates.len(),
+                        chosen: values
+                            .iter()
+                            .map(|value| ChosenValue {
+                                text: value.text.clone(),
+                                tags: value.tags.clone(),
+                                weight: candidates
+                                    .iter()
+                                    .find(|c| c.text == value.text)
+                                    .map_or(1.0, |c| c.weight),
+                            })
+                            .collect(),
+                        fallback,
+                        section: None,
+                    });
                 }
 
+                self.observer
+                    .values_selected(&ref_name, &reference.target, &values);
                 selected.insert(ref_name.clone(), values);
             }
         }
 
-        Ok(selected)
+        *context = selector.into_context();
+
+        Ok((selected, deferred))
     }
 
     /// Compute selection order based on filter dependencies
     ///
     /// M8.5 Blocker 1 Part 2: Dependency ordering
-    /// References with filters that depend on other references must be selected after those references
-    fn compute_selection_order(
+    /// References with filters that depend on other references must be selected after those references.
+    /// Filters that read the context also wait for the nested promptsections
+    /// before them in the template, whose rules may write it.
+    pub(crate) fn compute_selection_order(
         &self,
-        promptsection: &PromptSection,
+        references: &IndexMap<String, Reference>,
         parsed: &Template,
     ) -> Result<Vec<String>> {
-        use crate::renderer::tag_expression::ExpressionParser;
+        use crate::renderer::tag_expression::{reads_context, ExpressionParser};
 
         // Get all reference names from template (in template order)
         let mut ref_names: Vec<String> = Vec::new();
-        for token in &parsed.tokens {
-            if let TemplateToken::Reference { name, .. } = token {
-                if !ref_names.contains(name) {
-                    ref_names.push(name.clone());
-                }
+        for name in parsed.get_references() {
+            if !ref_names.contains(&name) {
+                ref_names.push(name);
             }
         }
 
         // Build dependency graph
         let mut dependencies: HashMap<String, Vec<String>> = HashMap::new();
 
+        let mut nested_sections: Vec<String> = Vec::new();
+
         for ref_name in &ref_names {
-            if let Some(reference) = promptsection.references.get(ref_name) {
+            if let Some(reference) = references.get(ref_name) {
                 // Skip context references
                 if reference.target.starts_with("context:") {
                     continue;
@@ -326,14 +621,25 @@ impl<'a> Renderer<'a> {
                 // Extract dependencies from filter
                 if let Some(filter_expr) = &reference.filter {
                     if let Ok(parsed_filter) = ExpressionParser::parse(filter_expr) {
-                        let deps = crate::renderer::tag_expression::extract_ref_dependencies(
+                        let mut deps = crate::renderer::tag_expression::extract_ref_dependencies(
                             &parsed_filter,
                         );
+                        if reads_context(&parsed_filter) {
+                            for section in &nested_sections {
+                                if !deps.contains(section) {
+                                    deps.push(section.clone());
+                                }
+                            }
+                        }
                         if !deps.is_empty() {
                             dependencies.insert(ref_name.clone(), deps);
                         }
                     }
                 }
+
+                if self.is_promptsection_reference(&reference.target)? {
+                    nested_sections.push(ref_name.clone());
+                }
             }
         }
 
@@ -375,7 +681,8 @@ impl<'a> Renderer<'a> {
             .map(|(node, _)| node.clone())
             .collect();
 
-        // Process nodes in template order for determinism
+        // Sorted by template position and popped from the back, so independent
+        // references are processed in reverse template order
         queue.sort_by_key(|name| nodes.iter().position(|n| n == name).unwrap_or(usize::MAX));
 
         while let Some(node) = queue.pop() {
@@ -422,62 +729,84 @@ impl<'a> Renderer<'a> {
     ///
     /// M4: Execute Rules to compute derived values
     /// M4: Rules can read selected values' tags and write to context
-    /// M5: Converts Vec<SelectedValue> to single value for rules (uses first)
+    /// Rules and decisions see every selected value of multi-select references
     /// M8.5 Blocker 2 Phase 1: Execute rules from ALL namespaces (package-wide)
     /// M8.5 Blocker 2 Phase 2: Execute rules from dependencies too (cross-package)
-    fn phase_2_enrichment(
+    /// Rules run in a deterministic order (see `order_rules`)
+    /// Decisions (M1 Pattern 3) run after rules, in the same package order
+    pub(crate) fn phase_2_enrichment(
         &self,
         context: &mut Context,
         selected: &HashMap<String, Vec<SelectedValue>>,
         _namespace: &crate::core::Namespace, // Kept for compatibility but unused now
+        mut trace: Option<&mut SectionTrace>,
     ) -> Result<()> {
-        // M5: Convert Vec<SelectedValue> to HashMap<String, SelectedValue> for rules
-        // Rules only see the first selected value
-        let single_selected: HashMap<String, SelectedValue> = selected
-            .iter()
-            .filter_map(|(key, values)| values.first().map(|v| (key.clone(), v.clone())))
-            .collect();
+        // Dependencies in package id order, then the main package
+        let mut packages: Vec<&Package> = Vec::new();
+        if let Some(dependencies) = self.dependencies {
+            let mut dep_ids: Vec<&String> = dependencies.keys().collect();
+            dep_ids.sort();
+            packages.extend(dep_ids.into_iter().map(|id| &dependencies[id]));
+        }
+        packages.push(self.package);
 
-        println!("\n=== Phase 2: Enrichment ===");
+        // M8.5 Blocker 2: Rules from dependencies FIRST, then ALL namespaces in main package
+        // Stable default order (package, namespace id, rule id), adjusted by priority/after
+        let mut scoped = Vec::new();
+        for package in &packages {
+            scoped.extend(scoped_rules(&package.namespaces));
+        }
 
-        // M8.5 Blocker 2 Phase 2: Execute rules from dependencies FIRST (if any)
-        // This allows main package to override dependency rules if needed
-        if let Some(dependencies) = self.dependencies {
-            for (dep_id, dep_package) in dependencies {
-                for (namespace_id, namespace) in &dep_package.namespaces {
-                    if !namespace.rules.is_empty() {
-                        println!(
-                            "Executing {} rule(s) from dependency {}.{}",
-                            namespace.rules.len(),
-                            dep_id,
-                            namespace_id
-                        );
+        if !scoped.is_empty() {
+            let ordered = order_rules(scoped)?;
+
+            // M4: Execute Rules
+            let outcomes = RulesProcessor::new(context, selected).execute_ordered(&ordered)?;
 
-                        let mut rules_processor = RulesProcessor::new(context, &single_selected);
-                        rules_processor.execute_rules(&namespace.rules)?;
+            for (scoped, outcome) in ordered.iter().zip(outcomes) {
+                let rule = format!("{}:{}", scoped.namespace, scoped.id);
+                self.observer.rule_executed(&rule, &outcome);
+
+                if let Some(trace) = trace.as_deref_mut() {
+                    if let RuleOutcome::Applied { key } = &outcome {
+                        trace.context_writes.push(context_write(
+                            context,
+                            key,
+                            format!("rule {}", rule),
+                        ));
                     }
+                    trace.rules.push(RuleTrace { rule, outcome });
                 }
             }
         }
 
-        // M8.5 Blocker 2 Phase 1: Execute rules from ALL namespaces in main package
-        // This makes rules package-wide instead of namespace-scoped
-        for (namespace_id, namespace) in &self.package.namespaces {
-            if !namespace.rules.is_empty() {
-                println!(
-                    "Executing {} rule(s) from namespace: {}",
-                    namespace.rules.len(),
-                    namespace_id
-                );
-
-                // M4: Execute Rules from this namespace
-                let mut rules_processor = RulesProcessor::new(context, &single_selected);
-                rules_processor.execute_rules(&namespace.rules)?;
+        // Decisions run after all rules so they can read rule contributions
+        // Same package order as rules, namespaces by id, decisions in declaration order
+        for package in &packages {
+            let mut namespace_ids: Vec<&String> = package.namespaces.keys().collect();
+            namespace_ids.sort();
+
+            for namespace_id in namespace_ids {
+                let namespace = &package.namespaces[namespace_id];
+                if !namespace.decisions.is_empty() {
+                    let written = DecisionProcessor::new(context, selected)
+                        .execute_decisions(&namespace.decisions)?;
+
+                    for (decision, key) in written {
+                        self.observer.decision_output(&decision, &key);
+
+                        if let Some(trace) = trace.as_deref_mut() {
+                            trace.context_writes.push(context_write(
+                                context,
+                                &key,
+                                format!("decision {}", decision),
+                            ));
+                        }
+                    }
+                }
             }
         }
 
-        // M4: Later will add Decisions execution here
-
         Ok(())
     }
 
@@ -485,50 +814,83 @@ impl<'a> Renderer<'a> {
     ///
     /// M4: Can read from context for computed values
     /// M5: Handles Vec<SelectedValue> and formats with separator sets
-    fn phase_3_rendering(
+    /// Optional segments render when their condition holds (after enrichment,
+    /// so conditions see rule and decision output); probability gates draw
+    /// from a seed derived from their position in the template
+    /// `{a/an}` placeholders are resolved once the whole template is rendered
+    ///
+    /// Returns the output and the explicit article of its first value.
+    pub(crate) fn phase_3_rendering(
         &self,
-        template: &str,
-        promptsection: &PromptSection,
+        parsed: &Template,
+        references: &IndexMap<String, Reference>,
         selected: &HashMap<String, Vec<SelectedValue>>,
+        deferred: &HashMap<String, SelectionError>,
         context: &Context,
-        namespace: &crate::core::Namespace,
-    ) -> Result<String> {
-        let parsed = Template::parse(template)?;
+        namespace: &Namespace,
+    ) -> Result<(String, Option<String>)> {
+        let inputs = RenderInputs {
+            references,
+            selected,
+            deferred,
+            context,
+            namespace,
+        };
+        let mut output = ArticleBuffer::new();
+        self.render_tokens(&parsed.tokens, &inputs, &mut output)?;
+        let leading_article = output.leading_article().map(str::to_string);
+        Ok((output.finish(), leading_article))
+    }
 
-        let mut output = String::new();
+    /// Render a token list into `output` (recursing into segments)
+    fn render_tokens(
+        &self,
+        tokens: &[TemplateToken],
+        inputs: &RenderInputs,
+        output: &mut ArticleBuffer,
+    ) -> Result<()> {
+        let RenderInputs {
+            references,
+            selected,
+            deferred,
+            context,
+            namespace,
+        } = *inputs;
 
-        for token in parsed.tokens {
+        for token in tokens {
             match token {
                 TemplateToken::Text(text) => {
-                    output.push_str(&text);
+                    output.push_str(text);
                 }
-                TemplateToken::Reference { name: ref_name, .. } => {
+                TemplateToken::Reference {
+                    name: ref_name,
+                    transforms,
+                    ..
+                } => {
+                    // Explicit article tag of the first value (wins over {a/an} guessing)
+                    let article = selected
+                        .get(ref_name)
+                        .and_then(|values| values.first())
+                        .and_then(|value| value.tags.get("article"))
+                        .and_then(|article| article.as_str());
+
                     // Try to get from selected values first
-                    if let Some(values) = selected.get(&ref_name) {
-                        // M5: Get separator from YAML Reference definition
-                        let separator_ref = promptsection
-                            .references
-                            .get(&ref_name)
-                            .and_then(|r| r.separator.as_ref());
+                    let text = if let Some(values) = selected.get(ref_name) {
+                        // M5: Get separator from the effective Reference definition
+                        // (resolved even for a single value so a bad reference always fails)
+                        let separator = references
+                            .get(ref_name)
+                            .and_then(|r| r.separator.as_deref())
+                            .map(|sep_ref| self.find_separator_set(sep_ref, namespace, ref_name))
+                            .transpose()?;
 
                         // M5: Format multiple values with separator
-                        let text = if values.len() > 1 {
-                            // Get separator set if specified
-                            if let Some(sep_ref) = separator_ref {
-                                // Find separator set in namespace
-                                if let Some(sep_set) = namespace.separator_sets.get(sep_ref) {
-                                    // Use separator set to format
-                                    let texts: Vec<String> =
-                                        values.iter().map(|v| v.text.clone()).collect();
-                                    sep_set.format(&texts)
-                                } else {
-                                    // Separator not found, fall back to space
-                                    values
-                                        .iter()
-                                        .map(|v| v.text.as_str())
-                                        .collect::<Vec<_>>()
-                                        .join(" ")
-                                }
+                        if values.len() > 1 {
+                            // Use separator set if specified
+                            if let Some(sep_set) = separator {
+                                let texts: Vec<String> =
+                                    values.iter().map(|v| v.text.clone()).collect();
+                                sep_set.format(&texts)
                             } else {
                                 // No separator specified, use space
                                 values
@@ -543,111 +905,160 @@ impl<'a> Renderer<'a> {
                         } else {
                             // Empty list, render nothing
                             String::new()
-                        };
-
-                        output.push_str(&text);
+                        }
                     } else {
                         // Try to get from context (for computed values)
                         // Check if it exists in prompt scope
-                        if context.has(&ref_name) {
-                            let text = context.get_text(&ref_name)?;
-                            output.push_str(&text);
+                        if context.has(ref_name) {
+                            context.get_text(ref_name)?
+                        } else if let Some(error) = deferred.get(ref_name) {
+                            // Its optional segment renders after all
+                            return Err(RenderError::Selection(error.clone()));
                         } else {
                             return Err(RenderError::ReferenceNotSelected(ref_name.clone()));
                         }
+                    };
+
+                    // Pipe transforms apply to the rendered text, left to right
+                    let text = transforms.iter().try_fold(text, |text, transform| {
+                        self.transforms.apply(transform, &text).ok_or_else(|| {
+                            RenderError::UnknownTransform(transform.clone(), ref_name.clone())
+                        })
+                    })?;
+
+                    output.push_value(&text, article);
+                }
+                TemplateToken::Article { capitalized } => output.push_article(*capitalized),
+                TemplateToken::Conditional {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    let branch = if condition_holds(condition, selected, context) {
+                        then
+                    } else {
+                        otherwise
+                    };
+                    self.render_tokens(branch, inputs, output)?;
+                }
+                TemplateToken::Chance {
+                    percent,
+                    index,
+                    tokens,
+                } => {
+                    use crate::renderer::seeded_random::SeededRandom;
+                    let shown = match self.chance_outcomes {
+                        Some(outcomes) => outcomes.get(*index).copied().unwrap_or(false),
+                        None => {
+                            let mut rng = SeededRandom::new(derive_seed(
+                                self.seed,
+                                &format!("chance:{}", index),
+                            ));
+                            (rng.gen_range(0..=99) as u32) < *percent
+                        }
+                    };
+                    if shown {
+                        self.render_tokens(tokens, inputs, output)?;
                     }
                 }
             }
         }
 
-        Ok(output)
+        Ok(())
     }
 
     /// Find a promptsection by reference
     /// M9 Phase 3: Searches dependencies if not found in main package
-    fn find_promptsection(&self, reference: &str) -> Result<&PromptSection> {
-        // Parse reference
-        let (namespace, section_name) = if reference.contains(':') {
-            let parts: Vec<&str> = reference.split(':').collect();
-            (parts[0].to_string(), parts[1].to_string())
-        } else {
-            // Use first namespace
-            let first_ns = self
-                .package
-                .namespaces
-                .keys()
-                .next()
-                .ok_or_else(|| RenderError::PromptSectionNotFound(reference.to_string()))?;
-            (first_ns.clone(), reference.to_string())
-        };
+    pub(crate) fn find_promptsection(&self, reference: &str) -> Result<&PromptSection> {
+        let (_, section_name) = self.split_promptsection_reference(reference)?;
+        self.find_namespace_for_promptsection(reference)?
+            .prompt_sections
+            .get(&section_name)
+            .ok_or_else(|| RenderError::PromptSectionNotFound(reference.to_string()))
+    }
 
-        // First try main package
-        if let Some(ns) = self.package.namespaces.get(&namespace) {
-            if let Some(ps) = ns.prompt_sections.get(&section_name) {
-                return Ok(ps);
-            }
-        }
+    /// Find the namespace that contains a promptsection
+    /// M9 Phase 3: Searches dependencies if not found in main package
+    ///
+    /// Accepts "name", "namespace:name" and "package-id/namespace:name";
+    /// a namespace provided by several dependencies is an ambiguity error.
+    pub(crate) fn find_namespace_for_promptsection(&self, reference: &str) -> Result<&Namespace> {
+        let (namespace, section_name) = self.split_promptsection_reference(reference)?;
+
+        find_namespace(
+            self.package,
+            self.dependencies,
+            &namespace,
+            &section_name,
+            |ns| ns.prompt_sections.contains_key(&section_name),
+        )?
+        .map(|(_, ns)| ns)
+        .ok_or_else(|| RenderError::PromptSectionNotFound(reference.to_string()))
+    }
 
-        // M9: If not found and we have dependencies, search them
-        if let Some(deps) = self.dependencies {
-            for dep_package in deps.values() {
-                if let Some(ns) = dep_package.namespaces.get(&namespace) {
-                    if let Some(ps) = ns.prompt_sections.get(&section_name) {
-                        return Ok(ps);
-                    }
-                }
-            }
+    /// Split a promptsection reference into (namespace, name)
+    /// Bare names use the package's default namespace
+    fn split_promptsection_reference(&self, reference: &str) -> Result<(String, String)> {
+        if let Some((namespace, section_name)) = reference.split_once(':') {
+            return Ok((namespace.to_string(), section_name.to_string()));
         }
 
-        // Not found anywhere
-        Err(RenderError::PromptSectionNotFound(reference.to_string()))
+        let namespace = self
+            .package
+            .default_namespace_id()
+            .ok_or_else(|| RenderError::PromptSectionNotFound(reference.to_string()))?;
+        Ok((namespace.to_string(), reference.to_string()))
     }
 
-    /// Find the namespace that contains a promptsection
-    /// M9 Phase 3: Searches dependencies if not found in main package
-    fn find_namespace_for_promptsection(&self, reference: &str) -> Result<&crate::core::Namespace> {
-        // Parse reference
-        let (namespace_id, section_name) = if reference.contains(':') {
-            let parts: Vec<&str> = reference.split(':').collect();
-            (parts[0].to_string(), parts[1].to_string())
-        } else {
-            // Use first namespace
-            let first_ns = self
-                .package
-                .namespaces
-                .keys()
-                .next()
-                .ok_or_else(|| RenderError::PromptSectionNotFound(reference.to_string()))?;
-            (first_ns.clone(), reference.to_string())
+    /// Find a separator set for a reference's `separator`
+    ///
+    /// Bare names ("comma_and") resolve in the promptsection's own namespace;
+    /// qualified names ("common:oxford_comma", "package-id/common:oxford_comma")
+    /// search the main package, then dependencies.
+    fn find_separator_set<'n>(
+        &'n self,
+        separator: &str,
+        namespace: &'n Namespace,
+        ref_name: &str,
+    ) -> Result<&'n SeparatorSet> {
+        let not_found =
+            || RenderError::SeparatorNotFound(separator.to_string(), ref_name.to_string());
+
+        let Some((namespace_id, set_name)) = separator.split_once(':') else {
+            return namespace
+                .separator_sets
+                .get(separator)
+                .ok_or_else(not_found);
         };
 
-        // First try main package
-        if let Some(ns) = self.package.namespaces.get(&namespace_id) {
-            // Verify the promptsection actually exists in this namespace
-            if ns.prompt_sections.contains_key(&section_name) {
-                return Ok(ns);
+        // Prefer the promptsection's own namespace (it may live in a dependency)
+        if namespace.id == namespace_id {
+            if let Some(set) = namespace.separator_sets.get(set_name) {
+                return Ok(set);
             }
         }
 
-        // M9: If not found and we have dependencies, search them
-        if let Some(deps) = self.dependencies {
-            for dep_package in deps.values() {
-                if let Some(ns) = dep_package.namespaces.get(&namespace_id) {
-                    if ns.prompt_sections.contains_key(&section_name) {
-                        return Ok(ns);
-                    }
-                }
-            }
-        }
-
-        // Not found anywhere
-        Err(RenderError::PromptSectionNotFound(reference.to_string()))
+        find_namespace(
+            self.package,
+            self.dependencies,
+            namespace_id,
+            set_name,
+            |ns| ns.separator_sets.contains_key(set_name),
+        )?
+        .and_then(|(_, ns)| ns.separator_sets.get(set_name))
+        .ok_or_else(not_found)
     }
 
     /// M5 Phase 1: Check if a reference points to a promptsection (vs a datatype)
-    fn is_promptsection_reference(&self, reference: &str) -> bool {
-        // Try to find as promptsection
-        self.find_promptsection(reference).is_ok()
+    ///
+    /// Lookup errors (e.g. an ambiguous namespace) are reported rather than
+    /// treated as "not a promptsection".
+    pub(crate) fn is_promptsection_reference(&self, reference: &str) -> Result<bool> {
+        match self.find_promptsection(reference) {
+            Ok(_) => Ok(true),
+            Err(RenderError::PromptSectionNotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
     }
 
     /// M9: Render from a rulebook
@@ -673,18 +1084,7 @@ impl<'a> Renderer<'a> {
         rulebook_ref: &str,
         used_entry_points: Option<&mut Vec<String>>,
     ) -> Result<RenderResult> {
-        // Parse rulebook reference
-        let (namespace_name, rulebook_name) = self.parse_rulebook_reference(rulebook_ref)?;
-
-        // Find rulebook
-        let rulebook = self.find_rulebook(&namespace_name, &rulebook_name)?;
-
-        // Validate rulebook
-        rulebook.validate().map_err(|e| {
-            RenderError::Selection(crate::renderer::selector::SelectionError::InvalidReference(
-                e,
-            ))
-        })?;
+        let rulebook = self.resolve_rulebook(rulebook_ref)?;
 
         // Select entry point
         let entry_point = self.select_entry_point(rulebook, used_entry_points)?;
@@ -704,8 +1104,7 @@ impl<'a> Renderer<'a> {
     ///
     /// If batch_variety is enabled and used_entry_points is provided,
     /// will try to select an unused entry point.
-    #[allow(dead_code)] // Used internally by rulebook rendering
-    fn select_entry_point(
+    pub(crate) fn select_entry_point(
         &self,
         rulebook: &crate::core::rulebook::Rulebook,
         used_entry_points: Option<&mut Vec<String>>,
@@ -754,6 +1153,23 @@ impl<'a> Renderer<'a> {
         Ok(selected.prompt_section.clone())
     }
 
+    /// Find and validate a rulebook by reference
+    pub(crate) fn resolve_rulebook(
+        &self,
+        rulebook_ref: &str,
+    ) -> Result<&crate::core::rulebook::Rulebook> {
+        let (namespace_name, rulebook_name) = self.parse_rulebook_reference(rulebook_ref)?;
+        let rulebook = self.find_rulebook(&namespace_name, &rulebook_name)?;
+
+        rulebook.validate().map_err(|e| {
+            RenderError::Selection(crate::renderer::selector::SelectionError::InvalidReference(
+                e,
+            ))
+        })?;
+
+        Ok(rulebook)
+    }
+
     /// Find a rulebook by reference
     #[allow(dead_code)] // Used internally by rulebook rendering
     fn find_rulebook(
@@ -788,25 +1204,102 @@ impl<'a> Renderer<'a> {
             }
             Ok((parts[0].to_string(), parts[1].to_string()))
         } else {
-            // Use first namespace as default
-            let first_namespace = self.package.namespaces.keys().next().ok_or_else(|| {
+            let namespace = self.package.default_namespace_id().ok_or_else(|| {
                 RenderError::Selection(crate::renderer::selector::SelectionError::InvalidReference(
                     "No namespaces in package".to_string(),
                 ))
             })?;
 
-            Ok((first_namespace.clone(), reference.to_string()))
+            Ok((namespace.to_string(), reference.to_string()))
         }
     }
 }
 
+/// Consecutive renders of a rulebook that share its batch variety
+///
+/// Render `index` uses seed `start_seed + index`. The rulebook batch Tauri
+/// commands and `rpg-cli render --rulebook --count` both render through this,
+/// so the same start seed gives the same entry points and outputs.
+pub struct RulebookBatch {
+    start_seed: u64,
+    /// Entry points used so far
+    used_entry_points: Vec<String>,
+}
+
+impl RulebookBatch {
+    pub fn new(start_seed: u64) -> Self {
+        RulebookBatch {
+            start_seed,
+            used_entry_points: Vec::new(),
+        }
+    }
+
+    /// Seed of the render at `index`
+    pub fn seed(&self, index: usize) -> u64 {
+        self.start_seed.wrapping_add(index as u64)
+    }
+
+    /// Render the rulebook for `index`, with a renderer built for its seed
+    ///
+    /// Returns the result and the entry point it rendered.
+    pub fn render<'a>(
+        &mut self,
+        index: usize,
+        rulebook_ref: &str,
+        renderer: impl FnOnce(u64) -> Renderer<'a>,
+    ) -> Result<(RenderResult, String)> {
+        let result = renderer(self.seed(index))
+            .render_from_rulebook_with_options(rulebook_ref, Some(&mut self.used_entry_points))?;
+        let entry_point = self.used_entry_points.last().cloned().unwrap_or_default();
+        Ok((result, entry_point))
+    }
+}
+
+/// What phase 3 renders from
+#[derive(Clone, Copy)]
+struct RenderInputs<'r> {
+    references: &'r IndexMap<String, Reference>,
+    selected: &'r HashMap<String, Vec<SelectedValue>>,
+    /// Failed selections of references only used in optional segments
+    deferred: &'r HashMap<String, SelectionError>,
+    context: &'r Context,
+    namespace: &'r Namespace,
+}
+
+/// Whether an optional segment's condition holds for a set of selections
+pub(crate) fn condition_holds(
+    condition: &Expr,
+    selected: &HashMap<String, Vec<SelectedValue>>,
+    context: &Context,
+) -> bool {
+    condition
+        .evaluate(&|name| {
+            lookup_path(name, context, selected)
+                .or_else(|| context.get(name).ok().map(ExprValue::from))
+        })
+        .is_truthy()
+}
+
+/// Record the current value of a context key for the render trace
+fn context_write(context: &Context, key: &str, source: String) -> ContextWrite {
+    ContextWrite {
+        key: key.to_string(),
+        value: context
+            .get(key)
+            .map(|value| value.to_string())
+            .unwrap_or_default(),
+        source,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::core::{Datatype, DatatypeValue, Namespace, PackageMetadata, Reference};
+    use indexmap::IndexMap;
 
     fn create_test_package() -> Package {
-        let mut datatypes = HashMap::new();
+        let mut datatypes = IndexMap::new();
 
         // Colors
         datatypes.insert(
@@ -816,17 +1309,17 @@ mod tests {
                 values: vec![
                     DatatypeValue {
                         text: "red".to_string(),
-                        tags: HashMap::new(),
+                        tags: IndexMap::new(),
                         weight: 1.0,
                     },
                     DatatypeValue {
                         text: "blue".to_string(),
-                        tags: HashMap::new(),
+                        tags: IndexMap::new(),
                         weight: 1.0,
                     },
                 ],
                 extends: None,
-                override_tags: HashMap::new(),
+                override_tags: IndexMap::new(),
             },
         );
 
@@ -838,22 +1331,22 @@ mod tests {
                 values: vec![
                     DatatypeValue {
                         text: "ball".to_string(),
-                        tags: HashMap::new(),
+                        tags: IndexMap::new(),
                         weight: 1.0,
                     },
                     DatatypeValue {
                         text: "apple".to_string(),
-                        tags: HashMap::new(),
+                        tags: IndexMap::new(),
                         weight: 1.0,
                     },
                 ],
                 extends: None,
-                override_tags: HashMap::new(),
+                override_tags: IndexMap::new(),
             },
         );
 
-        let mut prompt_sections = HashMap::new();
-        let mut references = HashMap::new();
+        let mut prompt_sections = IndexMap::new();
+        let mut references = IndexMap::new();
         references.insert(
             "color".to_string(),
             Reference {
@@ -863,6 +1356,7 @@ mod tests {
                 max: 1,
                 separator: None,
                 unique: false,
+                fallback: Fallback::Error,
             },
         );
         references.insert(
@@ -874,6 +1368,7 @@ mod tests {
                 max: 1,
                 separator: None,
                 unique: false,
+                fallback: Fallback::Error,
             },
         );
 
@@ -886,17 +1381,17 @@ mod tests {
             },
         );
 
-        let mut namespaces = HashMap::new();
+        let mut namespaces = IndexMap::new();
         namespaces.insert(
             "test".to_string(),
             Namespace {
                 id: "test".to_string(),
                 datatypes,
                 prompt_sections,
-                separator_sets: HashMap::new(),
-                rules: HashMap::new(),
+                separator_sets: IndexMap::new(),
+                rules: IndexMap::new(),
                 decisions: Vec::new(),
-                rulebooks: HashMap::new(), // M9: Added rulebooks
+                rulebooks: IndexMap::new(), // M9: Added rulebooks
             },
         );
 
@@ -910,6 +1405,7 @@ mod tests {
                 bypass_filters: false,
             },
             namespaces,
+            default_namespace: None,
             dependencies: Vec::new(),
         }
     }
@@ -971,7 +1467,7 @@ mod tests {
                 weight: 1.0,
             }],
             batch_variety: false,
-            context_defaults: HashMap::new(),
+            context_defaults: IndexMap::new(),
         };
 
         package
@@ -995,7 +1491,7 @@ mod tests {
         let mut package = create_test_package();
 
         // Add another promptsection
-        let mut refs2 = HashMap::new();
+        let mut refs2 = IndexMap::new();
         refs2.insert(
             "color".to_string(),
             Reference {
@@ -1005,6 +1501,7 @@ mod tests {
                 max: 1,
                 separator: None,
                 unique: false,
+                fallback: Fallback::Error,
             },
         );
 
@@ -1037,7 +1534,7 @@ mod tests {
                 },
             ],
             batch_variety: true,
-            context_defaults: HashMap::new(),
+            context_defaults: IndexMap::new(),
         };
 
         package
@@ -1070,7 +1567,7 @@ mod tests {
 
         // Add a promptsection that uses context values
         // Template references context via rules
-        let mut refs = HashMap::new();
+        let mut refs = IndexMap::new();
         refs.insert(
             "item".to_string(),
             Reference {
@@ -1080,6 +1577,7 @@ mod tests {
                 max: 1,
                 separator: None,
                 unique: false,
+                fallback: Fallback::Error,
             },
         );
 
@@ -1098,7 +1596,7 @@ mod tests {
             );
 
         // Add a rulebook with context defaults
-        let mut context_defaults = HashMap::new();
+        let mut context_defaults = IndexMap::new();
         context_defaults.insert("test_key".to_string(), "test_value".to_string());
         context_defaults.insert("number".to_string(), "42".to_string());
 
@@ -1143,7 +1641,7 @@ mod tests {
         let mut package = create_test_package();
 
         // Add a promptsection
-        let mut refs = HashMap::new();
+        let mut refs = IndexMap::new();
         refs.insert(
             "color".to_string(),
             Reference {
@@ -1153,6 +1651,7 @@ mod tests {
                 max: 1,
                 separator: None,
                 unique: false,
+                fallback: Fallback::Error,
             },
         );
 
@@ -1171,7 +1670,7 @@ mod tests {
             );
 
         // Add a rulebook with scoped context defaults
-        let mut context_defaults = HashMap::new();
+        let mut context_defaults = IndexMap::new();
         context_defaults.insert("prompt:style".to_string(), "fantasy".to_string());
         context_defaults.insert("global:world".to_string(), "middle_earth".to_string());
 
@@ -1211,4 +1710,1083 @@ mod tests {
             "middle_earth"
         );
     }
+
+    #[test]
+    fn test_decisions_write_context() {
+        use crate::core::models::{ConditionalRule, Decision, Processor};
+
+        let mut package = create_test_package();
+
+        let mut refs = IndexMap::new();
+        refs.insert(
+            "color".to_string(),
+            Reference {
+                target: "test:colors".to_string(),
+                filter: None,
+                min: 1,
+                max: 1,
+                separator: None,
+                unique: false,
+                fallback: Fallback::Error,
+            },
+        );
+        refs.insert(
+            "mood".to_string(),
+            Reference {
+                target: "context:mood".to_string(),
+                filter: None,
+                min: 1,
+                max: 1,
+                separator: None,
+                unique: false,
+                fallback: Fallback::Error,
+            },
+        );
+
+        let namespace = package.namespaces.get_mut("test").unwrap();
+        namespace.prompt_sections.insert(
+            "moody".to_string(),
+            PromptSection {
+                name: "moody".to_string(),
+                template: "{mood} {color} sky".to_string(),
+                references: refs,
+            },
+        );
+        namespace.decisions.push(Decision {
+            name: "pick_mood".to_string(),
+            inputs: IndexMap::from([("color".to_string(), "text".to_string())]),
+            outputs: IndexMap::from([("mood".to_string(), "text".to_string())]),
+            bindings: IndexMap::new(),
+            processor: Processor::RuleSet {
+                rules: vec![
+                    ConditionalRule {
+                        condition: "color == \"red\"".to_string(),
+                        output: IndexMap::from([("mood".to_string(), serde_json::json!("angry"))]),
+                    },
+                    ConditionalRule {
+                        condition: String::new(),
+                        output: IndexMap::from([("mood".to_string(), serde_json::json!("calm"))]),
+                    },
+                ],
+            },
+        });
+
+        for seed in 0..10 {
+            let renderer = Renderer::new(&package, seed);
+            let result = renderer.render("moody").unwrap();
+            assert!(
+                result.output == "angry red sky" || result.output == "calm blue sky",
+                "unexpected output: {}",
+                result.output
+            );
+            assert!(result.selected_values.contains_key("context:mood"));
+        }
+    }
+
+    #[test]
+    fn test_filters_read_context() {
+        use crate::core::models::{ConditionalRule, Decision, Processor};
+        use crate::core::rulebook::{EntryPoint, Rulebook};
+
+        let mut package = create_test_package();
+        let namespace = package.namespaces.get_mut("test").unwrap();
+
+        let value = |text: &str, mood: &str| DatatypeValue {
+            text: text.to_string(),
+            tags: IndexMap::from([("mood".to_string(), serde_json::json!(mood))]),
+            weight: 1.0,
+        };
+        namespace.datatypes.insert(
+            "skies".to_string(),
+            Datatype {
+                name: "skies".to_string(),
+                values: vec![
+                    value("stormy", "dark"),
+                    value("sunny", "bright"),
+                    value("furious", "angry"),
+                    value("quiet", "calm"),
+                ],
+                extends: None,
+                override_tags: IndexMap::new(),
+            },
+        );
+
+        let section = |name: &str, template: &str| PromptSection {
+            name: name.to_string(),
+            template: template.to_string(),
+            references: IndexMap::new(),
+        };
+        // Nested sections see the rulebook defaults
+        namespace.prompt_sections.insert(
+            "sky".to_string(),
+            section("sky", "{skies#{tags.mood == context.prompt.mood}} sky"),
+        );
+        namespace
+            .prompt_sections
+            .insert("scene".to_string(), section("scene", "A {sky}"));
+
+        // Later references see the context writes of earlier nested sections
+        namespace.prompt_sections.insert(
+            "tinted".to_string(),
+            section("tinted", "{colors?min=1&max=1}"),
+        );
+        namespace.prompt_sections.insert(
+            "painting".to_string(),
+            section(
+                "painting",
+                "{tinted}, {skies#{context.prompt.tint && tags.mood == context.prompt.tint}}",
+            ),
+        );
+        namespace.decisions.push(Decision {
+            name: "tint".to_string(),
+            inputs: IndexMap::from([("colors".to_string(), "text".to_string())]),
+            outputs: IndexMap::from([("tint".to_string(), "text".to_string())]),
+            bindings: IndexMap::new(),
+            processor: Processor::RuleSet {
+                rules: vec![
+                    ConditionalRule {
+                        condition: "colors == \"red\"".to_string(),
+                        output: IndexMap::from([("tint".to_string(), serde_json::json!("angry"))]),
+                    },
+                    ConditionalRule {
+                        condition: "colors == \"blue\"".to_string(),
+                        output: IndexMap::from([("tint".to_string(), serde_json::json!("calm"))]),
+                    },
+                ],
+            },
+        });
+
+        namespace.rulebooks.insert(
+            "dark".to_string(),
+            Rulebook {
+                name: "dark".to_string(),
+                description: String::new(),
+                entry_points: vec![EntryPoint {
+                    prompt_section: "test:scene".to_string(),
+                    weight: 1.0,
+                }],
+                batch_variety: false,
+                context_defaults: IndexMap::from([("mood".to_string(), "dark".to_string())]),
+            },
+        );
+
+        for seed in 0..10 {
+            let renderer = Renderer::new(&package, seed);
+            assert_eq!(
+                renderer.render_from_rulebook("dark").unwrap().output,
+                "A stormy sky"
+            );
+
+            let output = renderer.render("painting").unwrap().output;
+            assert!(
+                output == "red, furious" || output == "blue, quiet",
+                "unexpected output: {}",
+                output
+            );
+        }
+    }
+
+    #[test]
+    fn test_selection_order_waits_for_context_writers() {
+        let package = create_test_package();
+        let renderer = Renderer::new(&package, 0);
+        let order = |template: &str| {
+            let parsed = Template::parse(template).unwrap();
+            let references = parsed.effective_references(&IndexMap::new(), "test");
+            renderer
+                .compute_selection_order(&references, &parsed)
+                .unwrap()
+        };
+
+        // Independent references keep their established order
+        assert_eq!(
+            order("{colors} {simple} {objects}"),
+            ["objects", "simple", "colors"]
+        );
+
+        // A filter reading the context waits for the nested promptsections before it
+        assert_eq!(
+            order("{colors} {simple} {objects#{context.prompt.tint}}"),
+            ["simple", "objects", "colors"]
+        );
+    }
+
+    #[test]
+    fn test_render_inherited_datatype() {
+        let content = std::fs::read_to_string("../test-packages/inheritance-test.yaml")
+            .expect("Failed to read inheritance-test.yaml");
+        let package = crate::parser::parse_yaml(&content).unwrap();
+
+        // Only owl (inherited, overridden to magical) and dragon pass the filter
+        for seed in 0..20 {
+            let renderer = Renderer::new(&package, seed);
+            let result = renderer.render("magical_flyer").unwrap();
+            assert!(
+                result.output == "a magical owl" || result.output == "a magical dragon",
+                "unexpected output: {}",
+                result.output
+            );
+        }
+    }
+
+    #[test]
+    fn test_nested_sections_use_independent_streams() {
+        let mut package = create_test_package();
+
+        let mut refs = IndexMap::new();
+        for name in ["first", "second"] {
+            refs.insert(
+                name.to_string(),
+                Reference {
+                    target: "test:simple".to_string(),
+                    filter: None,
+                    min: 1,
+                    max: 1,
+                    separator: None,
+                    unique: false,
+                    fallback: Fallback::Error,
+                },
+            );
+        }
+
+        package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .insert(
+                "pair".to_string(),
+                PromptSection {
+                    name: "pair".to_string(),
+                    template: "{first} / {second}".to_string(),
+                    references: refs,
+                },
+            );
+
+        // Same section nested twice must not always replay the same choices
+        let outputs: Vec<String> = (0..20)
+            .map(|seed| Renderer::new(&package, seed).render("pair").unwrap().output)
+            .collect();
+        assert!(outputs.iter().any(|output| {
+            let (first, second) = output.split_once(" / ").unwrap();
+            first != second
+        }));
+
+        // Still deterministic
+        let again = Renderer::new(&package, 7).render("pair").unwrap().output;
+        assert_eq!(again, outputs[7]);
+    }
+
+    #[test]
+    fn test_count_draws_are_independent() {
+        let mut package = create_test_package();
+
+        // Equal-length reference names used to share a count seed
+        let mut refs = IndexMap::new();
+        for name in ["aa", "bb"] {
+            refs.insert(
+                name.to_string(),
+                Reference {
+                    target: "test:colors".to_string(),
+                    filter: None,
+                    min: 1,
+                    max: 4,
+                    separator: None,
+                    unique: false,
+                    fallback: Fallback::Error,
+                },
+            );
+        }
+
+        package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .insert(
+                "counts".to_string(),
+                PromptSection {
+                    name: "counts".to_string(),
+                    template: "{aa}|{bb}".to_string(),
+                    references: refs,
+                },
+            );
+
+        let differs = (0..20).any(|seed| {
+            let output = Renderer::new(&package, seed)
+                .render("counts")
+                .unwrap()
+                .output;
+            let (aa, bb) = output.split_once('|').unwrap();
+            aa.split_whitespace().count() != bb.split_whitespace().count()
+        });
+        assert!(differs);
+    }
+
+    #[test]
+    fn test_reference_picks_are_independent() {
+        let mut package = create_test_package();
+
+        let mut refs = IndexMap::new();
+        for name in ["c0", "c1", "c2"] {
+            refs.insert(
+                name.to_string(),
+                Reference {
+                    target: "test:colors".to_string(),
+                    filter: None,
+                    min: 1,
+                    max: 1,
+                    separator: None,
+                    unique: false,
+                    fallback: Fallback::Error,
+                },
+            );
+        }
+
+        let sections = &mut package.namespaces.get_mut("test").unwrap().prompt_sections;
+        for (name, template) in [("two", "{c1} {c2}"), ("three", "{c1} {c2} {c0}")] {
+            sections.insert(
+                name.to_string(),
+                PromptSection {
+                    name: name.to_string(),
+                    template: template.to_string(),
+                    references: refs.clone(),
+                },
+            );
+        }
+
+        // Adding an unrelated reference leaves the existing picks alone
+        for seed in 0..20 {
+            let renderer = Renderer::new(&package, seed);
+            let two = renderer.render("two").unwrap().output;
+            let three = renderer.render("three").unwrap().output;
+            assert!(
+                three.starts_with(&format!("{} ", two)),
+                "{} / {}",
+                two,
+                three
+            );
+        }
+    }
+
+    #[test]
+    fn test_inline_parameters_without_references_block() {
+        let mut package = create_test_package();
+
+        package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .insert(
+                "compact".to_string(),
+                PromptSection {
+                    name: "compact".to_string(),
+                    template: "{colors?min=2,max=2&unique} {objects}".to_string(),
+                    references: IndexMap::new(),
+                },
+            );
+
+        for seed in 0..10 {
+            let output = Renderer::new(&package, seed)
+                .render("compact")
+                .unwrap()
+                .output;
+            assert!(
+                output.starts_with("red blue ") || output.starts_with("blue red "),
+                "unexpected output: {}",
+                output
+            );
+        }
+    }
+
+    #[test]
+    fn test_inline_parameters_override_yaml() {
+        let mut package = create_test_package();
+
+        let section = package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .get_mut("simple")
+            .unwrap();
+        section.template = "A {color?min=2&unique} {object}".to_string();
+
+        for seed in 0..10 {
+            let result = Renderer::new(&package, seed).render("simple").unwrap();
+            assert!(result.output.contains("red") && result.output.contains("blue"));
+        }
+
+        // An inline filter replaces the YAML filter (no object has this tag)
+        let section = package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .get_mut("simple")
+            .unwrap();
+        section.template = "A {color} {object#{tags.missing}}".to_string();
+        assert!(Renderer::new(&package, 1).render("simple").is_err());
+    }
+
+    #[test]
+    fn test_qualified_separator_from_dependency() {
+        use crate::core::SeparatorSet;
+
+        let mut package = create_test_package();
+        package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .get_mut("simple")
+            .unwrap()
+            .template = "{color?min=2&unique&sep=common:oxford_comma}".to_string();
+
+        // Unresolved separators are errors, not a silent fallback to spaces
+        let err = Renderer::new(&package, 1).render("simple").unwrap_err();
+        assert!(
+            matches!(err, RenderError::SeparatorNotFound(ref sep, _) if sep == "common:oxford_comma")
+        );
+
+        let mut common = create_test_package();
+        common.id = "common.package".to_string();
+        let mut namespace = common.namespaces.shift_remove("test").unwrap();
+        namespace.id = "common".to_string();
+        namespace.separator_sets.insert(
+            "oxford_comma".to_string(),
+            SeparatorSet {
+                name: "oxford_comma".to_string(),
+                primary: ", ".to_string(),
+                secondary: " and ".to_string(),
+                tertiary: None,
+            },
+        );
+        common.namespaces.insert("common".to_string(), namespace);
+
+        let mut dependencies = HashMap::new();
+        dependencies.insert(common.id.clone(), common);
+
+        let output = Renderer::new_with_dependencies(&package, &dependencies, 1)
+            .render("simple")
+            .unwrap()
+            .output;
+        assert!(
+            output == "red and blue" || output == "blue and red",
+            "unexpected output: {}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_package_qualified_references() {
+        let mut package = create_test_package();
+        package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .get_mut("simple")
+            .unwrap()
+            .template = "{common:colors}".to_string();
+
+        // Two dependencies provide namespace "common"
+        let mut dependencies = HashMap::new();
+        for (id, color) in [("dep.a", "green"), ("dep.b", "purple")] {
+            let mut dep = create_test_package();
+            dep.id = id.to_string();
+            let mut namespace = dep.namespaces.shift_remove("test").unwrap();
+            namespace.id = "common".to_string();
+            namespace.datatypes.get_mut("colors").unwrap().values = vec![DatatypeValue {
+                text: color.to_string(),
+                tags: IndexMap::new(),
+                weight: 1.0,
+            }];
+            dep.namespaces.insert("common".to_string(), namespace);
+            dependencies.insert(id.to_string(), dep);
+        }
+
+        let err = Renderer::new_with_dependencies(&package, &dependencies, 1)
+            .render("simple")
+            .unwrap_err();
+        assert!(err.to_string().contains("Ambiguous"), "{}", err);
+
+        let section = package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .get_mut("simple")
+            .unwrap();
+        section.template = "{dep.b/common:colors}".to_string();
+        let result = Renderer::new_with_dependencies(&package, &dependencies, 1)
+            .render("simple")
+            .unwrap();
+        assert_eq!(result.output, "purple");
+
+        // Promptsections can be addressed the same way
+        let result = Renderer::new_with_dependencies(&package, &dependencies, 1)
+            .render("dep.a/common:simple")
+            .unwrap();
+        assert!(result.output.starts_with("A "));
+    }
+
+    #[test]
+    fn test_conditional_segments() {
+        let mut package = create_test_package();
+        let namespace = package.namespaces.get_mut("test").unwrap();
+
+        let mut references = IndexMap::new();
+        references.insert(
+            "shade".to_string(),
+            Reference {
+                target: "test:colors".to_string(),
+                filter: None,
+                min: 0,
+                max: 1,
+                separator: None,
+                unique: false,
+                fallback: Fallback::Error,
+            },
+        );
+        namespace.prompt_sections.insert(
+            "optional".to_string(),
+            PromptSection {
+                name: "optional".to_string(),
+                template:
+                    "A {objects}{?ref:shade} in {shade}{/?}{?ref:objects == \"ball\"}!{?else}.{/?}"
+                        .to_string(),
+                references,
+            },
+        );
+
+        let outputs: Vec<String> = (0..40)
+            .map(|seed| {
+                Renderer::new(&package, seed)
+                    .render("optional")
+                    .unwrap()
+                    .output
+            })
+            .collect();
+
+        for output in &outputs {
+            let ends = if output.starts_with("A ball") {
+                "!"
+            } else {
+                "."
+            };
+            assert!(output.ends_with(ends), "unexpected output: {}", output);
+            assert!(
+                !output.contains(" in ."),
+                "empty segment rendered: {}",
+                output
+            );
+            assert!(
+                !output.contains(" in !"),
+                "empty segment rendered: {}",
+                output
+            );
+        }
+        assert!(outputs.iter().any(|o| o.contains(" in ")));
+        assert!(outputs.iter().any(|o| !o.contains(" in ")));
+    }
+
+    #[test]
+    fn test_failed_selection_in_skipped_segment() {
+        let mut package = create_test_package();
+        let namespace = package.namespaces.get_mut("test").unwrap();
+
+        let mut references = IndexMap::new();
+        references.insert(
+            "moon".to_string(),
+            Reference {
+                target: "test:colors".to_string(),
+                filter: Some("tags.phase == context.phase".to_string()),
+                min: 1,
+                max: 1,
+                separator: None,
+                unique: false,
+                fallback: Fallback::Error,
+            },
+        );
+        for (name, template) in [
+            ("night", "a night{?context.show} under {moon}{/?}"),
+            ("dark", "a night{?not context.show} under {moon}{/?}"),
+        ] {
+            namespace.prompt_sections.insert(
+                name.to_string(),
+                PromptSection {
+                    name: name.to_string(),
+                    template: template.to_string(),
+                    references: references.clone(),
+                },
+            );
+        }
+
+        // Nothing matches, but the segment never renders
+        let result = Renderer::new(&package, 1).render("night").unwrap();
+        assert_eq!(result.output, "a night");
+
+        // The failure still surfaces when the segment renders
+        assert!(matches!(
+            Renderer::new(&package, 1).render("dark"),
+            Err(RenderError::Selection(SelectionError::NoMatchingValues(_)))
+        ));
+    }
+
+    #[test]
+    fn test_pipe_transforms() {
+        let mut package = create_test_package();
+        package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .insert(
+                "piped".to_string(),
+                PromptSection {
+                    name: "piped".to_string(),
+                    template:
+                        "{objects|a_an|capitalize}, {colors?min=2&unique|upper}, {objects|plural}"
+                            .to_string(),
+                    references: IndexMap::new(),
+                },
+            );
+
+        for seed in 0..10 {
+            let output = Renderer::new(&package, seed)
+                .render("piped")
+                .unwrap()
+                .output;
+            let (article, object) = output.split_once(',').unwrap().0.split_once(' ').unwrap();
+            assert_eq!(article, if object == "apple" { "An" } else { "A" });
+            assert!(
+                output.contains(", RED BLUE, ") || output.contains(", BLUE RED, "),
+                "unexpected output: {}",
+                output
+            );
+            assert!(output.ends_with("balls") || output.ends_with("apples"));
+        }
+
+        // Embedders can register their own transforms
+        let mut transforms = TransformRegistry::default();
+        transforms.register("shout", |text| format!("{}!", text.to_uppercase()));
+        let section = package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .get_mut("piped")
+            .unwrap();
+        section.template = "{objects|shout}".to_string();
+
+        let output = Renderer::new(&package, 1)
+            .with_transforms(&transforms)
+            .render("piped")
+            .unwrap()
+            .output;
+        assert!(output == "BALL!" || output == "APPLE!", "{}", output);
+        assert!(matches!(
+            Renderer::new(&package, 1).render("piped"),
+            Err(RenderError::UnknownTransform(..))
+        ));
+    }
+
+    #[test]
+    fn test_automatic_articles() {
+        let mut package = create_test_package();
+        let namespace = package.namespaces.get_mut("test").unwrap();
+        for (name, template) in [
+            (
+                "articles",
+                "{A/An} {objects} and {a/an} {colors?min=2&unique}",
+            ),
+            ("inner", "{colors} thing"),
+            ("outer", "{a/an} {inner}"),
+        ] {
+            namespace.prompt_sections.insert(
+                name.to_string(),
+                PromptSection {
+                    name: name.to_string(),
+                    template: template.to_string(),
+                    references: IndexMap::new(),
+                },
+            );
+        }
+
+        for seed in 0..10 {
+            let output = Renderer::new(&package, seed)
+                .render("articles")
+                .unwrap()
+                .output;
+            assert!(
+                output.starts_with("An apple and a ") || output.starts_with("A ball and a "),
+                "unexpected output: {}",
+                output
+            );
+        }
+
+        // An explicit article tag wins, also through a nested promptsection
+        let colors = &mut package.namespaces.get_mut("test").unwrap().datatypes["colors"];
+        for value in &mut colors.values {
+            value
+                .tags
+                .insert("article".to_string(), serde_json::json!("an"));
+        }
+        for seed in 0..5 {
+            let output = Renderer::new(&package, seed)
+                .render("outer")
+                .unwrap()
+                .output;
+            assert!(output.starts_with("an "), "unexpected output: {}", output);
+        }
+    }
+
+    #[test]
+    fn test_chance_segments() {
+        let mut package = create_test_package();
+        package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .insert(
+                "gated".to_string(),
+                PromptSection {
+                    name: "gated".to_string(),
+                    template: "{objects}{%50} now{/%}{%0} never{/%}{%100}!{/%}".to_string(),
+                    references: IndexMap::new(),
+                },
+            );
+
+        let render = |seed| {
+            Renderer::new(&package, seed)
+                .render("gated")
+                .unwrap()
+                .output
+        };
+        let outputs: Vec<String> = (0..40).map(render).collect();
+
+        assert!(outputs
+            .iter()
+            .all(|o| o.ends_with('!') && !o.contains("never")));
+        assert!(outputs.iter().any(|o| o.contains(" now")));
+        assert!(outputs.iter().any(|o| !o.contains(" now")));
+
+        // Gates draw from the seeded RNG
+        assert_eq!(render(7), outputs[7]);
+    }
+
+    #[test]
+    fn test_unqualified_reference_uses_default_namespace() {
+        let mut package = create_test_package();
+
+        // A second namespace with its own "simple" promptsection
+        let mut other = package.namespaces["test"].clone();
+        other.id = "aaa".to_string();
+        other.prompt_sections.get_mut("simple").unwrap().template = "other".to_string();
+        package.namespaces.insert("aaa".to_string(), other);
+
+        let output = Renderer::new(&package, 1).render("simple").unwrap().output;
+        assert_eq!(output, "other");
+
+        package.default_namespace = Some("test".to_string());
+        let output = Renderer::new(&package, 1).render("simple").unwrap().output;
+        assert!(output.starts_with("A "));
+    }
+
+    #[test]
+    fn test_rules_see_all_selected_values() {
+        use crate::core::models::Rule;
+
+        let mut package = create_test_package();
+
+        let mut refs = IndexMap::new();
+        refs.insert(
+            "colors".to_string(),
+            Reference {
+                target: "test:colors".to_string(),
+                filter: None,
+                min: 1,
+                max: 2,
+                separator: None,
+                unique: true,
+                fallback: Fallback::Error,
+            },
+        );
+        refs.insert(
+            "verb".to_string(),
+            Reference {
+                target: "context:verb".to_string(),
+                filter: None,
+                min: 1,
+                max: 1,
+                separator: None,
+                unique: false,
+                fallback: Fallback::Error,
+            },
+        );
+
+        let namespace = package.namespaces.get_mut("test").unwrap();
+        namespace.prompt_sections.insert(
+            "agreement".to_string(),
+            PromptSection {
+                name: "agreement".to_string(),
+                template: "{colors} {verb} bright".to_string(),
+                references: refs,
+            },
+        );
+        for (id, logic, verb) in [("plural", "> 1", "are"), ("singular", "== 1", "is")] {
+            namespace.rules.insert(
+                id.to_string(),
+                Rule {
+                    when: "ref:colors.count".to_string(),
+                    logic: logic.to_string(),
+                    set: "context.prompt.verb".to_string(),
+                    value: verb.to_string(),
+                    priority: 0,
+                    after: Vec::new(),
+                },
+            );
+        }
+
+        let mut seen = (false, false);
+        for seed in 0..20 {
+            let output = Renderer::new(&package, seed)
+                .render("agreement")
+                .unwrap()
+                .output;
+            let plural = output.contains("red") && output.contains("blue");
+            if plural {
+                assert!(
+                    output.ends_with("are bright"),
+                    "unexpected output: {}",
+                    output
+                );
+                seen.0 = true;
+            } else {
+                assert!(
+                    output.ends_with("is bright"),
+                    "unexpected output: {}",
+                    output
+                );
+                seen.1 = true;
+            }
+        }
+        assert!(seen.0 && seen.1);
+    }
+
+    #[test]
+    fn test_filter_fallbacks() {
+        let mut package = create_test_package();
+        let namespace = package.namespaces.get_mut("test").unwrap();
+
+        let mut references = IndexMap::new();
+        references.insert(
+            "color".to_string(),
+            Reference {
+                target: "test:colors".to_string(),
+                filter: Some("tags.shiny".to_string()),
+                min: 2,
+                max: 2,
+                separator: None,
+                unique: true,
+                fallback: Fallback::Error,
+            },
+        );
+        namespace.prompt_sections.insert(
+            "shiny".to_string(),
+            PromptSection {
+                name: "shiny".to_string(),
+                template: "[{color}] {objects}".to_string(),
+                references,
+            },
+        );
+
+        let render = |package: &Package, fallback: Fallback| {
+            let mut package = package.clone();
+            package.namespaces.get_mut("test").unwrap().prompt_sections["shiny"].references
+                ["color"]
+                .fallback = fallback;
+            Renderer::new(&package, 7)
+                .with_trace(true)
+                .render("shiny")
+                .map(|result| {
+                    let trace = result.trace.unwrap();
+                    let color = trace
+                        .references
+                        .iter()
+                        .find(|reference| reference.name == "color")
+                        .unwrap();
+                    (result.output, color.fallback.clone(), color.candidates)
+                })
+        };
+
+        assert!(matches!(
+            render(&package, Fallback::Error),
+            Err(RenderError::Selection(SelectionError::NoMatchingValues(_)))
+        ));
+
+        let (output, fallback, candidates) = render(&package, Fallback::Unfiltered).unwrap();
+        assert!(output.starts_with("[red blue]") || output.starts_with("[blue red]"));
+        assert_eq!(fallback, Some(Fallback::Unfiltered));
+        assert_eq!(candidates, 2);
+
+        let (output, fallback, _) = render(&package, Fallback::Empty).unwrap();
+        assert!(output.starts_with("[] "), "unexpected output: {}", output);
+        assert_eq!(fallback, Some(Fallback::Empty));
+
+        let default = Fallback::Default("plain".to_string());
+        let (output, fallback, _) = render(&package, default.clone()).unwrap();
+        assert!(
+            output.starts_with("[plain] "),
+            "unexpected output: {}",
+            output
+        );
+        assert_eq!(fallback, Some(default));
+    }
+
+    #[test]
+    fn test_trace_records_tree() {
+        use crate::core::models::Rule;
+
+        let mut package = create_test_package();
+
+        let mut refs = IndexMap::new();
+        refs.insert(
+            "inner".to_string(),
+            Reference {
+                target: "test:simple".to_string(),
+                filter: None,
+                min: 1,
+                max: 1,
+                separator: None,
+                unique: false,
+                fallback: Fallback::Error,
+            },
+        );
+
+        let namespace = package.namespaces.get_mut("test").unwrap();
+        namespace.prompt_sections.insert(
+            "outer".to_string(),
+            PromptSection {
+                name: "outer".to_string(),
+                template: "[{inner}]".to_string(),
+                references: refs,
+            },
+        );
+        namespace.rules.insert(
+            "remember_color".to_string(),
+            Rule {
+                when: "ref:color".to_string(),
+                logic: String::new(),
+                set: "context.prompt.color".to_string(),
+                value: "ref:color.text".to_string(),
+                priority: 0,
+                after: Vec::new(),
+            },
+        );
+
+        // Off by default
+        assert!(Renderer::new(&package, 1)
+            .render("outer")
+            .unwrap()
+            .trace
+            .is_none());
+
+        let result = Renderer::new(&package, 1)
+            .with_trace(true)
+            .render("outer")
+            .unwrap();
+        let trace = result.trace.unwrap();
+
+        assert_eq!(trace.promptsection, "outer");
+        assert_eq!(trace.output, result.output);
+        assert_eq!(trace.references.len(), 1);
+
+        // The nested section carries its own trace
+        let inner = trace.references[0].section.as_ref().unwrap();
+        assert_eq!(format!("[{}]", inner.output), result.output);
+        assert_ne!(inner.seed, trace.seed);
+
+        let color = inner.references.iter().find(|r| r.name == "color").unwrap();
+        assert_eq!(color.candidates, 2);
+        assert_eq!(color.chosen.len(), 1);
+        assert_eq!(color.chosen[0].weight, 1.0);
+        assert!(inner.output.contains(&color.chosen[0].text));
+
+        // The rule fired for the inner section only
+        assert!(matches!(
+            trace.rules[0].outcome,
+            RuleOutcome::ReferenceMissing
+        ));
+        assert_eq!(inner.rules[0].rule, "test:remember_color");
+        assert!(matches!(
+            inner.rules[0].outcome,
+            RuleOutcome::Applied { .. }
+        ));
+        assert_eq!(inner.context_writes.len(), 1);
+        assert_eq!(inner.context_writes[0].value, color.chosen[0].text);
+        assert_eq!(inner.context_writes[0].source, "rule test:remember_color");
+
+        // Tracing does not change the output
+        let plain = Renderer::new(&package, 1).render("outer").unwrap();
+        assert_eq!(plain.output, result.output);
+    }
+
+    #[test]
+    fn test_observer_receives_events() {
+        use crate::renderer::observer::RenderObserver;
+        use std::cell::RefCell;
+
+        #[derive(Default)]
+        struct Recorder {
+            events: RefCell<Vec<String>>,
+        }
+
+        impl RenderObserver for Recorder {
+            fn phase_started(&self, promptsection: &str, phase: RenderPhase, depth: usize) {
+                self.events
+                    .borrow_mut()
+                    .push(format!("{} {} {}", depth, promptsection, phase));
+            }
+
+            fn values_selected(&self, reference: &str, _target: &str, values: &[SelectedValue]) {
+                self.events
+                    .borrow_mut()
+                    .push(format!("select {} x{}", reference, values.len()));
+            }
+
+            fn section_rendered(&self, promptsection: &str, _output: &str, _depth: usize) {
+                self.events
+                    .borrow_mut()
+                    .push(format!("done {}", promptsection));
+            }
+        }
+
+        let package = create_test_package();
+        let recorder = Recorder::default();
+        let result = Renderer::new(&package, 42)
+            .with_observer(&recorder)
+            .render("simple")
+            .unwrap();
+
+        let events = recorder.events.into_inner();
+        assert_eq!(events[0], "0 simple Selection");
+        assert!(events.contains(&"select color x1".to_string()));
+        assert!(events.contains(&"select object x1".to_string()));
+        assert_eq!(
+            events[events.len() - 3..],
+            [
+                "0 simple Enrichment".to_string(),
+                "0 simple Rendering".to_string(),
+                "done simple".to_string()
+            ]
+        );
+
+        // Observing does not change the output
+        let plain = Renderer::new(&package, 42).render("simple").unwrap();
+        assert_eq!(plain.output, result.output);
+    }
 }
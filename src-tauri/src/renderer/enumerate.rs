@@ -0,0 +1,1026 @@
+// Output Enumeration
+// Walks every combination of choices a promptsection can make
+//
+// The walk mirrors phase 1 of the renderer: references in selection order (so
+// cross-reference filters see earlier choices), count draws between min and
+// max, weighted picks with or without replacement, filter fallbacks and nested
+// promptsections. Each complete combination is then enriched and rendered by
+// the engine itself, so rules, conditions, transforms and articles behave
+// exactly as in a render. Probability gates the render reaches branch the walk
+// into shown and hidden; gates inside skipped segments are never expanded.
+//
+// Combinations are produced lazily, depth first. A nested promptsection is
+// walked lazily too, one combination at a time, whenever a combination reaches
+// it (it inherits that combination's context).
+
+use crate::context::Context;
+use crate::core::{DatatypeValue, Fallback, Namespace, Reference};
+use crate::renderer::engine::{
+    condition_holds, RenderError, Renderer, Result, MAX_RECURSION_DEPTH,
+};
+use crate::renderer::selector::{SelectedValue, SelectionError};
+use crate::renderer::template_parser::{Template, TemplateToken};
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// One combination of choices and what it renders to
+#[derive(Debug, Clone, Serialize)]
+pub struct EnumeratedPath {
+    /// Probability of this combination
+    pub probability: f64,
+
+    /// Chosen value texts per reference, in selection order; choices made
+    /// inside a nested promptsection are listed as "reference.inner"
+    pub choices: IndexMap<String, Vec<String>>,
+
+    /// The rendered output, or why this combination fails
+    #[serde(flatten)]
+    pub result: PathResult,
+}
+
+/// Outcome of one combination
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathResult {
+    Output(String),
+    Error(String),
+}
+
+/// Totals over an enumeration
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EnumerationSummary {
+    /// Combinations that render
+    pub paths: usize,
+
+    /// Combinations that fail (e.g. a filter without fallback matching nothing)
+    pub failures: usize,
+
+    /// Probability that a render fails
+    pub failure_probability: f64,
+
+    /// Distinct outputs with their total probability, most likely first
+    pub outputs: IndexMap<String, f64>,
+
+    /// Datatype values that no rendering combination chooses, per reference
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub unreachable: IndexMap<String, Vec<String>>,
+
+    /// The walk stopped at the path limit (totals are lower bounds)
+    pub truncated: bool,
+}
+
+/// Lazy enumeration of a promptsection's combinations (see `Renderer::enumerate`)
+pub struct Enumeration<'r, 'a> {
+    section: String,
+    walk: SectionWalk<'r, 'a>,
+}
+
+impl<'a> Renderer<'a> {
+    /// Enumerate every way a promptsection can render, with probabilities
+    ///
+    /// Yields one `EnumeratedPath` per combination of choices; several
+    /// combinations can render the same output (`Enumeration::summarize`
+    /// merges them). Seeds play no part: all outcomes are walked.
+    pub fn enumerate(&self, promptsection_ref: &str) -> Result<Enumeration<'_, 'a>> {
+        self.enumerate_with_context(promptsection_ref, &IndexMap::new())
+    }
+
+    /// Enumerate starting from initial context values (rulebook `context_defaults`)
+    pub(crate) fn enumerate_with_context(
+        &self,
+        promptsection_ref: &str,
+        initial_context: &IndexMap<String, String>,
+    ) -> Result<Enumeration<'_, 'a>> {
+        let mut context = Context::new();
+        let mut defaults: Vec<(&String, &String)> = initial_context.iter().collect();
+        defaults.sort();
+        for (key, value) in defaults {
+            context.set(key, value.clone())?;
+        }
+
+        Ok(Enumeration {
+            section: promptsection_ref.to_string(),
+            walk: SectionWalk::new(self, promptsection_ref, 0, context)?,
+        })
+    }
+}
+
+impl Enumeration<'_, '_> {
+    /// Count combinations, merge distinct outputs and find unreachable values
+    ///
+    /// Stops after `limit` combinations (rendering or failing), if given.
+    pub fn summarize(self, limit: Option<usize>) -> EnumerationSummary {
+        let renderer = self.walk.renderer;
+        let section = self.section.clone();
+
+        let mut summary = EnumerationSummary::default();
+        let mut chosen: HashMap<String, HashSet<String>> = HashMap::new();
+
+        for path in self {
+            if limit.is_some_and(|limit| summary.paths + summary.failures >= limit) {
+                summary.truncated = true;
+                break;
+            }
+
+            match path.result {
+                PathResult::Output(output) => {
+                    summary.paths += 1;
+                    *summary.outputs.entry(output).or_default() += path.probability;
+                    for (reference, texts) in path.choices {
+                        chosen.entry(reference).or_default().extend(texts);
+                    }
+                }
+                PathResult::Error(_) => {
+                    summary.failures += 1;
+                    summary.failure_probability += path.probability;
+                }
+            }
+        }
+
+        summary.outputs.sort_by(|_, a, _, b| b.total_cmp(a));
+
+        for (reference, target) in datatype_references(renderer, &section, "", 0) {
+            let Ok(values) = renderer
+                .selector()
+                .candidates(&target, None, &HashMap::new())
+            else {
+                continue;
+            };
+
+            let reached = chosen.get(&reference);
+            let mut missing: Vec<String> = Vec::new();
+            for value in values {
+                if !reached.is_some_and(|texts| texts.contains(&value.text))
+                    && !missing.contains(&value.text)
+                {
+                    missing.push(value.text);
+                }
+            }
+            if !missing.is_empty() {
+                summary.unreachable.insert(reference, missing);
+            }
+        }
+
+        summary
+    }
+}
+
+impl Iterator for Enumeration<'_, '_> {
+    type Item = EnumeratedPath;
+
+    fn next(&mut self) -> Option<EnumeratedPath> {
+        let leaf = self.walk.next()?;
+        Some(EnumeratedPath {
+            probability: leaf.probability,
+            choices: leaf.choices,
+            result: match leaf.result {
+                Ok(rendered) => PathResult::Output(rendered.output),
+                Err(error) => PathResult::Error(error),
+            },
+        })
+    }
+}
+
+/// Depth-first walk over one promptsection's combinations
+struct SectionWalk<'r, 'a> {
+    renderer: &'r Renderer<'a>,
+    depth: usize,
+    parsed: Template,
+    references: IndexMap<String, Reference>,
+    namespace: &'r Namespace,
+    /// Reference names in selection order
+    order: Vec<String>,
+    /// References only used inside optional segments
+    optional: Vec<String>,
+    /// Percent of each probability gate, by segment index
+    chances: Vec<u32>,
+    stack: Vec<Node<'r, 'a>>,
+}
+
+/// A combination with the first `next` references chosen
+#[derive(Clone)]
+struct Partial {
+    next: usize,
+    probability: f64,
+    selected: HashMap<String, Vec<SelectedValue>>,
+    /// Failed selections of optional references (see `phase_1_selection`)
+    deferred: HashMap<String, SelectionError>,
+    choices: IndexMap<String, Vec<String>>,
+    context: Context,
+}
+
+/// An enriched combination with the probability gates decided so far
+struct Gated {
+    probability: f64,
+    selected: HashMap<String, Vec<SelectedValue>>,
+    deferred: HashMap<String, SelectionError>,
+    choices: IndexMap<String, Vec<String>>,
+    context: Context,
+    /// Outcome of each gate, by segment index (None: not decided yet)
+    outcomes: Vec<Option<bool>>,
+}
+
+/// A complete combination
+struct Leaf {
+    probability: f64,
+    choices: IndexMap<String, Vec<String>>,
+    result: std::result::Result<Rendered, String>,
+}
+
+struct Rendered {
+    output: String,
+    leading_article: Option<String>,
+    context: Context,
+}
+
+/// A nested promptsection's walk, for the partial combination that reached it
+struct Nested<'r, 'a> {
+    partial: Partial,
+    name: String,
+    walk: SectionWalk<'r, 'a>,
+}
+
+enum Node<'r, 'a> {
+    Partial(Partial),
+    Gated(Gated),
+    /// Yields one child per combination of the nested promptsection
+    Nested(Box<Nested<'r, 'a>>),
+    Done(Leaf),
+}
+
+impl<'r, 'a> SectionWalk<'r, 'a> {
+    fn new(
+        renderer: &'r Renderer<'a>,
+        promptsection_ref: &str,
+        depth: usize,
+        context: Context,
+    ) -> Result<Self> {
+        if depth > MAX_RECURSION_DEPTH {
+            return Err(RenderError::MaxRecursionDepth(
+                MAX_RECURSION_DEPTH,
+                promptsection_ref.to_string(),
+            ));
+        }
+
+        let promptsection = renderer.find_promptsection(promptsection_ref)?;
+        let namespace = renderer.find_namespace_for_promptsection(promptsection_ref)?;
+        let parsed = Template::parse(&promptsection.template)?;
+        let references = parsed.effective_references(&promptsection.references, &namespace.id);
+        let order = renderer.compute_selection_order(&references, &parsed)?;
+        let optional = parsed.optional_references();
+
+        let mut chances = Vec::new();
+        collect_chances(&parsed.tokens, &mut chances);
+
+        Ok(SectionWalk {
+            renderer,
+            depth,
+            parsed,
+            references,
+            namespace,
+            order,
+            optional,
+            chances,
+            stack: vec![Node::Partial(Partial {
+                next: 0,
+                probability: 1.0,
+                selected: HashMap::new(),
+                deferred: HashMap::new(),
+                choices: IndexMap::new(),
+                context,
+            })],
+        })
+    }
+
+    /// Children of a partial combination: one per choice for its next reference
+    fn expand(&self, partial: Partial) -> Vec<Node<'r, 'a>> {
+        let name = &self.order[partial.next];
+        let Some(reference) = self.references.get(name) else {
+            let error = RenderError::ReferenceNotSelected(name.clone());
+            return vec![failed(&partial, partial.probability, error.to_string())];
+        };
+
+        // Context references are filled in by rules during enrichment
+        if reference.target.starts_with("context:") {
+            return vec![Node::Partial(Partial {
+                next: partial.next + 1,
+                ..partial
+            })];
+        }
+
+        match self.renderer.is_promptsection_reference(&reference.target) {
+            Ok(true) => self.expand_section(partial, name, reference),
+            Ok(false) => self.expand_datatype(partial, name, reference),
+            Err(e) => vec![failed(&partial, partial.probability, e.to_string())],
+        }
+    }
+
+    /// A nested promptsection: one child per combination of the nested section,
+    /// walked as the children are needed
+    fn expand_section(
+        &self,
+        partial: Partial,
+        name: &str,
+        reference: &Reference,
+    ) -> Vec<Node<'r, 'a>> {
+        match SectionWalk::new(
+            self.renderer,
+            &reference.target,
+            self.depth + 1,
+            partial.context.clone(),
+        ) {
+            Ok(walk) => vec![Node::Nested(Box::new(Nested {
+                partial,
+                name: name.to_string(),
+                walk,
+            }))],
+            Err(e) => vec![failed(&partial, partial.probability, e.to_string())],
+        }
+    }
+
+    /// A datatype: one child per count and sequence of picked values
+    fn expand_datatype(
+        &self,
+        partial: Partial,
+        name: &str,
+        reference: &Reference,
+    ) -> Vec<Node<'r, 'a>> {
+        let (min, max) = (reference.min, reference.max);
+        if min > max {
+            let error = format!("Reference '{}' has min {} > max {}", name, min, max);
+            return vec![failed(&partial, partial.probability, error)];
+        }
+        let count_probability = 1.0 / (max - min + 1) as f64;
+
+        // The values to pick from, after filters and fallbacks
+        let selector = self
+            .renderer
+            .selector()
+            .with_context(partial.context.clone());
+        let target = &reference.target;
+        let pool = match selector.candidates(target, reference.filter.as_deref(), &partial.selected)
+        {
+            Err(SelectionError::NoMatchingValues(_)) => match &reference.fallback {
+                Fallback::Error => Pool::Failed(SelectionError::NoMatchingValues(
+                    reference.filter.clone().unwrap_or_default(),
+                )),
+                Fallback::Unfiltered => {
+                    match selector.candidates(target, None, &partial.selected) {
+                        Ok(values) => Pool::Values(values),
+                        Err(e) => Pool::Failed(e),
+                    }
+                }
+                Fallback::Empty => Pool::Fixed(Vec::new()),
+                Fallback::Default(text) => Pool::Fixed(vec![SelectedValue {
+                    text: text.clone(),
+                    tags: IndexMap::new(),
+                }]),
+            },
+            Ok(values) => Pool::Values(values),
+            Err(e) => Pool::Failed(e),
+        };
+
+        let mut nodes = Vec::new();
+        for count in min..=max {
+            let probability = partial.probability * count_probability;
+
+            // Nothing is selected (or filtered) for a count of zero
+            if count == 0 {
+                nodes.push(advance(&partial, name, probability, Vec::new()));
+                continue;
+            }
+
+            match &pool {
+                Pool::Failed(error) => {
+                    nodes.push(self.selection_failed(&partial, name, probability, error.clone()))
+                }
+                Pool::Fixed(values) => {
+                    nodes.push(advance(&partial, name, probability, values.clone()))
+                }
+                Pool::Values(values) => {
+                    let unique = reference.unique && count > 1;
+                    if unique && values.len() < count {
+                        let error = SelectionError::NotEnoughUniqueValues {
+                            requested: count,
+                            available: values.len(),
+                        };
+                        nodes.push(self.selection_failed(&partial, name, probability, error));
+                        continue;
+                    }
+
+                    let candidates: Vec<&DatatypeValue> = values.iter().collect();
+                    let mut sequences = Vec::new();
+                    pick_sequences(
+                        &candidates,
+                        count,
+                        unique,
+                        &mut Vec::new(),
+                        1.0,
+                        &mut sequences,
+                    );
+                    for (values, pick_probability) in sequences {
+                        nodes.push(advance(
+                            &partial,
+                            name,
+                            probability * pick_probability,
+                            values,
+                        ));
+                    }
+                }
+            }
+        }
+        nodes
+    }
+
+    /// A reference that finds nothing to pick: the combination fails, unless
+    /// the reference is optional and the failure waits for it to render
+    fn selection_failed(
+        &self,
+        partial: &Partial,
+        name: &str,
+        probability: f64,
+        error: SelectionError,
+    ) -> Node<'r, 'a> {
+        let deferrable = matches!(
+            error,
+            SelectionError::NoMatchingValues(_) | SelectionError::NotEnoughUniqueValues { .. }
+        );
+        if !deferrable || !self.optional.iter().any(|optional| optional == name) {
+            return failed(partial, probability, RenderError::from(error).to_string());
+        }
+
+        let mut next = partial.clone();
+        next.next += 1;
+        next.probability = probability;
+        next.deferred.insert(name.to_string(), error);
+        Node::Partial(next)
+    }
+
+    /// Enrich a complete combination; its gates are decided by `branch`
+    fn finish(&self, partial: Partial) -> Node<'r, 'a> {
+        let Partial {
+            probability,
+            selected,
+            deferred,
+            choices,
+            mut context,
+            ..
+        } = partial;
+
+        if let Err(e) =
+            self.renderer
+                .phase_2_enrichment(&mut context, &selected, self.namespace, None)
+        {
+            return Node::Done(Leaf {
+                probability,
+                choices,
+                result: Err(e.to_string()),
+            });
+        }
+
+        Node::Gated(Gated {
+            probability,
+            selected,
+            deferred,
+            choices,
+            context,
+            outcomes: vec![None; self.chances.len()],
+        })
+    }
+
+    /// Decide the next gate the render reaches (hidden, then shown), or
+    /// render once every reached gate is decided
+    fn branch(&self, gated: Gated) -> Vec<Node<'r, 'a>> {
+        let reached = next_gate(
+            &self.parsed.tokens,
+            &gated.outcomes,
+            &gated.selected,
+            &gated.context,
+        );
+        let Some(index) = reached else {
+            return vec![Node::Done(self.render(gated))];
+        };
+
+        let shown = self.chances[index].min(100) as f64 / 100.0;
+        [(false, 1.0 - shown), (true, shown)]
+            .into_iter()
+            .filter(|&(_, probability)| probability > 0.0)
+            .map(|(outcome, probability)| {
+                let mut outcomes = gated.outcomes.clone();
+                outcomes[index] = Some(outcome);
+                Node::Gated(Gated {
+                    probability: gated.probability * probability,
+                    selected: gated.selected.clone(),
+                    deferred: gated.deferred.clone(),
+                    choices: gated.choices.clone(),
+                    context: gated.context.clone(),
+                    outcomes,
+                })
+            })
+            .collect()
+    }
+
+    /// Render a combination whose reached gates are all decided
+    fn render(&self, gated: Gated) -> Leaf {
+        // Gates the render doesn't reach are never read
+        let outcomes: Vec<bool> = gated
+            .outcomes
+            .iter()
+            .map(|outcome| outcome.unwrap_or(false))
+            .collect();
+
+        let result = self
+            .renderer
+            .with_chance_outcomes(&outcomes)
+            .phase_3_rendering(
+                &self.parsed,
+                &self.references,
+                &gated.selected,
+                &gated.deferred,
+                &gated.context,
+                self.namespace,
+            )
+            .map(|(output, leading_article)| Rendered {
+                output,
+                leading_article,
+                context: gated.context,
+            })
+            .map_err(|e| e.to_string());
+
+        Leaf {
+            probability: gated.probability,
+            choices: gated.choices,
+            result,
+        }
+    }
+}
+
+impl Iterator for SectionWalk<'_, '_> {
+    type Item = Leaf;
+
+    fn next(&mut self) -> Option<Leaf> {
+        loop {
+            let children = match self.stack.pop()? {
+                Node::Done(leaf) => return Some(leaf),
+                Node::Gated(gated) => self.branch(gated),
+                Node::Nested(mut nested) => match nested.walk.next() {
+                    // The nested walk resumes once this combination is done
+                    Some(leaf) => vec![nested_child(&nested, leaf), Node::Nested(nested)],
+                    None => Vec::new(),
+                },
+                Node::Partial(partial) if partial.next == self.order.len() => {
+                    vec![self.finish(partial)]
+                }
+                Node::Partial(partial) => self.expand(partial),
+            };
+
+            // Reversed, so the first choice is walked first
+            self.stack.extend(children.into_iter().rev());
+        }
+    }
+}
+
+/// Values a reference picks from
+enum Pool {
+    Values(Vec<DatatypeValue>),
+    /// A fallback's fixed selection
+    Fixed(Vec<SelectedValue>),
+    Failed(SelectionError),
+}
+
+/// `partial` with `values` chosen for its next reference
+fn advance<'r, 'a>(
+    partial: &Partial,
+    name: &str,
+    probability: f64,
+    values: Vec<SelectedValue>,
+) -> Node<'r, 'a> {
+    let mut next = partial.clone();
+    next.next += 1;
+    next.probability = probability;
+    next.choices.insert(
+        name.to_string(),
+        values.iter().map(|value| value.text.clone()).collect(),
+    );
+    next.selected.insert(name.to_string(), values);
+    Node::Partial(next)
+}
+
+/// A nested promptsection combination: its output chosen for the reference
+fn nested_child<'r, 'a>(nested: &Nested<'_, '_>, leaf: Leaf) -> Node<'r, 'a> {
+    let probability = nested.partial.probability * leaf.probability;
+    let mut choices = nested.partial.choices.clone();
+
+    let rendered = match leaf.result {
+        Ok(rendered) => rendered,
+        Err(error) => {
+            for (inner, texts) in leaf.choices {
+                choices.insert(format!("{}.{}", nested.name, inner), texts);
+            }
+            return Node::Done(Leaf {
+                probability,
+                choices,
+                result: Err(error),
+            });
+        }
+    };
+
+    choices.insert(nested.name.clone(), vec![rendered.output.clone()]);
+    for (inner, texts) in leaf.choices {
+        choices.insert(format!("{}.{}", nested.name, inner), texts);
+    }
+
+    // Same shape as a rendered nested section in phase 1
+    let mut selected = nested.partial.selected.clone();
+    selected.insert(
+        nested.name.clone(),
+        vec![SelectedValue {
+            text: rendered.output,
+            tags: rendered
+                .leading_article
+                .map(|article| IndexMap::from([("article".to_string(), article.into())]))
+                .unwrap_or_default(),
+        }],
+    );
+
+    Node::Partial(Partial {
+        next: nested.partial.next + 1,
+        probability,
+        selected,
+        deferred: nested.partial.deferred.clone(),
+        choices,
+        context: rendered.context,
+    })
+}
+
+/// A combination that fails
+fn failed<'r, 'a>(partial: &Partial, probability: f64, error: String) -> Node<'r, 'a> {
+    Node::Done(Leaf {
+        probability,
+        choices: partial.choices.clone(),
+        result: Err(error),
+    })
+}
+
+/// Every ordered sequence of `count` picks with its probability
+///
+/// Picks follow `SeededRandom::weighted_choice`; with `unique`, picked values
+/// leave the pool (as in `Selector::select_multiple`).
+fn pick_sequences(
+    pool: &[&DatatypeValue],
+    count: usize,
+    unique: bool,
+    current: &mut Vec<SelectedValue>,
+    probability: f64,
+    out: &mut Vec<(Vec<SelectedValue>, f64)>,
+) {
+    if current.len() == count {
+        out.push((current.clone(), probability));
+        return;
+    }
+
+    let weights: Vec<f64> = pool.iter().map(|value| value.weight as f64).collect();
+    for (i, pick_probability) in pick_probabilities(&weights).into_iter().enumerate() {
+        if pick_probability <= 0.0 {
+            continue;
+        }
+
+        current.push(SelectedValue {
+            text: pool[i].text.clone(),
+            tags: pool[i].tags.clone(),
+        });
+        let probability = probability * pick_probability;
+        if unique {
+            let mut rest = pool.to_vec();
+            rest.remove(i);
+            pick_sequences(&rest, count, unique, current, probability, out);
+        } else {
+            pick_sequences(pool, count, unique, current, probability, out);
+        }
+        current.pop();
+    }
+}
+
+/// Probability of each index under `SeededRandom::weighted_choice`
+pub(crate) fn pick_probabilities(weights: &[f64]) -> Vec<f64> {
+    if weights.len() == 1 {
+        return vec![1.0];
+    }
+
+    let total: f64 = weights.iter().sum();
+    if total <= 0.0 {
+        // All weights are 0 or negative: uniform
+        return vec![1.0 / weights.len() as f64; weights.len()];
+    }
+
+    weights
+        .iter()
+        .map(|&weight| weight.max(0.0) / total)
+        .collect()
+}
+
+/// The first undecided gate a render reaches, following decided gates and
+/// the conditions of optional segments
+fn next_gate(
+    tokens: &[TemplateToken],
+    outcomes: &[Option<bool>],
+    selected: &HashMap<String, Vec<SelectedValue>>,
+    context: &Context,
+) -> Option<usize> {
+    tokens.iter().find_map(|token| match token {
+        TemplateToken::Chance { index, tokens, .. } => match outcomes[*index] {
+            None => Some(*index),
+            Some(true) => next_gate(tokens, outcomes, selected, context),
+            Some(false) => None,
+        },
+        TemplateToken::Conditional {
+            condition,
+            then,
+            otherwise,
+        } => {
+            let branch = if condition_holds(condition, selected, context) {
+                then
+            } else {
+                otherwise
+            };
+            next_gate(branch, outcomes, selected, context)
+        }
+        _ => None,
+    })
+}
+
+/// Percent of each probability gate in a template, by segment index
+fn collect_chances(tokens: &[TemplateToken], chances: &mut Vec<u32>) {
+    for token in tokens {
+        match token {
+            TemplateToken::Chance {
+                percent,
+                index,
+                tokens,
+            } => {
+                if chances.len() <= *index {
+                    chances.resize(*index + 1, 0);
+                }
+                chances[*index] = *percent;
+                collect_chances(tokens, chances);
+            }
+            TemplateToken::Conditional {
+                then, otherwise, ..
+            } => {
+                collect_chances(then, chances);
+                collect_chances(otherwise, chances);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Datatype references of a promptsection and its nested promptsections
+/// as (choice key, qualified target), e.g. ("scene.creature", "fantasy:creatures")
+pub(crate) fn datatype_references(
+    renderer: &Renderer,
+    promptsection_ref: &str,
+    prefix: &str,
+    depth: usize,
+) -> Vec<(String, String)> {
+    let (Ok(promptsection), Ok(namespace)) = (
+        renderer.find_promptsection(promptsection_ref),
+        renderer.find_namespace_for_promptsection(promptsection_ref),
+    ) else {
+        return Vec::new();
+    };
+    let Ok(parsed) = Template::parse(&promptsection.template) else {
+        return Vec::new();
+    };
+
+    let mut found = Vec::new();
+    for (name, reference) in parsed.effective_references(&promptsection.references, &namespace.id) {
+        let key = format!("{}{}", prefix, name);
+        if reference.target.starts_with("context:") {
+            continue;
+        }
+        match renderer.is_promptsection_reference(&reference.target) {
+            Ok(true) if depth < MAX_RECURSION_DEPTH => found.extend(datatype_references(
+                renderer,
+                &reference.target,
+                &format!("{}.", key),
+                depth + 1,
+            )),
+            Ok(false) if reference.target.contains(':') => found.push((key, reference.target)),
+            Ok(false) => found.push((key, format!("{}:{}", namespace.id, reference.target))),
+            _ => {}
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_yaml;
+
+    const PACKAGE: &str = r#"
+id: test.enumerate
+version: 1.0.0
+metadata:
+  name: Enumerate
+  authors: []
+namespaces:
+  test:
+    id: test
+    datatypes:
+      animals:
+        name: animals
+        values:
+          - text: eagle
+            tags: {can_fly: true}
+            weight: 3
+          - text: owl
+            tags: {can_fly: true}
+            weight: 1
+          - text: cat
+            tags: {can_fly: false}
+            weight: 1
+      colors:
+        name: colors
+        values:
+          - text: red
+          - text: blue
+    prompt_sections:
+      scene:
+        name: scene
+        template: "{color} {animal}{%50} at night{/%}"
+        references:
+          animal:
+            target: test:animals
+            filter: tags.can_fly
+          color:
+            target: test:colors
+            min: 1
+            max: 2
+            unique: true
+      outer:
+        name: outer
+        template: "{a/an} {inner}!"
+        references:
+          inner:
+            target: test:scene
+      hidden:
+        name: hidden
+        template: "[{animal}]"
+        references:
+          animal:
+            target: test:animals
+            filter: tags.missing
+            min: 0
+            max: 1
+"#;
+
+    #[test]
+    fn test_enumerate_probabilities() {
+        let package = parse_yaml(PACKAGE).unwrap();
+        let renderer = Renderer::new(&package, 0);
+        let paths: Vec<EnumeratedPath> = renderer.enumerate("test:scene").unwrap().collect();
+
+        // 2 color sequences of one + 2 of two, 2 flying animals, 2 gate outcomes
+        assert_eq!(paths.len(), 16);
+        let total: f64 = paths.iter().map(|path| path.probability).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+
+        let probability = |output: &str| {
+            paths
+                .iter()
+                .find(|path| path.result == PathResult::Output(output.to_string()))
+                .map(|path| path.probability)
+        };
+        assert!((probability("red eagle").unwrap() - 0.09375).abs() < 1e-9);
+        assert!((probability("blue red owl at night").unwrap() - 0.03125).abs() < 1e-9);
+        assert_eq!(probability("red red eagle"), None);
+        assert_eq!(probability("red cat"), None);
+
+        let first = &paths[0];
+        assert_eq!(first.choices["color"], vec!["red"]);
+        assert_eq!(first.choices["animal"], vec!["eagle"]);
+
+        // Every sampled render is one of the enumerated outputs
+        for seed in 0..50 {
+            let output = Renderer::new(&package, seed)
+                .render("scene")
+                .unwrap()
+                .output;
+            assert!(probability(&output).is_some(), "not enumerated: {}", output);
+        }
+    }
+
+    #[test]
+    fn test_enumerate_summary() {
+        let package = parse_yaml(PACKAGE).unwrap();
+        let renderer = Renderer::new(&package, 0);
+
+        let summary = renderer.enumerate("test:outer").unwrap().summarize(None);
+        assert_eq!(summary.paths, 16);
+        assert_eq!(summary.failures, 0);
+        assert_eq!(summary.outputs.len(), 16);
+        assert!(summary.outputs.contains_key("a red eagle at night!"));
+        assert_eq!(summary.outputs.values().next(), Some(&0.09375));
+        assert_eq!(
+            summary.unreachable.get("inner.animal"),
+            Some(&vec!["cat".to_string()])
+        );
+        assert!(!summary.unreachable.contains_key("inner.color"));
+        assert!(!summary.truncated);
+
+        let summary = renderer.enumerate("test:outer").unwrap().summarize(Some(5));
+        assert_eq!(summary.paths, 5);
+        assert!(summary.truncated);
+    }
+
+    #[test]
+    fn test_enumerate_failures_and_fallbacks() {
+        let mut package = parse_yaml(PACKAGE).unwrap();
+
+        let summary = Renderer::new(&package, 0)
+            .enumerate("test:hidden")
+            .unwrap()
+            .summarize(None);
+        // A count of 0 never applies the filter; a count of 1 finds nothing
+        assert_eq!(summary.paths, 1);
+        assert_eq!(summary.failures, 1);
+        assert!((summary.failure_probability - 0.5).abs() < 1e-9);
+        assert_eq!(summary.outputs.get("[]"), Some(&0.5));
+        assert_eq!(summary.unreachable["animal"].len(), 3);
+
+        package.namespaces["test"].prompt_sections["hidden"].references["animal"].fallback =
+            Fallback::Default("nothing".to_string());
+        let summary = Renderer::new(&package, 0)
+            .enumerate("test:hidden")
+            .unwrap()
+            .summarize(None);
+        assert_eq!(summary.failures, 0);
+        assert_eq!(summary.outputs.get("[nothing]"), Some(&0.5));
+
+        // A reference used only in a segment that doesn't render may match nothing
+        let hidden = &mut package.namespaces["test"].prompt_sections["hidden"];
+        hidden.template = "[{?context.show}{animal}{/?}]".to_string();
+        hidden.references["animal"].fallback = Fallback::Error;
+        let summary = Renderer::new(&package, 0)
+            .enumerate("test:hidden")
+            .unwrap()
+            .summarize(None);
+        assert_eq!(summary.failures, 0);
+        assert_eq!(summary.outputs.get("[]"), Some(&1.0));
+
+        assert!(matches!(
+            Renderer::new(&package, 0).enumerate("test:missing"),
+            Err(RenderError::PromptSectionNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_enumerate_many_gates() {
+        use crate::core::PromptSection;
+
+        let mut package = parse_yaml(PACKAGE).unwrap();
+        let sections = &mut package.namespaces.get_mut("test").unwrap().prompt_sections;
+        let mut add = |name: &str, template: String| {
+            sections.insert(
+                name.to_string(),
+                PromptSection {
+                    name: name.to_string(),
+                    template,
+                    references: IndexMap::new(),
+                },
+            );
+        };
+        add("gates", "{%50}x{/%}".repeat(64));
+        // A nested section's combinations are walked as they're needed
+        add("few_gates", "{%50}x{/%}".repeat(24));
+        add("parent", "<{few_gates}>".to_string());
+        // Gates inside a segment that never renders are never expanded
+        add(
+            "skipped",
+            format!("{{%0}}{}{{/%}}b", "{%50}a{/%}".repeat(64)),
+        );
+
+        let renderer = Renderer::new(&package, 0);
+        let paths: Vec<EnumeratedPath> =
+            renderer.enumerate("test:gates").unwrap().take(3).collect();
+        assert_eq!(paths.len(), 3);
+        assert_eq!(paths[0].result, PathResult::Output(String::new()));
+        assert_eq!(paths[0].probability, 0.5f64.powi(64));
+
+        let paths: Vec<EnumeratedPath> =
+            renderer.enumerate("test:parent").unwrap().take(1).collect();
+        assert_eq!(paths[0].result, PathResult::Output("<>".to_string()));
+        assert_eq!(paths[0].probability, 0.5f64.powi(24));
+
+        let summary = renderer.enumerate("test:gates").unwrap().summarize(Some(3));
+        assert_eq!(summary.paths, 3);
+        assert!(summary.truncated);
+
+        let summary = renderer.enumerate("test:skipped").unwrap().summarize(None);
+        assert_eq!(summary.paths, 1);
+        assert_eq!(summary.outputs.get("b"), Some(&1.0));
+    }
+}
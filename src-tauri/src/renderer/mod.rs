@@ -1,12 +1,20 @@
 // M3: Renderer Module
 // Three-phase rendering pipeline for RPG prompts
 
+pub mod articles; // Automatic a/an ({a/an})
 pub mod engine;
+pub mod enumerate; // Combinatorial enumeration of outputs
+pub mod observer; // Pluggable render event sink
+pub mod probability; // Marginal value probabilities
+pub mod ref_path; // Multi-select aware ref: paths
 pub mod seeded_random;
 pub mod selector;
 pub mod separator;
+pub mod stats; // Sampling statistics over many seeds
 pub mod tag_expression; // M5 Phase 2: Complex tag expressions
 pub mod template_parser; // M5 Phase 3+4: Separator sets
+pub mod trace; // Opt-in structured render trace
+pub mod transforms; // Pipe transforms ({x|capitalize})
 
 // Export what's used by external modules (commands, CLI)
-pub use engine::Renderer;
+pub use engine::{Renderer, RulebookBatch};
@@ -0,0 +1,51 @@
+// Render Observer
+// Pluggable event sink for the render pipeline (silent by default)
+//
+// The renderer never writes to stdout itself. Embedders (CLI, app, tests)
+// attach an observer with Renderer::with_observer to follow progress.
+
+use crate::renderer::selector::SelectedValue;
+use crate::rules::processor::RuleOutcome;
+
+/// Phases of the three-phase pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderPhase {
+    Selection,
+    Enrichment,
+    Rendering,
+}
+
+impl std::fmt::Display for RenderPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderPhase::Selection => write!(f, "Selection"),
+            RenderPhase::Enrichment => write!(f, "Enrichment"),
+            RenderPhase::Rendering => write!(f, "Rendering"),
+        }
+    }
+}
+
+/// Receives render events; every method defaults to doing nothing
+///
+/// `depth` is the nesting depth of the promptsection (0 = top level).
+pub trait RenderObserver {
+    /// A promptsection entered a pipeline phase
+    fn phase_started(&self, _promptsection: &str, _phase: RenderPhase, _depth: usize) {}
+
+    /// Value(s) were selected for a reference (nested promptsections included)
+    fn values_selected(&self, _reference: &str, _target: &str, _values: &[SelectedValue]) {}
+
+    /// A rule was executed ("namespace:rule_id"), in execution order
+    fn rule_executed(&self, _rule: &str, _outcome: &RuleOutcome) {}
+
+    /// A decision wrote one of its outputs to the context
+    fn decision_output(&self, _decision: &str, _key: &str) {}
+
+    /// A promptsection finished rendering
+    fn section_rendered(&self, _promptsection: &str, _output: &str, _depth: usize) {}
+}
+
+/// Observer that ignores every event (the renderer's default)
+pub struct SilentObserver;
+
+impl RenderObserver for SilentObserver {}
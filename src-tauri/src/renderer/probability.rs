@@ -0,0 +1,296 @@
+// Value Probabilities
+// Exact chance of each datatype value appearing in a render
+//
+// Built on output enumeration: a value's probability is the total probability
+// of the rendering combinations that choose it, so weights, filters,
+// fallbacks, unique picks, count ranges and (for rulebooks) entry-point weights
+// and context defaults are all accounted for. A value no combination chooses
+// has probability zero, usually because filters exclude it everywhere it is
+// referenced.
+
+use crate::renderer::engine::{Renderer, Result};
+use crate::renderer::enumerate::{datatype_references, pick_probabilities, PathResult};
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Probability of one datatype value being chosen
+#[derive(Debug, Clone, Serialize)]
+pub struct ValueProbability {
+    pub text: String,
+    pub weight: f32,
+    /// Chance that a render chooses this value at least once
+    pub probability: f64,
+}
+
+/// Marginal value probabilities for a promptsection or rulebook
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProbabilityReport {
+    /// Rendered promptsections and their probability (the entry points of a rulebook)
+    pub sections: IndexMap<String, f64>,
+
+    /// Values of every referenced datatype, by qualified datatype name
+    pub datatypes: IndexMap<String, Vec<ValueProbability>>,
+
+    /// Probability that a render fails
+    pub failure_probability: f64,
+
+    /// Enumeration stopped at the combination limit (probabilities are lower bounds)
+    pub truncated: bool,
+}
+
+impl ProbabilityReport {
+    /// Values that are never chosen, as (datatype, text)
+    pub fn zero_probability(&self) -> Vec<(&str, &str)> {
+        self.datatypes
+            .iter()
+            .flat_map(|(datatype, values)| {
+                values
+                    .iter()
+                    .filter(|value| value.probability == 0.0)
+                    .map(move |value| (datatype.as_str(), value.text.as_str()))
+            })
+            .collect()
+    }
+}
+
+impl Renderer<'_> {
+    /// Probability of each value of the datatypes a promptsection references
+    ///
+    /// Walks at most `limit` rendering combinations, if given.
+    pub fn value_probabilities(
+        &self,
+        promptsection_ref: &str,
+        limit: Option<usize>,
+    ) -> Result<ProbabilityReport> {
+        let mut tally = Tally::new(limit);
+        tally.add_section(self, promptsection_ref, 1.0, &IndexMap::new())?;
+        Ok(tally.finish(self))
+    }
+
+    /// Probability of each value of the datatypes a rulebook's entry points reference
+    ///
+    /// Entry points are weighted as in `render_from_rulebook` and start from
+    /// the rulebook's `context_defaults`.
+    pub fn rulebook_value_probabilities(
+        &self,
+        rulebook_ref: &str,
+        limit: Option<usize>,
+    ) -> Result<ProbabilityReport> {
+        let rulebook = self.resolve_rulebook(rulebook_ref)?;
+        let weights: Vec<f64> = rulebook.entry_points.iter().map(|ep| ep.weight).collect();
+
+        let mut tally = Tally::new(limit);
+        for (entry_point, probability) in rulebook
+            .entry_points
+            .iter()
+            .zip(pick_probabilities(&weights))
+        {
+            tally.add_section(
+                self,
+                &entry_point.prompt_section,
+                probability,
+                &rulebook.context_defaults,
+            )?;
+        }
+        Ok(tally.finish(self))
+    }
+}
+
+/// Running totals while enumerating
+struct Tally {
+    remaining: Option<usize>,
+    report: ProbabilityReport,
+    /// Referenced datatypes, in the order they are first seen
+    targets: Vec<String>,
+    /// Probability per (datatype, value text)
+    chosen: HashMap<(String, String), f64>,
+}
+
+impl Tally {
+    fn new(limit: Option<usize>) -> Self {
+        Tally {
+            remaining: limit,
+            report: ProbabilityReport::default(),
+            targets: Vec::new(),
+            chosen: HashMap::new(),
+        }
+    }
+
+    fn add_section(
+        &mut self,
+        renderer: &Renderer,
+        section: &str,
+        probability: f64,
+        initial_context: &IndexMap<String, String>,
+    ) -> Result<()> {
+        *self.report.sections.entry(section.to_string()).or_default() += probability;
+        if probability <= 0.0 {
+            return Ok(());
+        }
+
+        let references = datatype_references(renderer, section, "", 0);
+        for (_, target) in &references {
+            if !self.targets.contains(target) {
+                self.targets.push(target.clone());
+            }
+        }
+        let targets: HashMap<String, String> = references.into_iter().collect();
+
+        for path in renderer.enumerate_with_context(section, initial_context)? {
+            if self.remaining == Some(0) {
+                self.report.truncated = true;
+                break;
+            }
+            if let Some(remaining) = self.remaining.as_mut() {
+                *remaining -= 1;
+            }
+
+            let path_probability = probability * path.probability;
+            if let PathResult::Error(_) = path.result {
+                self.report.failure_probability += path_probability;
+                continue;
+            }
+
+            // A value counts once per render, however many references chose it
+            let mut values = HashSet::new();
+            for (key, texts) in path.choices {
+                if let Some(target) = targets.get(&key) {
+                    values.extend(texts.into_iter().map(|text| (target.clone(), text)));
+                }
+            }
+            for value in values {
+                *self.chosen.entry(value).or_default() += path_probability;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self, renderer: &Renderer) -> ProbabilityReport {
+        let selector = renderer.selector();
+        for target in &self.targets {
+            let Ok(values) = selector.candidates(target, None, &HashMap::new()) else {
+                continue;
+            };
+
+            let mut probabilities: Vec<ValueProbability> = Vec::new();
+            for value in values {
+                if probabilities.iter().any(|known| known.text == value.text) {
+                    continue;
+                }
+                let probability = self
+                    .chosen
+                    .get(&(target.clone(), value.text.clone()))
+                    .copied()
+                    .unwrap_or(0.0);
+                probabilities.push(ValueProbability {
+                    text: value.text,
+                    weight: value.weight,
+                    probability,
+                });
+            }
+            self.report.datatypes.insert(target.clone(), probabilities);
+        }
+        self.report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_yaml;
+
+    const PACKAGE: &str = r#"
+id: test.probability
+version: 1.0.0
+metadata:
+  name: Probability
+  authors: []
+namespaces:
+  test:
+    id: test
+    datatypes:
+      skies:
+        name: skies
+        values:
+          - text: grey
+            tags: {mood: dark}
+          - text: blue
+            tags: {mood: bright}
+          - text: stormy
+            tags: {mood: dark}
+            weight: 3
+    prompt_sections:
+      sky:
+        name: sky
+        template: "{skies#{tags.mood == context.prompt.mood}} sky"
+      plain:
+        name: plain
+        template: "{skies} sky"
+    rulebooks:
+      weather:
+        name: weather
+        entry_points:
+          - prompt_section: test:sky
+            weight: 1
+          - prompt_section: test:plain
+            weight: 3
+        context_defaults:
+          mood: dark
+"#;
+
+    fn probabilities(report: &ProbabilityReport) -> Vec<(&str, f64)> {
+        report.datatypes["test:skies"]
+            .iter()
+            .map(|value| (value.text.as_str(), (value.probability * 1e4).round() / 1e4))
+            .collect()
+    }
+
+    #[test]
+    fn test_promptsection_probabilities() {
+        let package = parse_yaml(PACKAGE).unwrap();
+        let renderer = Renderer::new(&package, 0);
+
+        let report = renderer.value_probabilities("test:plain", None).unwrap();
+        assert_eq!(
+            probabilities(&report),
+            vec![("grey", 0.2), ("blue", 0.2), ("stormy", 0.6)]
+        );
+        assert!(report.zero_probability().is_empty());
+        assert_eq!(report.failure_probability, 0.0);
+
+        // Without a mood in the context the filter matches nothing
+        let report = renderer.value_probabilities("test:sky", None).unwrap();
+        assert_eq!(report.failure_probability, 1.0);
+        assert_eq!(report.zero_probability().len(), 3);
+    }
+
+    #[test]
+    fn test_rulebook_probabilities() {
+        let package = parse_yaml(PACKAGE).unwrap();
+        let renderer = Renderer::new(&package, 0);
+
+        let report = renderer
+            .rulebook_value_probabilities("test:weather", None)
+            .unwrap();
+        assert_eq!(report.sections["test:sky"], 0.25);
+        assert_eq!(report.sections["test:plain"], 0.75);
+        assert_eq!(report.failure_probability, 0.0);
+        assert_eq!(
+            probabilities(&report),
+            vec![("grey", 0.2125), ("blue", 0.15), ("stormy", 0.6375)]
+        );
+
+        let mut package = package;
+        package.namespaces["test"].rulebooks["weather"]
+            .context_defaults
+            .insert("mood".to_string(), "bright".to_string());
+        let report = Renderer::new(&package, 0)
+            .rulebook_value_probabilities("test:weather", None)
+            .unwrap();
+        assert_eq!(
+            probabilities(&report),
+            vec![("grey", 0.15), ("blue", 0.4), ("stormy", 0.45)]
+        );
+    }
+}
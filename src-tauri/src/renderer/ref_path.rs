@@ -0,0 +1,290 @@
+// Reference Paths
+// Address selected values of (possibly multi-select) references
+//
+// Used by both tag filters (`ref:` in filters) and rules/decisions.
+//
+// Formats (after the `ref:` prefix):
+// - name, name.text              First selected value's text
+// - name.tags.article            First selected value's tag
+// - name[1].tags.article         Value at index 1 (0-based)
+// - name[*].text                 List of all selected texts
+// - name[*].tags.article         List of a tag across all selected values
+// - name.count                   Number of selected values
+// - name.any.tags.plural         Whether any selected value has a truthy tag
+// - name.all.tags.plural         Whether all selected values have a truthy tag
+
+use crate::renderer::selector::SelectedValue;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RefPathError {
+    #[error("Reference not found: {0}")]
+    ReferenceNotFound(String),
+
+    #[error("Field not found: {0}")]
+    FieldNotFound(String),
+
+    #[error("Invalid reference path: {0}")]
+    InvalidPath(String),
+}
+
+/// Which selected value(s) a path addresses
+#[derive(Debug, Clone, PartialEq)]
+pub enum RefSelector {
+    First,
+    Index(usize),
+    All,
+    Any,
+    Every,
+    Count,
+}
+
+/// The field read from each addressed value
+#[derive(Debug, Clone, PartialEq)]
+pub enum RefField {
+    Text,
+    Tag(String),
+}
+
+/// A parsed reference path (without the `ref:` prefix)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefPath {
+    pub name: String,
+    pub selector: RefSelector,
+    pub field: RefField,
+}
+
+impl RefPath {
+    /// Parse a path such as "colors[1].tags.article" or "colors.count"
+    pub fn parse(path: &str) -> Result<RefPath, RefPathError> {
+        let invalid = || RefPathError::InvalidPath(path.to_string());
+
+        let mut parts = path.split('.');
+        let head = parts.next().filter(|h| !h.is_empty()).ok_or_else(invalid)?;
+
+        let (name, mut selector) = match head.split_once('[') {
+            Some((name, index)) => {
+                let index = index.strip_suffix(']').ok_or_else(invalid)?;
+                let selector = if index == "*" {
+                    RefSelector::All
+                } else {
+                    RefSelector::Index(index.parse().map_err(|_| invalid())?)
+                };
+                (name, selector)
+            }
+            None => (head, RefSelector::First),
+        };
+
+        if name.is_empty() {
+            return Err(invalid());
+        }
+
+        let mut rest: Vec<&str> = parts.collect();
+
+        // Quantifiers and count apply to the whole list
+        if selector == RefSelector::First {
+            match rest.first() {
+                Some(&"count") if rest.len() == 1 => {
+                    return Ok(RefPath {
+                        name: name.to_string(),
+                        selector: RefSelector::Count,
+                        field: RefField::Text,
+                    });
+                }
+                Some(&"any") => {
+                    selector = RefSelector::Any;
+                    rest.remove(0);
+                }
+                Some(&"all") => {
+                    selector = RefSelector::Every;
+                    rest.remove(0);
+                }
+                _ => {}
+            }
+        }
+
+        let field = match rest.as_slice() {
+            [] | ["text"] => RefField::Text,
+            ["tags", tag] => RefField::Tag(tag.to_string()),
+            _ => return Err(invalid()),
+        };
+
+        Ok(RefPath {
+            name: name.to_string(),
+            selector,
+            field,
+        })
+    }
+
+    /// Resolve the path against the values selected so far
+    pub fn resolve(
+        &self,
+        selected: &HashMap<String, Vec<SelectedValue>>,
+    ) -> Result<JsonValue, RefPathError> {
+        let values = selected
+            .get(&self.name)
+            .ok_or_else(|| RefPathError::ReferenceNotFound(self.name.clone()))?;
+
+        let field_of = |value: &SelectedValue| -> Option<JsonValue> {
+            match &self.field {
+                RefField::Text => Some(JsonValue::String(value.text.clone())),
+                RefField::Tag(tag) => value.tags.get(tag).cloned(),
+            }
+        };
+
+        let single = |value: Option<&SelectedValue>| -> Result<JsonValue, RefPathError> {
+            let value = value.ok_or_else(|| RefPathError::FieldNotFound(self.to_string()))?;
+            field_of(value).ok_or_else(|| RefPathError::FieldNotFound(self.to_string()))
+        };
+
+        match self.selector {
+            RefSelector::First => single(values.first()),
+            RefSelector::Index(index) => single(values.get(index)),
+            RefSelector::Count => Ok(JsonValue::from(values.len())),
+            RefSelector::All => Ok(JsonValue::Array(
+                values.iter().filter_map(field_of).collect(),
+            )),
+            RefSelector::Any => Ok(JsonValue::Bool(
+                values
+                    .iter()
+                    .any(|v| field_of(v).is_some_and(|j| is_truthy(&j))),
+            )),
+            RefSelector::Every => Ok(JsonValue::Bool(
+                values
+                    .iter()
+                    .all(|v| field_of(v).is_some_and(|j| is_truthy(&j))),
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for RefPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        match self.selector {
+            RefSelector::First => {}
+            RefSelector::Index(index) => write!(f, "[{}]", index)?,
+            RefSelector::All => write!(f, "[*]")?,
+            RefSelector::Any => write!(f, ".any")?,
+            RefSelector::Every => write!(f, ".all")?,
+            RefSelector::Count => return write!(f, ".count"),
+        }
+        match &self.field {
+            RefField::Text => write!(f, ".text"),
+            RefField::Tag(tag) => write!(f, ".tags.{}", tag),
+        }
+    }
+}
+
+/// Truthiness of a tag value (shared by filters and rules)
+pub fn is_truthy(value: &JsonValue) -> bool {
+    match value {
+        JsonValue::Bool(b) => *b,
+        JsonValue::String(s) => !s.is_empty(),
+        JsonValue::Number(n) => n.as_f64().unwrap_or(0.0) != 0.0,
+        JsonValue::Null => false,
+        JsonValue::Array(items) => !items.is_empty(),
+        JsonValue::Object(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn value(text: &str, tags: &[(&str, JsonValue)]) -> SelectedValue {
+        SelectedValue {
+            text: text.to_string(),
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        }
+    }
+
+    fn selected() -> HashMap<String, Vec<SelectedValue>> {
+        let mut selected = HashMap::new();
+        selected.insert(
+            "colors".to_string(),
+            vec![
+                value("red", &[("article", json!("a")), ("warm", json!(true))]),
+                value("orange", &[("article", json!("an")), ("warm", json!(true))]),
+                value("blue", &[("article", json!("a")), ("warm", json!(false))]),
+            ],
+        );
+        selected
+    }
+
+    fn resolve(path: &str) -> Result<JsonValue, RefPathError> {
+        RefPath::parse(path)?.resolve(&selected())
+    }
+
+    #[test]
+    fn test_first_value() {
+        assert_eq!(resolve("colors").unwrap(), json!("red"));
+        assert_eq!(resolve("colors.text").unwrap(), json!("red"));
+        assert_eq!(resolve("colors.tags.article").unwrap(), json!("a"));
+    }
+
+    #[test]
+    fn test_index() {
+        assert_eq!(resolve("colors[1].tags.article").unwrap(), json!("an"));
+        assert_eq!(resolve("colors[2]").unwrap(), json!("blue"));
+        assert!(matches!(
+            resolve("colors[5].text"),
+            Err(RefPathError::FieldNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_count_and_all() {
+        assert_eq!(resolve("colors.count").unwrap(), json!(3));
+        assert_eq!(
+            resolve("colors[*].text").unwrap(),
+            json!(["red", "orange", "blue"])
+        );
+        assert_eq!(
+            resolve("colors[*].tags.article").unwrap(),
+            json!(["a", "an", "a"])
+        );
+    }
+
+    #[test]
+    fn test_quantifiers() {
+        assert_eq!(resolve("colors.any.tags.warm").unwrap(), json!(true));
+        assert_eq!(resolve("colors.all.tags.warm").unwrap(), json!(false));
+        assert_eq!(resolve("colors.all.tags.article").unwrap(), json!(true));
+        assert_eq!(resolve("colors.any.tags.missing").unwrap(), json!(false));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            resolve("missing.text"),
+            Err(RefPathError::ReferenceNotFound(_))
+        ));
+        assert!(matches!(
+            resolve("colors.tags.missing"),
+            Err(RefPathError::FieldNotFound(_))
+        ));
+        assert!(RefPath::parse("colors.weight").is_err());
+        assert!(RefPath::parse("colors[x].text").is_err());
+        assert!(RefPath::parse("[1].text").is_err());
+        assert!(RefPath::parse("colors[1].count").is_err());
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        for path in [
+            "colors[1].tags.article",
+            "colors.count",
+            "colors.any.tags.warm",
+        ] {
+            let parsed = RefPath::parse(path).unwrap();
+            assert_eq!(RefPath::parse(&parsed.to_string()).unwrap(), parsed);
+        }
+    }
+}
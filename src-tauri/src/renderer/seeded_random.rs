@@ -161,6 +161,26 @@ impl SeededRandom {
     }
 }
 
+/// Derive an independent, deterministic child seed from a parent seed and a label
+///
+/// Labels name the position in the render tree (e.g. "section:creature",
+/// "count:colors"), so nested derivations form a hierarchy. Uses FNV-1a over
+/// the label and a SplitMix64 finalizer, which are stable across platforms
+/// and Rust versions (unlike `std::hash`).
+pub fn derive_seed(seed: u64, label: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in label.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    let mut z = seed ^ hash.rotate_left(32);
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -270,4 +290,25 @@ mod tests {
         // Should not crash and should produce values
         let _ = rng.next_u64();
     }
+
+    #[test]
+    fn test_derive_seed() {
+        // Deterministic
+        assert_eq!(
+            derive_seed(42, "count:color"),
+            derive_seed(42, "count:color")
+        );
+
+        // Labels of equal length don't collide
+        assert_ne!(
+            derive_seed(42, "count:color"),
+            derive_seed(42, "count:shape")
+        );
+
+        // Different parents give different children
+        assert_ne!(derive_seed(42, "section:a"), derive_seed(43, "section:a"));
+
+        // Child differs from parent
+        assert_ne!(derive_seed(42, "section:a"), 42);
+    }
 }
@@ -3,13 +3,17 @@
 // M5 Phase 2: Complex tag expressions (AND/OR/NOT)
 // M8.5 Blocker 1: Cross-reference filtering support
 
-use crate::core::{Datatype, DatatypeValue, Package};
+use crate::context::Context;
+use crate::core::inheritance::{resolve_datatype_values, InheritanceError};
+use crate::core::lookup::find_namespace;
+use crate::core::{DatatypeValue, Package};
 use crate::renderer::seeded_random::SeededRandom;
 use crate::renderer::tag_expression::{evaluate_with_context, ExpressionParser};
+use indexmap::IndexMap;
 use std::collections::HashMap;
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum SelectionError {
     #[error("Datatype not found: {0}")]
     DatatypeNotFound(String),
@@ -26,6 +30,9 @@ pub enum SelectionError {
     #[error("Filter matched no values: {0}")]
     NoMatchingValues(String),
 
+    #[error("Circular datatype extends: {0}")]
+    CircularExtends(String),
+
     #[error("Cannot select {requested} unique values from datatype with only {available} values")]
     NotEnoughUniqueValues { requested: usize, available: usize },
 }
@@ -36,7 +43,7 @@ pub type Result<T> = std::result::Result<T, SelectionError>;
 #[derive(Debug, Clone)]
 pub struct SelectedValue {
     pub text: String,
-    pub tags: HashMap<String, serde_json::Value>,
+    pub tags: IndexMap<String, serde_json::Value>,
 }
 
 /// Selects values from datatypes
@@ -44,6 +51,10 @@ pub struct SelectedValue {
 pub struct Selector<'a> {
     package: &'a Package,
     dependencies: Option<&'a HashMap<String, Package>>,
+    /// Context visible to filters (context.prompt.x, context.global.x)
+    context: Context,
+    /// Ignore all filters ("chaos mode"), regardless of package metadata
+    bypass_filters: bool,
     rng: SeededRandom,
 }
 
@@ -53,6 +64,8 @@ impl<'a> Selector<'a> {
         Selector {
             package,
             dependencies: None,
+            context: Context::new(),
+            bypass_filters: false,
             rng: SeededRandom::new(seed),
         }
     }
@@ -66,10 +79,44 @@ impl<'a> Selector<'a> {
         Selector {
             package,
             dependencies: Some(dependencies),
+            context: Context::new(),
+            bypass_filters: false,
             rng: SeededRandom::new(seed),
         }
     }
 
+    /// Let filters read context values (context.prompt.mood)
+    pub fn with_context(mut self, context: Context) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Ignore all filters, not just those on datatypes of `bypass_filters` packages
+    pub fn with_bypass_filters(mut self, bypass: bool) -> Self {
+        self.bypass_filters = bypass;
+        self
+    }
+
+    /// The context filters currently see
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Replace the context filters see (e.g. after a nested section's rules ran)
+    pub fn set_context(&mut self, context: Context) {
+        self.context = context;
+    }
+
+    /// Take the context back
+    pub fn into_context(self) -> Context {
+        self.context
+    }
+
+    /// Draw the following picks from a fresh stream seeded with `seed`
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = SeededRandom::new(seed);
+    }
+
     /// Select a value from a datatype reference
     ///
     /// Reference format: "datatype" or "namespace:datatype"
@@ -90,22 +137,49 @@ impl<'a> Selector<'a> {
         &mut self,
         reference: &str,
         filter: Option<&str>,
-        selected: &HashMap<String, (String, HashMap<String, serde_json::Value>)>,
+        selected: &HashMap<String, Vec<SelectedValue>>,
     ) -> Result<SelectedValue> {
+        let (_, datatype_name) = self.parse_reference(reference)?;
+        let values = self.candidates(reference, filter, selected)?;
+
+        // Select value
+        self.select_from_values(&values, &datatype_name)
+    }
+
+    /// The values a reference can select from: resolved (including inherited
+    /// values) and filtered. Does not consume randomness.
+    pub fn candidates(
+        &self,
+        reference: &str,
+        filter: Option<&str>,
+        selected: &HashMap<String, Vec<SelectedValue>>,
+    ) -> Result<Vec<DatatypeValue>> {
         // Parse reference (simple version for M3)
         let (namespace, datatype_name) = self.parse_reference(reference)?;
 
-        // Find datatype and clone its values to avoid borrowing issues
-        let datatype = self.find_datatype(&namespace, &datatype_name)?;
-        let mut values = datatype.values.clone();
+        // Resolve datatype values (including inherited ones)
+        let mut values = self.resolve_values(&namespace, &datatype_name)?;
 
         // M4: Apply filter if provided
         if let Some(filter_expr) = filter {
-            values = self.apply_filter(values, filter_expr, selected)?;
+            if !self.filters_bypassed(&namespace, &datatype_name) {
+                values = self.apply_filter(values, filter_expr, selected)?;
+            }
         }
 
-        // Select value
-        self.select_from_values(&values, &datatype_name)
+        Ok(values)
+    }
+
+    /// Whether filters on a datatype are ignored: globally, or because the
+    /// package owning the datatype (possibly a dependency) sets `bypass_filters`
+    fn filters_bypassed(&self, namespace: &str, name: &str) -> bool {
+        self.bypass_filters
+            || find_namespace(self.package, self.dependencies, namespace, name, |ns| {
+                ns.datatypes.contains_key(name)
+            })
+            .ok()
+            .flatten()
+            .is_some_and(|(owner, _)| owner.metadata.bypass_filters)
     }
 
     /// M5 Phase 3+4: Select multiple values with optional uniqueness constraint
@@ -118,23 +192,14 @@ impl<'a> Selector<'a> {
         count: usize,
         filter: Option<&str>,
         unique: bool,
-        selected: &HashMap<String, (String, HashMap<String, serde_json::Value>)>,
+        selected: &HashMap<String, Vec<SelectedValue>>,
     ) -> Result<Vec<SelectedValue>> {
         if count == 0 {
             return Ok(Vec::new());
         }
 
-        // Parse reference
-        let (namespace, datatype_name) = self.parse_reference(reference)?;
-
-        // Find datatype and clone its values
-        let datatype = self.find_datatype(&namespace, &datatype_name)?;
-        let mut values = datatype.values.clone();
-
-        // Apply filter if provided
-        if let Some(filter_expr) = filter {
-            values = self.apply_filter(values, filter_expr, selected)?;
-        }
+        let (_, datatype_name) = self.parse_reference(reference)?;
+        let values = self.candidates(reference, filter, selected)?;
 
         if unique {
             // For unique selections, we need at least `count` values available
@@ -189,7 +254,7 @@ impl<'a> Selector<'a> {
     }
 
     /// Parse reference into namespace and datatype name
-    /// Format: "datatype" uses first namespace, "namespace:datatype" is explicit
+    /// Format: "datatype" uses the package's default namespace, "namespace:datatype" is explicit
     fn parse_reference(&self, reference: &str) -> Result<(String, String)> {
         if reference.contains(':') {
             let parts: Vec<&str> = reference.split(':').collect();
@@ -198,41 +263,27 @@ impl<'a> Selector<'a> {
             }
             Ok((parts[0].to_string(), parts[1].to_string()))
         } else {
-            // Use first namespace as default (or could use package default)
-            let first_namespace = self.package.namespaces.keys().next().ok_or_else(|| {
+            let namespace = self.package.default_namespace_id().ok_or_else(|| {
                 SelectionError::InvalidReference("No namespaces in package".to_string())
             })?;
 
-            Ok((first_namespace.clone(), reference.to_string()))
+            Ok((namespace.to_string(), reference.to_string()))
         }
     }
 
-    /// Find a datatype by namespace and name
+    /// Resolve a datatype's values by namespace and name
     /// M9 Phase 2.7: Searches dependencies if not found in main package
-    fn find_datatype(&self, namespace: &str, name: &str) -> Result<&Datatype> {
-        // First try main package
-        if let Some(ns) = self.package.namespaces.get(namespace) {
-            if let Some(dt) = ns.datatypes.get(name) {
-                return Ok(dt);
-            }
-        }
-
-        // M9: If not found and we have dependencies, search them
-        if let Some(deps) = self.dependencies {
-            for dep_package in deps.values() {
-                if let Some(ns) = dep_package.namespaces.get(namespace) {
-                    if let Some(dt) = ns.datatypes.get(name) {
-                        return Ok(dt);
-                    }
+    /// Merges values inherited through `extends`, applying `override_tags`
+    fn resolve_values(&self, namespace: &str, name: &str) -> Result<Vec<DatatypeValue>> {
+        resolve_datatype_values(self.package, self.dependencies, namespace, name).map_err(|e| {
+            match e {
+                InheritanceError::NotFound(name) => SelectionError::DatatypeNotFound(name),
+                InheritanceError::Cycle(chain) => {
+                    SelectionError::CircularExtends(chain.join(" -> "))
                 }
+                InheritanceError::Lookup(e) => SelectionError::InvalidReference(e.to_string()),
             }
-        }
-
-        // Not found anywhere
-        Err(SelectionError::DatatypeNotFound(format!(
-            "{}:{}",
-            namespace, name
-        )))
+        })
     }
 
     /// Select a value from a list of datatype values, respecting weights
@@ -264,12 +315,13 @@ impl<'a> Selector<'a> {
     ///
     /// M5 Phase 2: Complex tag expressions with AND/OR/NOT
     /// M8.5 Blocker 1: Cross-reference filtering support
-    /// Supports: tags.can_fly, tags.type == "melee", ref:other.text in tags.applies_to
+    /// Supports: tags.can_fly, tags.type == "melee", ref:other.text in tags.applies_to,
+    /// tags.size >= 3, tags.mood == context.prompt.mood
     fn apply_filter(
         &self,
         values: Vec<DatatypeValue>,
         filter_expr: &str,
-        selected: &HashMap<String, (String, HashMap<String, serde_json::Value>)>,
+        selected: &HashMap<String, Vec<SelectedValue>>,
     ) -> Result<Vec<DatatypeValue>> {
         // Parse the filter expression
         let expression = ExpressionParser::parse(filter_expr)
@@ -278,7 +330,9 @@ impl<'a> Selector<'a> {
         // Filter values using the parsed expression with selected values context
         let filtered: Vec<DatatypeValue> = values
             .into_iter()
-            .filter(|value| evaluate_with_context(&expression, &value.tags, selected))
+            .filter(|value| {
+                evaluate_with_context(&expression, &value.tags, selected, Some(&self.context))
+            })
             .collect();
 
         // Check if any values matched
@@ -293,10 +347,11 @@ impl<'a> Selector<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::{Namespace, PackageMetadata};
+    use crate::core::{Datatype, Namespace, PackageMetadata};
+    use indexmap::IndexMap;
 
     fn create_test_package() -> Package {
-        let mut datatypes = HashMap::new();
+        let mut datatypes = IndexMap::new();
 
         // Colors datatype
         datatypes.insert(
@@ -306,31 +361,31 @@ mod tests {
                 values: vec![
                     DatatypeValue {
                         text: "red".to_string(),
-                        tags: HashMap::new(),
+                        tags: IndexMap::new(),
                         weight: 1.0,
                     },
                     DatatypeValue {
                         text: "blue".to_string(),
-                        tags: HashMap::new(),
+                        tags: IndexMap::new(),
                         weight: 1.0,
                     },
                 ],
                 extends: None,
-                override_tags: HashMap::new(),
+                override_tags: IndexMap::new(),
             },
         );
 
-        let mut namespaces = HashMap::new();
+        let mut namespaces = IndexMap::new();
         namespaces.insert(
             "test".to_string(),
             Namespace {
                 id: "test".to_string(),
                 datatypes,
-                prompt_sections: HashMap::new(),
-                separator_sets: HashMap::new(),
-                rules: HashMap::new(),
+                prompt_sections: IndexMap::new(),
+                separator_sets: IndexMap::new(),
+                rules: IndexMap::new(),
                 decisions: Vec::new(),
-                rulebooks: HashMap::new(), // M9
+                rulebooks: IndexMap::new(), // M9
             },
         );
 
@@ -344,6 +399,7 @@ mod tests {
                 bypass_filters: false,
             },
             namespaces,
+            default_namespace: None,
             dependencies: Vec::new(),
         }
     }
@@ -388,9 +444,56 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_bypass_filters() {
+        let none = HashMap::new();
+        let filter = Some("tags.shiny");
+
+        // The dependency owns absurd:colors and ignores filters on its datatypes
+        let mut absurd = create_test_package();
+        absurd.id = "absurd.package".to_string();
+        absurd.metadata.bypass_filters = true;
+        let namespace = absurd.namespaces.shift_remove("test").unwrap();
+        absurd.namespaces.insert(
+            "absurd".to_string(),
+            Namespace {
+                id: "absurd".to_string(),
+                ..namespace
+            },
+        );
+        let dependencies = HashMap::from([(absurd.id.clone(), absurd)]);
+
+        let package = create_test_package();
+        let selector = Selector::new_with_dependencies(&package, &dependencies, 42);
+        assert!(selector.candidates("test:colors", filter, &none).is_err());
+        assert_eq!(
+            selector
+                .candidates("absurd:colors", filter, &none)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let mut package = create_test_package();
+        package.metadata.bypass_filters = true;
+        let selector = Selector::new(&package, 42);
+        assert_eq!(
+            selector.candidates("colors", filter, &none).unwrap().len(),
+            2
+        );
+
+        // Chaos mode ignores filters everywhere
+        let package = create_test_package();
+        let selector = Selector::new(&package, 42).with_bypass_filters(true);
+        assert_eq!(
+            selector.candidates("colors", filter, &none).unwrap().len(),
+            2
+        );
+    }
+
     #[test]
     fn test_select_with_filter() {
-        let mut datatypes = HashMap::new();
+        let mut datatypes = IndexMap::new();
 
         // Animals datatype with can_fly tag
         datatypes.insert(
@@ -401,7 +504,7 @@ mod tests {
                     DatatypeValue {
                         text: "eagle".to_string(),
                         tags: {
-                            let mut tags = HashMap::new();
+                            let mut tags = IndexMap::new();
                             tags.insert("can_fly".to_string(), serde_json::Value::Bool(true));
                             tags
                         },
@@ -410,7 +513,7 @@ mod tests {
                     DatatypeValue {
                         text: "deer".to_string(),
                         tags: {
-                            let mut tags = HashMap::new();
+                            let mut tags = IndexMap::new();
                             tags.insert("can_fly".to_string(), serde_json::Value::Bool(false));
                             tags
                         },
@@ -419,7 +522,7 @@ mod tests {
                     DatatypeValue {
                         text: "swan".to_string(),
                         tags: {
-                            let mut tags = HashMap::new();
+                            let mut tags = IndexMap::new();
                             tags.insert("can_fly".to_string(), serde_json::Value::Bool(true));
                             tags
                         },
@@ -427,21 +530,21 @@ mod tests {
                     },
                 ],
                 extends: None,
-                override_tags: HashMap::new(),
+                override_tags: IndexMap::new(),
             },
         );
 
-        let mut namespaces = HashMap::new();
+        let mut namespaces = IndexMap::new();
         namespaces.insert(
             "test".to_string(),
             Namespace {
                 id: "test".to_string(),
                 datatypes,
-                prompt_sections: HashMap::new(),
-                separator_sets: HashMap::new(),
-                rules: HashMap::new(),
+                prompt_sections: IndexMap::new(),
+                separator_sets: IndexMap::new(),
+                rules: IndexMap::new(),
                 decisions: Vec::new(),
-                rulebooks: HashMap::new(), // M9
+                rulebooks: IndexMap::new(), // M9
             },
         );
 
@@ -455,6 +558,7 @@ mod tests {
                 bypass_filters: false,
             },
             namespaces,
+            default_namespace: None,
             dependencies: Vec::new(),
         };
 
@@ -470,7 +574,7 @@ mod tests {
 
     #[test]
     fn test_filter_no_matches() {
-        let mut datatypes = HashMap::new();
+        let mut datatypes = IndexMap::new();
 
         // Animals without can_swim tag
         datatypes.insert(
@@ -479,25 +583,25 @@ mod tests {
                 name: "animals".to_string(),
                 values: vec![DatatypeValue {
                     text: "eagle".to_string(),
-                    tags: HashMap::new(),
+                    tags: IndexMap::new(),
                     weight: 1.0,
                 }],
                 extends: None,
-                override_tags: HashMap::new(),
+                override_tags: IndexMap::new(),
             },
         );
 
-        let mut namespaces = HashMap::new();
+        let mut namespaces = IndexMap::new();
         namespaces.insert(
             "test".to_string(),
             Namespace {
                 id: "test".to_string(),
                 datatypes,
-                prompt_sections: HashMap::new(),
-                separator_sets: HashMap::new(),
-                rules: HashMap::new(),
+                prompt_sections: IndexMap::new(),
+                separator_sets: IndexMap::new(),
+                rules: IndexMap::new(),
                 decisions: Vec::new(),
-                rulebooks: HashMap::new(), // M9
+                rulebooks: IndexMap::new(), // M9
             },
         );
 
@@ -511,6 +615,7 @@ mod tests {
                 bypass_filters: false,
             },
             namespaces,
+            default_namespace: None,
             dependencies: Vec::new(),
         };
 
@@ -524,7 +629,7 @@ mod tests {
     #[test]
     fn test_cross_ref_filter() {
         // Test selecting with cross-reference filter
-        let mut datatypes = HashMap::new();
+        let mut datatypes = IndexMap::new();
 
         datatypes.insert(
             "features".to_string(),
@@ -532,11 +637,11 @@ mod tests {
                 name: "features".to_string(),
                 values: vec![DatatypeValue {
                     text: "eyes".to_string(),
-                    tags: HashMap::new(),
+                    tags: IndexMap::new(),
                     weight: 1.0,
                 }],
                 extends: None,
-                override_tags: HashMap::new(),
+                override_tags: IndexMap::new(),
             },
         );
 
@@ -547,7 +652,7 @@ mod tests {
                 values: vec![DatatypeValue {
                     text: "blue".to_string(),
                     tags: {
-                        let mut tags = HashMap::new();
+                        let mut tags = IndexMap::new();
                         tags.insert(
                             "applies_to".to_string(),
                             serde_json::json!(["eyes", "claws"]),
@@ -557,21 +662,21 @@ mod tests {
                     weight: 1.0,
                 }],
                 extends: None,
-                override_tags: HashMap::new(),
+                override_tags: IndexMap::new(),
             },
         );
 
-        let mut namespaces = HashMap::new();
+        let mut namespaces = IndexMap::new();
         namespaces.insert(
             "test".to_string(),
             Namespace {
                 id: "test".to_string(),
                 datatypes,
-                prompt_sections: HashMap::new(),
-                separator_sets: HashMap::new(),
-                rules: HashMap::new(),
+                prompt_sections: IndexMap::new(),
+                separator_sets: IndexMap::new(),
+                rules: IndexMap::new(),
                 decisions: Vec::new(),
-                rulebooks: HashMap::new(), // M9
+                rulebooks: IndexMap::new(), // M9
             },
         );
 
@@ -585,6 +690,7 @@ mod tests {
                 bypass_filters: false,
             },
             namespaces,
+            default_namespace: None,
             dependencies: Vec::new(),
         };
 
@@ -594,14 +700,17 @@ mod tests {
         let mut selected = HashMap::new();
         selected.insert(
             "feature".to_string(),
-            ("eyes".to_string(), {
-                let mut tags = HashMap::new();
-                tags.insert(
-                    "applies_to".to_string(),
-                    serde_json::json!(["eyes", "claws"]),
-                );
-                tags
-            }),
+            vec![SelectedValue {
+                text: "eyes".to_string(),
+                tags: {
+                    let mut tags = IndexMap::new();
+                    tags.insert(
+                        "applies_to".to_string(),
+                        serde_json::json!(["eyes", "claws"]),
+                    );
+                    tags
+                },
+            }],
         );
 
         let result = selector.select_with_filter(
@@ -617,7 +726,7 @@ mod tests {
     #[test]
     fn test_cross_ref_filter_no_match() {
         // Test when the cross-reference doesn't match any values
-        let mut datatypes = HashMap::new();
+        let mut datatypes = IndexMap::new();
 
         datatypes.insert(
             "features".to_string(),
@@ -625,11 +734,11 @@ mod tests {
                 name: "features".to_string(),
                 values: vec![DatatypeValue {
                     text: "sharp".to_string(),
-                    tags: HashMap::new(),
+                    tags: IndexMap::new(),
                     weight: 1.0,
                 }],
                 extends: None,
-                override_tags: HashMap::new(),
+                override_tags: IndexMap::new(),
             },
         );
 
@@ -640,28 +749,28 @@ mod tests {
                 values: vec![DatatypeValue {
                     text: "blue".to_string(),
                     tags: {
-                        let mut tags = HashMap::new();
+                        let mut tags = IndexMap::new();
                         tags.insert("applies_to".to_string(), serde_json::json!(["wings"]));
                         tags
                     },
                     weight: 1.0,
                 }],
                 extends: None,
-                override_tags: HashMap::new(),
+                override_tags: IndexMap::new(),
             },
         );
 
-        let mut namespaces = HashMap::new();
+        let mut namespaces = IndexMap::new();
         namespaces.insert(
             "test".to_string(),
             Namespace {
                 id: "test".to_string(),
                 datatypes,
-                prompt_sections: HashMap::new(),
-                separator_sets: HashMap::new(),
-                rules: HashMap::new(),
+                prompt_sections: IndexMap::new(),
+                separator_sets: IndexMap::new(),
+                rules: IndexMap::new(),
                 decisions: Vec::new(),
-                rulebooks: HashMap::new(), // M9
+                rulebooks: IndexMap::new(), // M9
             },
         );
 
@@ -675,6 +784,7 @@ mod tests {
                 bypass_filters: false,
             },
             namespaces,
+            default_namespace: None,
             dependencies: Vec::new(),
         };
         let mut selector = Selector::new(&package, 42);
@@ -682,14 +792,17 @@ mod tests {
         let mut selected = HashMap::new();
         selected.insert(
             "feature".to_string(),
-            ("sharp".to_string(), {
-                let mut tags = HashMap::new();
-                tags.insert(
-                    "applies_to".to_string(),
-                    serde_json::json!(["eyes", "claws"]),
-                );
-                tags
-            }),
+            vec![SelectedValue {
+                text: "sharp".to_string(),
+                tags: {
+                    let mut tags = IndexMap::new();
+                    tags.insert(
+                        "applies_to".to_string(),
+                        serde_json::json!(["eyes", "claws"]),
+                    );
+                    tags
+                },
+            }],
         );
         // This should fail because "blue" only applies to "wings", not "eyes" or "claws"
         let result = selector.select_with_filter(
@@ -0,0 +1,268 @@
+// Sampling Statistics
+// Renders many seeds and tallies what came out
+//
+// The sampling counterpart of `probability`: rather than walking every
+// combination, it renders consecutive seeds exactly like `rpg-cli render
+// --count`, so it scales to sections too large to enumerate. Selected values
+// are read from the render trace.
+
+use crate::renderer::engine::{RenderResult, Renderer, Result};
+use crate::renderer::trace::SectionTrace;
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// What a batch of seeded renders produced
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SamplingReport {
+    /// Number of renders
+    pub samples: usize,
+
+    /// First seed; renders use `first_seed..first_seed + samples`
+    pub first_seed: u64,
+
+    /// Renders per promptsection (the entry points of a rulebook)
+    pub sections: IndexMap<String, usize>,
+
+    /// How often each value was selected, per reference, most frequent first;
+    /// references inside a nested promptsection are listed as "reference.inner",
+    /// and a rulebook's as "entry_point.reference"
+    pub references: IndexMap<String, IndexMap<String, usize>>,
+
+    /// Number of different outputs
+    pub distinct_outputs: usize,
+
+    /// Share of successful renders repeating an earlier output
+    pub duplicate_rate: f64,
+
+    /// Number of failed renders
+    pub failures: usize,
+
+    /// Share of renders that failed
+    pub failure_rate: f64,
+
+    /// Failed renders per `RenderError` variant
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub failures_by_kind: IndexMap<String, usize>,
+}
+
+impl Renderer<'_> {
+    /// Render `samples` consecutive seeds of a promptsection, starting at this
+    /// renderer's seed, and tally the results
+    pub fn sample(&self, promptsection_ref: &str, samples: usize) -> SamplingReport {
+        let mut tally = Tally::new(self.seed(), samples);
+        for seed in tally.seeds() {
+            let result = self
+                .with_seed(seed)
+                .with_trace(true)
+                .render(promptsection_ref);
+            tally.record(promptsection_ref, result, "");
+        }
+        tally.finish()
+    }
+
+    /// Render `samples` consecutive seeds of a rulebook, starting at this
+    /// renderer's seed, and tally the results and chosen entry points
+    pub fn sample_rulebook(&self, rulebook_ref: &str, samples: usize) -> Result<SamplingReport> {
+        let rulebook = self.resolve_rulebook(rulebook_ref)?;
+
+        let mut tally = Tally::new(self.seed(), samples);
+        for seed in tally.seeds() {
+            // Same steps as `render_from_rulebook`, keeping the entry point
+            let renderer = self.with_seed(seed).with_trace(true);
+            let entry_point = renderer.select_entry_point(rulebook, None)?;
+            let result =
+                renderer.render_with_context(&entry_point, Some(rulebook.context_defaults.clone()));
+            tally.record(&entry_point, result, &format!("{}.", entry_point));
+        }
+        Ok(tally.finish())
+    }
+}
+
+/// Running totals while sampling
+struct Tally {
+    report: SamplingReport,
+    outputs: HashSet<String>,
+    duplicates: usize,
+}
+
+impl Tally {
+    fn new(first_seed: u64, samples: usize) -> Self {
+        Tally {
+            report: SamplingReport {
+                first_seed,
+                samples,
+                ..Default::default()
+            },
+            outputs: HashSet::new(),
+            duplicates: 0,
+        }
+    }
+
+    fn seeds(&self) -> impl Iterator<Item = u64> {
+        let first = self.report.first_seed;
+        (0..self.report.samples as u64).map(move |i| first.wrapping_add(i))
+    }
+
+    fn record(&mut self, section: &str, result: Result<RenderResult>, prefix: &str) {
+        *self.report.sections.entry(section.to_string()).or_default() += 1;
+
+        match result {
+            Ok(result) => {
+                if let Some(trace) = &result.trace {
+                    tally_trace(trace, prefix, &mut self.report.references);
+                }
+                if !self.outputs.insert(result.output) {
+                    self.duplicates += 1;
+                }
+            }
+            Err(e) => {
+                self.report.failures += 1;
+                *self
+                    .report
+                    .failures_by_kind
+                    .entry(e.kind().to_string())
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    fn finish(mut self) -> SamplingReport {
+        let report = &mut self.report;
+        let successes = report.samples - report.failures;
+
+        report.distinct_outputs = self.outputs.len();
+        if successes > 0 {
+            report.duplicate_rate = self.duplicates as f64 / successes as f64;
+        }
+        if report.samples > 0 {
+            report.failure_rate = report.failures as f64 / report.samples as f64;
+        }
+
+        for values in report.references.values_mut() {
+            values.sort_by(|_, a, _, b| b.cmp(a));
+        }
+        report.sections.sort_by(|_, a, _, b| b.cmp(a));
+        report.failures_by_kind.sort_by(|_, a, _, b| b.cmp(a));
+
+        self.report
+    }
+}
+
+/// Count the values chosen in a trace (and its nested sections)
+fn tally_trace(
+    trace: &SectionTrace,
+    prefix: &str,
+    references: &mut IndexMap<String, IndexMap<String, usize>>,
+) {
+    for reference in &trace.references {
+        let key = format!("{}{}", prefix, reference.name);
+        match &reference.section {
+            Some(section) => tally_trace(section, &format!("{}.", key), references),
+            None => {
+                let values = references.entry(key).or_default();
+                for chosen in &reference.chosen {
+                    *values.entry(chosen.text.clone()).or_default() += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_yaml;
+
+    const PACKAGE: &str = r#"
+id: test.stats
+version: 1.0.0
+metadata:
+  name: Stats
+  authors: []
+namespaces:
+  test:
+    id: test
+    datatypes:
+      animals:
+        name: animals
+        values:
+          - text: eagle
+            tags: {can_fly: true}
+          - text: cat
+            tags: {can_fly: false}
+    prompt_sections:
+      scene:
+        name: scene
+        template: "{animal}"
+        references:
+          animal:
+            target: test:animals
+      flying:
+        name: flying
+        template: "{a/an} {inner} and {bird} in the sky"
+        references:
+          inner:
+            target: test:scene
+          bird:
+            target: test:animals
+            filter: tags.can_fly
+      broken:
+        name: broken
+        template: "{animal}"
+        references:
+          animal:
+            target: test:animals
+            filter: tags.can_swim
+    rulebooks:
+      mixed:
+        name: mixed
+        entry_points:
+          - prompt_section: test:flying
+            weight: 1
+          - prompt_section: test:broken
+            weight: 1
+"#;
+
+    #[test]
+    fn test_sample_promptsection() {
+        let package = parse_yaml(PACKAGE).unwrap();
+        let report = Renderer::new(&package, 100).sample("test:flying", 200);
+
+        assert_eq!(report.samples, 200);
+        assert_eq!(report.first_seed, 100);
+        assert_eq!(report.sections["test:flying"], 200);
+        assert_eq!(report.failures, 0);
+        assert_eq!(report.distinct_outputs, 2);
+        assert!((report.duplicate_rate - 198.0 / 200.0).abs() < 1e-9);
+
+        assert_eq!(report.references["bird"]["eagle"], 200);
+        assert!(!report.references["bird"].contains_key("cat"));
+        let inner = &report.references["inner.animal"];
+        assert_eq!(inner.values().sum::<usize>(), 200);
+        assert!(inner["eagle"] > 50 && inner["cat"] > 50);
+    }
+
+    #[test]
+    fn test_sample_rulebook() {
+        let package = parse_yaml(PACKAGE).unwrap();
+        let report = Renderer::new(&package, 0)
+            .sample_rulebook("test:mixed", 200)
+            .unwrap();
+
+        let flying = report.sections["test:flying"];
+        let broken = report.sections["test:broken"];
+        assert_eq!(flying + broken, 200);
+        assert!(flying > 50 && broken > 50);
+
+        // Every render of the broken entry point fails its filter
+        assert_eq!(report.failures, broken);
+        assert_eq!(report.failures_by_kind["Selection"], broken);
+        assert!((report.failure_rate - broken as f64 / 200.0).abs() < 1e-9);
+        assert_eq!(report.references["test:flying.bird"]["eagle"], flying);
+
+        assert!(Renderer::new(&package, 0)
+            .sample_rulebook("test:missing", 10)
+            .is_err());
+    }
+}
@@ -1,6 +1,14 @@
 // M5 Phase 2: Complex Tag Expression Parser
-// Supports AND (&&), OR (||), NOT (!), and comparisons (==, !=)
-
+// Supports AND (&&), OR (||), NOT (!), and comparisons
+// (==, !=, <, <=, >, >=, in, contains, starts_with) between tags, ref: paths,
+// context keys and literals
+
+use crate::context::Context;
+use crate::renderer::ref_path::{is_truthy, RefPath};
+use crate::renderer::selector::SelectedValue;
+use crate::rules::expression::Value;
+use crate::rules::processor::context_key_for;
+use indexmap::IndexMap;
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
 use thiserror::Error;
@@ -32,22 +40,24 @@ pub enum Expression {
     /// Logical NOT: !expr
     Not(Box<Expression>),
 
-    /// Comparison: tag == value or tag != value
-    Comparison {
-        tag: String,
+    /// Comparison between operands: tags.type == "melee", tags.size >= 3,
+    /// tags.era < ref:setting.tags.era, tags.mood == context.prompt.mood
+    Compare {
+        left: Operand,
         operator: ComparisonOp,
-        value: ComparisonValue,
+        right: Operand,
     },
 
     /// Simple tag check: tags.can_fly (checks if true)
     TagCheck(String),
 
-    /// Reference access: ref:other.text or ref:other.tags.field
+    /// Context check: context.prompt.dark (checks if set and true)
+    ContextCheck(String),
+
+    /// Reference access: ref:other.text, ref:other.tags.field, ref:others[1].text,
+    /// ref:others.count, ref:others.any.tags.field (see `RefPath`)
     /// Used for cross-reference filtering
-    RefAccess {
-        ref_name: String,
-        field_path: Vec<String>,
-    },
+    RefAccess(RefPath),
 
     /// List membership check: ref:other.text in tags.applies_to
     /// Checks if the value from ref is in the tag list
@@ -59,8 +69,32 @@ pub enum Expression {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ComparisonOp {
-    Equal,    // ==
-    NotEqual, // !=
+    Equal,        // ==
+    NotEqual,     // !=
+    Less,         // <
+    LessEqual,    // <=
+    Greater,      // >
+    GreaterEqual, // >=
+    In,           // left is an element of right (any element, for lists)
+    Contains,     // right is an element (or substring) of left
+    StartsWith,   // left text starts with right text
+}
+
+impl ComparisonOp {
+    fn from_token(token: &str) -> Option<ComparisonOp> {
+        Some(match token {
+            "==" => ComparisonOp::Equal,
+            "!=" => ComparisonOp::NotEqual,
+            "<" => ComparisonOp::Less,
+            "<=" => ComparisonOp::LessEqual,
+            ">" => ComparisonOp::Greater,
+            ">=" => ComparisonOp::GreaterEqual,
+            "in" => ComparisonOp::In,
+            "contains" => ComparisonOp::Contains,
+            "starts_with" => ComparisonOp::StartsWith,
+            _ => return None,
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -68,6 +102,22 @@ pub enum ComparisonValue {
     String(String),
     Number(f64),
     Bool(bool),
+    List(Vec<ComparisonValue>),
+}
+
+/// One side of a comparison
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    /// tags.size - the candidate value's tag
+    Tag(String),
+
+    /// ref:setting.tags.era - a previously selected value
+    Ref(RefPath),
+
+    /// context.prompt.mood, context.global.season - a context value
+    Context(String),
+
+    Literal(ComparisonValue),
 }
 
 /// Parser for tag filter expressions
@@ -166,8 +216,29 @@ impl ExpressionParser {
                     tokens.push(format!("\"{}\"", string_val));
                 }
 
-                // Parentheses
-                '(' | ')' => {
+                '<' | '>' => {
+                    if !current.is_empty() {
+                        tokens.push(current.clone());
+                        current.clear();
+                    }
+                    if chars.peek() == Some(&'=') {
+                        chars.next();
+                        tokens.push(format!("{}=", ch));
+                    } else {
+                        tokens.push(ch.to_string());
+                    }
+                }
+
+                // Parentheses, list literals ("[" inside a word is a ref: index)
+                '(' | ')' | ',' => {
+                    if !current.is_empty() {
+                        tokens.push(current.clone());
+                        current.clear();
+                    }
+                    tokens.push(ch.to_string());
+                }
+                '[' if current.is_empty() => tokens.push(ch.to_string()),
+                ']' if !current.contains('[') => {
                     if !current.is_empty() {
                         tokens.push(current.clone());
                         current.clear();
@@ -239,86 +310,88 @@ impl ExpressionParser {
             return Ok(expr);
         }
 
-        // Get the next token
-        let token = self.consume().ok_or(ParseError::UnexpectedEnd)?;
-
-        // Check if this is a ref: expression
-        if token.starts_with("ref:") {
-            // Parse ref:name.field.path
-            let ref_part = token.strip_prefix("ref:").unwrap();
-            let parts: Vec<String> = ref_part.split('.').map(|s| s.to_string()).collect();
-
-            if parts.is_empty() {
-                return Err(ParseError::UnexpectedToken(
-                    "ref: requires a reference name".to_string(),
-                ));
-            }
+        let left = self.parse_operand()?;
 
-            let ref_name = parts[0].clone();
-            let field_path = parts[1..].to_vec();
-
-            let ref_expr = Expression::RefAccess {
-                ref_name,
-                field_path,
-            };
+        // Comparison
+        if let Some(operator) = self.peek().and_then(ComparisonOp::from_token) {
+            self.consume();
+            let right = self.parse_operand()?;
 
-            // Check for "in" operator
-            if self.peek() == Some("in") {
-                self.consume();
-                // Next should be tags.something
-                let tag_token = self.consume().ok_or(ParseError::UnexpectedEnd)?;
-                if !tag_token.starts_with("tags.") {
-                    return Err(ParseError::UnexpectedToken(format!(
-                        "Expected 'tags.' after 'in', got '{}'",
-                        tag_token
-                    )));
+            return Ok(match (left, right) {
+                (Operand::Ref(path), Operand::Tag(list_tag)) if operator == ComparisonOp::In => {
+                    Expression::InList {
+                        value: Box::new(Expression::RefAccess(path)),
+                        list_tag,
+                    }
                 }
-                let list_tag = tag_token.strip_prefix("tags.").unwrap().to_string();
-
-                return Ok(Expression::InList {
-                    value: Box::new(ref_expr),
-                    list_tag,
-                });
-            }
-
-            return Ok(ref_expr);
+                (left, right) => Expression::Compare {
+                    left,
+                    operator,
+                    right,
+                },
+            });
         }
 
-        // Tag path (tags.something)
-        if !token.starts_with("tags.") {
-            return Err(ParseError::UnexpectedToken(format!(
-                "Expected 'tags.' or 'ref:', got '{}'",
-                token
-            )));
+        match left {
+            Operand::Tag(tag) => Ok(Expression::TagCheck(tag)),
+            Operand::Ref(path) => Ok(Expression::RefAccess(path)),
+            Operand::Context(key) => Ok(Expression::ContextCheck(key)),
+            Operand::Literal(value) => Err(ParseError::UnexpectedToken(format!(
+                "Expected 'tags.', 'ref:' or 'context.', got {:?}",
+                value
+            ))),
         }
+    }
 
-        let tag_name = token.strip_prefix("tags.").unwrap().to_string();
+    /// Parse one side of a comparison
+    fn parse_operand(&mut self) -> Result<Operand> {
+        let token = self.consume().ok_or(ParseError::UnexpectedEnd)?;
+
+        if let Some(ref_part) = token.strip_prefix("ref:") {
+            let path =
+                RefPath::parse(ref_part).map_err(|e| ParseError::UnexpectedToken(e.to_string()))?;
+            return Ok(Operand::Ref(path));
+        }
 
-        // Check for comparison operator
-        if let Some(op_str) = self.peek() {
-            let op_str_clone = op_str.to_string(); // Clone before consuming
-            if op_str_clone == "==" || op_str_clone == "!=" {
-                self.consume();
-                let op = match op_str_clone.as_str() {
-                    "==" => ComparisonOp::Equal,
-                    "!=" => ComparisonOp::NotEqual,
-                    _ => unreachable!(),
-                };
+        if let Some(tag_name) = token.strip_prefix("tags.") {
+            return Ok(Operand::Tag(tag_name.to_string()));
+        }
 
-                // Parse value
-                let value_token = self.consume().ok_or(ParseError::UnexpectedEnd)?;
-                let value = Self::parse_value(&value_token)?;
+        if token.starts_with("context.") {
+            return Ok(Operand::Context(token));
+        }
 
-                return Ok(Expression::Comparison {
-                    tag: tag_name,
-                    operator: op,
-                    value,
-                });
+        if token == "[" {
+            let mut items = Vec::new();
+            loop {
+                let item = self.consume().ok_or(ParseError::UnexpectedEnd)?;
+                match item.as_str() {
+                    "]" if items.is_empty() => break,
+                    _ => items.push(Self::parse_value(&item)?),
+                }
+                match self.consume().as_deref() {
+                    Some(",") => {}
+                    Some("]") => break,
+                    Some(other) => {
+                        return Err(ParseError::UnexpectedToken(format!(
+                            "Expected ',' or ']', got '{}'",
+                            other
+                        )))
+                    }
+                    None => return Err(ParseError::UnexpectedEnd),
+                }
             }
+            return Ok(Operand::Literal(ComparisonValue::List(items)));
         }
 
-        // Simple tag check
-        Ok(Expression::TagCheck(tag_name))
+        Self::parse_value(&token)
+            .map(Operand::Literal)
+            .map_err(|_| {
+                ParseError::UnexpectedToken(format!(
+                    "Expected 'tags.', 'ref:', 'context.' or a value, got '{}'",
+                    token
+                ))
+            })
     }
 
     /// Parse a comparison value
@@ -366,8 +439,8 @@ impl ExpressionParser {
 /// Evaluate an expression against a set of tags (without cross-reference support)
 /// For backward compatibility - use evaluate_with_context for cross-reference filtering
 #[allow(dead_code)]
-pub fn evaluate(expr: &Expression, tags: &HashMap<String, JsonValue>) -> bool {
-    evaluate_with_context(expr, tags, &HashMap::new())
+pub fn evaluate(expr: &Expression, tags: &IndexMap<String, JsonValue>) -> bool {
+    evaluate_with_context(expr, tags, &HashMap::new(), None)
 }
 
 /// Evaluate an expression with support for cross-reference filtering
@@ -375,159 +448,133 @@ pub fn evaluate(expr: &Expression, tags: &HashMap<String, JsonValue>) -> bool {
 /// # Arguments
 /// * `expr` - The parsed expression to evaluate
 /// * `tags` - The tags of the current value being filtered
-/// * `selected` - Previously selected values for ref: expressions
+/// * `selected` - Previously selected values (all values per reference) for ref: expressions
+/// * `context` - Context values for context. operands (None: they are missing)
 pub fn evaluate_with_context(
     expr: &Expression,
-    tags: &HashMap<String, JsonValue>,
-    selected: &HashMap<String, (String, HashMap<String, JsonValue>)>,
+    tags: &IndexMap<String, JsonValue>,
+    selected: &HashMap<String, Vec<SelectedValue>>,
+    context: Option<&Context>,
 ) -> bool {
     match expr {
         Expression::And(left, right) => {
-            evaluate_with_context(left, tags, selected)
-                && evaluate_with_context(right, tags, selected)
+            evaluate_with_context(left, tags, selected, context)
+                && evaluate_with_context(right, tags, selected, context)
         }
 
         Expression::Or(left, right) => {
-            evaluate_with_context(left, tags, selected)
-                || evaluate_with_context(right, tags, selected)
+            evaluate_with_context(left, tags, selected, context)
+                || evaluate_with_context(right, tags, selected, context)
         }
 
-        Expression::Not(inner) => !evaluate_with_context(inner, tags, selected),
+        Expression::Not(inner) => !evaluate_with_context(inner, tags, selected, context),
 
-        Expression::Comparison {
-            tag,
+        Expression::Compare {
+            left,
             operator,
-            value,
+            right,
         } => {
-            if let Some(tag_value) = tags.get(tag) {
-                match (operator, value) {
-                    (ComparisonOp::Equal, ComparisonValue::String(s)) => {
-                        tag_value.as_str() == Some(s)
-                    }
-                    (ComparisonOp::NotEqual, ComparisonValue::String(s)) => {
-                        tag_value.as_str() != Some(s)
-                    }
-                    (ComparisonOp::Equal, ComparisonValue::Number(n)) => {
-                        tag_value.as_f64() == Some(*n)
-                    }
-                    (ComparisonOp::NotEqual, ComparisonValue::Number(n)) => {
-                        tag_value.as_f64() != Some(*n)
-                    }
-                    (ComparisonOp::Equal, ComparisonValue::Bool(b)) => {
-                        tag_value.as_bool() == Some(*b)
-                    }
-                    (ComparisonOp::NotEqual, ComparisonValue::Bool(b)) => {
-                        tag_value.as_bool() != Some(*b)
+            // A missing operand (unset tag, unselected ref, unset context key) fails
+            let resolve = |operand: &Operand| -> Option<Value> {
+                match operand {
+                    Operand::Tag(tag) => tags.get(tag).map(Value::from),
+                    Operand::Ref(path) => path.resolve(selected).ok().map(|v| Value::from(&v)),
+                    Operand::Context(key) => {
+                        context?.get(context_key_for(key)).ok().map(Value::from)
                     }
+                    Operand::Literal(value) => Some(literal(value)),
                 }
-            } else {
-                false
+            };
+            match (resolve(left), resolve(right)) {
+                (Some(left), Some(right)) => compare(&left, operator, &right),
+                _ => false,
             }
         }
 
         Expression::TagCheck(tag) => {
-            if let Some(tag_value) = tags.get(tag) {
-                // Check if tag is truthy
-                match tag_value {
-                    JsonValue::Bool(b) => *b,
-                    JsonValue::String(s) => !s.is_empty(),
-                    JsonValue::Number(n) => n.as_f64().unwrap_or(0.0) != 0.0,
-                    JsonValue::Null => false,
-                    _ => true, // Arrays and objects are truthy
-                }
-            } else {
-                false
-            }
+            // Check if tag is present and truthy
+            tags.get(tag).is_some_and(is_truthy)
         }
 
-        Expression::RefAccess {
-            ref_name,
-            field_path,
-        } => {
-            // Look up the selected reference
-            if let Some((text, ref_tags)) = selected.get(ref_name) {
-                if field_path.is_empty() {
-                    // Just ref:name - return the text as truthy check
-                    !text.is_empty()
-                } else if field_path.len() == 1 && field_path[0] == "text" {
-                    // ref:name.text
-                    !text.is_empty()
-                } else if field_path.len() >= 2 && field_path[0] == "tags" {
-                    // ref:name.tags.field
-                    let tag_name = &field_path[1];
-                    if let Some(tag_value) = ref_tags.get(tag_name) {
-                        // Check if truthy
-                        match tag_value {
-                            JsonValue::Bool(b) => *b,
-                            JsonValue::String(s) => !s.is_empty(),
-                            JsonValue::Number(n) => n.as_f64().unwrap_or(0.0) != 0.0,
-                            JsonValue::Null => false,
-                            _ => true,
-                        }
-                    } else {
-                        false
-                    }
-                } else {
-                    false
-                }
-            } else {
-                // Reference not found - fail gracefully
-                false
-            }
+        Expression::ContextCheck(key) => context
+            .and_then(|context| context.get(context_key_for(key)).ok())
+            .is_some_and(|value| Value::from(value).is_truthy()),
+
+        Expression::RefAccess(path) => {
+            // Truthiness of the addressed value(s); missing refs fail gracefully
+            path.resolve(selected)
+                .map(|value| is_truthy(&value))
+                .unwrap_or(false)
         }
 
         Expression::InList { value, list_tag } => {
-            // Evaluate the value expression to get a string
-            let ref_value = match value.as_ref() {
-                Expression::RefAccess {
-                    ref_name,
-                    field_path,
-                } => {
-                    if let Some((text, ref_tags)) = selected.get(ref_name) {
-                        if field_path.is_empty()
-                            || (field_path.len() == 1 && field_path[0] == "text")
-                        {
-                            Some(text.clone())
-                        } else if field_path.len() >= 2 && field_path[0] == "tags" {
-                            // Get tag value as string
-                            let tag_name = &field_path[1];
-                            ref_tags
-                                .get(tag_name)
-                                .and_then(|v| v.as_str().map(|s| s.to_string()))
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                }
-                _ => None,
+            // Evaluate the value expression to get the candidate string(s)
+            let ref_values: Vec<String> = match value.as_ref() {
+                Expression::RefAccess(path) => match path.resolve(selected) {
+                    Ok(JsonValue::String(s)) => vec![s],
+                    Ok(JsonValue::Array(items)) => items
+                        .iter()
+                        .filter_map(|item| item.as_str().map(|s| s.to_string()))
+                        .collect(),
+                    _ => Vec::new(),
+                },
+                _ => Vec::new(),
             };
 
-            if let Some(ref_val) = ref_value {
-                // Check if the tag contains a list with this value
-                if let Some(tag_value) = tags.get(list_tag) {
-                    match tag_value {
-                        JsonValue::Array(arr) => {
-                            // Check if any array element matches
-                            arr.iter().any(|item| item.as_str() == Some(&ref_val))
-                        }
-                        JsonValue::String(s) => {
-                            // Single string match
-                            s == &ref_val
-                        }
-                        _ => false,
-                    }
-                } else {
-                    false
-                }
-            } else {
-                false
+            // Check if the tag contains a list with any of these values
+            match tags.get(list_tag) {
+                Some(JsonValue::Array(arr)) => ref_values
+                    .iter()
+                    .any(|ref_val| arr.iter().any(|item| item.as_str() == Some(ref_val))),
+                Some(JsonValue::String(s)) => ref_values.iter().any(|ref_val| s == ref_val),
+                _ => false,
             }
         }
     }
 }
 
+/// Convert a literal to an expression value
+fn literal(value: &ComparisonValue) -> Value {
+    match value {
+        ComparisonValue::String(s) => Value::Text(s.clone()),
+        ComparisonValue::Number(n) => Value::Number(*n),
+        ComparisonValue::Bool(b) => Value::Bool(*b),
+        ComparisonValue::List(items) => Value::List(items.iter().map(literal).collect()),
+    }
+}
+
+/// Apply a comparison operator
+///
+/// Shares the rule expression semantics: numbers compare numerically (numeric
+/// text included), other values by text. A list on the left of `in` matches
+/// if any of its items is in the right side (multi-select refs).
+fn compare(left: &Value, operator: &ComparisonOp, right: &Value) -> bool {
+    use std::cmp::Ordering;
+
+    match operator {
+        ComparisonOp::Equal => left.loose_eq(right),
+        ComparisonOp::NotEqual => !left.loose_eq(right),
+        ComparisonOp::Less => left.compare(right) == Some(Ordering::Less),
+        ComparisonOp::LessEqual => {
+            matches!(left.compare(right), Some(Ordering::Less | Ordering::Equal))
+        }
+        ComparisonOp::Greater => left.compare(right) == Some(Ordering::Greater),
+        ComparisonOp::GreaterEqual => matches!(
+            left.compare(right),
+            Some(Ordering::Greater | Ordering::Equal)
+        ),
+        ComparisonOp::In => match left {
+            Value::List(items) => items.iter().any(|item| right.contains(item)),
+            _ => right.contains(left),
+        },
+        ComparisonOp::Contains => left.contains(right),
+        ComparisonOp::StartsWith => match (left, right) {
+            (Value::Text(text), Value::Text(prefix)) => text.starts_with(prefix.as_str()),
+            _ => false,
+        },
+    }
+}
+
 /// Extract all reference names used in a tag expression
 ///
 /// Used for dependency ordering - determines which references must be selected
@@ -535,14 +582,12 @@ pub fn evaluate_with_context(
 ///
 /// # Example
 /// ```
+/// use rpg_lib::renderer::ref_path::RefPath;
 /// use rpg_lib::renderer::tag_expression::{extract_ref_dependencies, Expression};
 ///
 /// // Example: "ref:body_part.text in tags.applies_to"
 /// let expr = Expression::InList {
-///     value: Box::new(Expression::RefAccess {
-///         ref_name: "body_part".to_string(),
-///         field_path: vec!["text".to_string()],
-///     }),
+///     value: Box::new(Expression::RefAccess(RefPath::parse("body_part.text").unwrap())),
 ///     list_tag: "applies_to".to_string(),
 /// };
 ///
@@ -564,21 +609,50 @@ fn collect_ref_dependencies(expr: &Expression, deps: &mut Vec<String>) {
         Expression::Not(inner) => {
             collect_ref_dependencies(inner, deps);
         }
-        Expression::RefAccess { ref_name, .. } => {
-            if !deps.contains(ref_name) {
-                deps.push(ref_name.clone());
+        Expression::RefAccess(path) => {
+            if !deps.contains(&path.name) {
+                deps.push(path.name.clone());
             }
         }
         Expression::InList { value, .. } => {
             collect_ref_dependencies(value, deps);
         }
+        Expression::Compare { left, right, .. } => {
+            for operand in [left, right] {
+                if let Operand::Ref(path) = operand {
+                    if !deps.contains(&path.name) {
+                        deps.push(path.name.clone());
+                    }
+                }
+            }
+        }
         _ => {} // Other expressions don't contain ref dependencies
     }
 }
 
+/// Whether a filter expression reads the context (context.prompt.mood)
+///
+/// Used for dependency ordering: such filters must wait for the nested
+/// promptsections whose rules may write that context
+pub fn reads_context(expr: &Expression) -> bool {
+    match expr {
+        Expression::And(left, right) | Expression::Or(left, right) => {
+            reads_context(left) || reads_context(right)
+        }
+        Expression::Not(inner) => reads_context(inner),
+        Expression::ContextCheck(_) => true,
+        Expression::InList { value, .. } => reads_context(value),
+        Expression::Compare { left, right, .. } => [left, right]
+            .iter()
+            .any(|operand| matches!(operand, Operand::Context(_))),
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use indexmap::IndexMap;
 
     #[test]
     fn test_tokenize() {
@@ -613,21 +687,24 @@ mod tests {
     #[test]
     fn test_parse_comparison() {
         let expr = ExpressionParser::parse("tags.type == \"melee\"").unwrap();
-        assert!(matches!(expr, Expression::Comparison { .. }));
+        assert!(matches!(
+            expr,
+            Expression::Compare {
+                left: Operand::Tag(_),
+                operator: ComparisonOp::Equal,
+                right: Operand::Literal(ComparisonValue::String(_)),
+            }
+        ));
     }
 
     #[test]
     fn test_parse_ref_access() {
         let expr = ExpressionParser::parse("ref:body_part.text").unwrap();
-        assert!(matches!(expr, Expression::RefAccess { .. }));
+        assert!(matches!(expr, Expression::RefAccess(_)));
 
-        if let Expression::RefAccess {
-            ref_name,
-            field_path,
-        } = expr
-        {
-            assert_eq!(ref_name, "body_part");
-            assert_eq!(field_path, vec!["text"]);
+        if let Expression::RefAccess(path) = expr {
+            assert_eq!(path.name, "body_part");
+            assert_eq!(path.field, crate::renderer::ref_path::RefField::Text);
         }
     }
 
@@ -637,7 +714,7 @@ mod tests {
         assert!(matches!(expr, Expression::InList { .. }));
 
         if let Expression::InList { value, list_tag } = expr {
-            assert!(matches!(value.as_ref(), Expression::RefAccess { .. }));
+            assert!(matches!(value.as_ref(), Expression::RefAccess(_)));
             assert_eq!(list_tag, "applies_to");
         }
     }
@@ -647,21 +724,24 @@ mod tests {
         use serde_json::json;
 
         // Set up tags for a "scarred" quality
-        let mut tags = HashMap::new();
+        let mut tags = IndexMap::new();
         tags.insert("applies_to".to_string(), json!(["skin", "face"]));
 
         // Set up selected values context - body_part is "skin"
         let mut selected = HashMap::new();
-        let mut body_part_tags = HashMap::new();
+        let mut body_part_tags = IndexMap::new();
         body_part_tags.insert("type".to_string(), json!("body_part"));
         selected.insert(
             "body_part".to_string(),
-            ("skin".to_string(), body_part_tags),
+            vec![SelectedValue {
+                text: "skin".to_string(),
+                tags: body_part_tags,
+            }],
         );
 
         // Parse and evaluate: ref:body_part.text in tags.applies_to
         let expr = ExpressionParser::parse("ref:body_part.text in tags.applies_to").unwrap();
-        let result = evaluate_with_context(&expr, &tags, &selected);
+        let result = evaluate_with_context(&expr, &tags, &selected, None);
 
         // Should be true because "skin" is in ["skin", "face"]
         assert!(result);
@@ -669,9 +749,12 @@ mod tests {
         // Now test with body_part = "beard" (not in applies_to)
         selected.insert(
             "body_part".to_string(),
-            ("beard".to_string(), HashMap::new()),
+            vec![SelectedValue {
+                text: "beard".to_string(),
+                tags: IndexMap::new(),
+            }],
         );
-        let result = evaluate_with_context(&expr, &tags, &selected);
+        let result = evaluate_with_context(&expr, &tags, &selected, None);
 
         // Should be false because "beard" is not in ["skin", "face"]
         assert!(!result);
@@ -679,7 +762,7 @@ mod tests {
 
     #[test]
     fn test_evaluate_simple() {
-        let mut tags = HashMap::new();
+        let mut tags = IndexMap::new();
         tags.insert("can_fly".to_string(), JsonValue::Bool(true));
 
         let expr = Expression::TagCheck("can_fly".to_string());
@@ -688,7 +771,7 @@ mod tests {
 
     #[test]
     fn test_evaluate_and() {
-        let mut tags = HashMap::new();
+        let mut tags = IndexMap::new();
         tags.insert("can_fly".to_string(), JsonValue::Bool(true));
         tags.insert("nocturnal".to_string(), JsonValue::Bool(true));
 
@@ -701,14 +784,148 @@ mod tests {
 
     #[test]
     fn test_evaluate_comparison() {
-        let mut tags = HashMap::new();
+        let mut tags = IndexMap::new();
         tags.insert("type".to_string(), JsonValue::String("melee".to_string()));
 
-        let expr = Expression::Comparison {
-            tag: "type".to_string(),
+        let expr = Expression::Compare {
+            left: Operand::Tag("type".to_string()),
             operator: ComparisonOp::Equal,
-            value: ComparisonValue::String("melee".to_string()),
+            right: Operand::Literal(ComparisonValue::String("melee".to_string())),
         };
         assert!(evaluate(&expr, &tags));
     }
+
+    #[test]
+    fn test_evaluate_multi_select_refs() {
+        use serde_json::json;
+
+        let mut selected = HashMap::new();
+        selected.insert(
+            "parts".to_string(),
+            vec![
+                SelectedValue {
+                    text: "arm".to_string(),
+                    tags: IndexMap::from([("paired".to_string(), json!(true))]),
+                },
+                SelectedValue {
+                    text: "face".to_string(),
+                    tags: IndexMap::from([("paired".to_string(), json!(false))]),
+                },
+            ],
+        );
+
+        let mut tags = IndexMap::new();
+        tags.insert("applies_to".to_string(), json!(["face"]));
+
+        let eval = |filter: &str| {
+            let expr = ExpressionParser::parse(filter).unwrap();
+            evaluate_with_context(&expr, &tags, &selected, None)
+        };
+
+        assert!(eval("ref:parts[1].text in tags.applies_to"));
+        assert!(!eval("ref:parts.text in tags.applies_to"));
+        assert!(eval("ref:parts[*].text in tags.applies_to"));
+        assert!(eval("ref:parts.any.tags.paired"));
+        assert!(!eval("ref:parts.all.tags.paired"));
+        assert!(eval("ref:parts.count"));
+        assert_eq!(
+            extract_ref_dependencies(&ExpressionParser::parse("ref:parts[1].text").unwrap()),
+            vec!["parts"]
+        );
+    }
+
+    #[test]
+    fn test_parse_ordering_and_membership() {
+        assert_eq!(
+            ExpressionParser::parse("tags.size >= 3").unwrap(),
+            Expression::Compare {
+                left: Operand::Tag("size".to_string()),
+                operator: ComparisonOp::GreaterEqual,
+                right: Operand::Literal(ComparisonValue::Number(3.0)),
+            }
+        );
+        assert_eq!(
+            ExpressionParser::parse("tags.era < ref:setting.tags.era").unwrap(),
+            Expression::Compare {
+                left: Operand::Tag("era".to_string()),
+                operator: ComparisonOp::Less,
+                right: Operand::Ref(RefPath::parse("setting.tags.era").unwrap()),
+            }
+        );
+        assert_eq!(
+            ExpressionParser::parse(r#"tags.biome in ["forest", "swamp"]"#).unwrap(),
+            Expression::Compare {
+                left: Operand::Tag("biome".to_string()),
+                operator: ComparisonOp::In,
+                right: Operand::Literal(ComparisonValue::List(vec![
+                    ComparisonValue::String("forest".to_string()),
+                    ComparisonValue::String("swamp".to_string()),
+                ])),
+            }
+        );
+        assert_eq!(
+            extract_ref_dependencies(
+                &ExpressionParser::parse("tags.size >= 3 && tags.era < ref:setting.tags.era")
+                    .unwrap()
+            ),
+            vec!["setting"]
+        );
+
+        assert!(ExpressionParser::parse("tags.size >=").is_err());
+        assert!(ExpressionParser::parse("tags.biome in [\"forest\"").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_ordering_and_membership() {
+        use serde_json::json;
+
+        let mut selected = HashMap::new();
+        selected.insert(
+            "setting".to_string(),
+            vec![SelectedValue {
+                text: "renaissance".to_string(),
+                tags: IndexMap::from([("era".to_string(), json!(5))]),
+            }],
+        );
+
+        let mut context = Context::new();
+        context.set("mood", "grim").unwrap();
+        context.set("global:season", "winter").unwrap();
+
+        let tags = IndexMap::from([
+            ("size".to_string(), json!(4)),
+            ("era".to_string(), json!(3)),
+            ("name".to_string(), json!("iron sword")),
+            ("moods".to_string(), json!(["grim", "tense"])),
+            ("seasons".to_string(), json!("autumn, winter")),
+        ]);
+
+        let eval = |filter: &str| {
+            let expr = ExpressionParser::parse(filter).unwrap();
+            evaluate_with_context(&expr, &tags, &selected, Some(&context))
+        };
+
+        assert!(eval("tags.size >= 3 && tags.era < ref:setting.tags.era"));
+        assert!(eval("tags.size > 3 && tags.size <= 4 && !(tags.size < 4)"));
+        assert!(!eval("tags.era >= ref:setting.tags.era"));
+        assert!(eval("tags.size in [2, 4, 8]"));
+        assert!(!eval("tags.size in []"));
+        assert!(eval(r#"tags.moods contains "tense""#));
+        assert!(eval("context.prompt.mood in tags.moods"));
+        assert!(eval("tags.moods contains context.prompt.mood"));
+        assert!(eval("context.global.season in tags.seasons"));
+        assert!(eval(r#"tags.name starts_with "iron""#));
+        assert!(!eval(r#"tags.name starts_with "sword""#));
+
+        assert!(eval("context.prompt.mood && context.global.season"));
+        assert!(!eval("context.prompt.weather"));
+
+        // Missing operands never match
+        assert!(!eval("tags.weight < 10"));
+        assert!(!eval("tags.era < ref:missing.tags.era"));
+        assert!(!eval("context.prompt.weather != tags.name"));
+
+        let expr = ExpressionParser::parse("context.prompt.mood in tags.moods").unwrap();
+        assert!(!evaluate_with_context(&expr, &tags, &selected, None));
+    }
 }
@@ -1,6 +1,12 @@
 // M3: Template Parser
 // Parses template strings containing {reference} syntax
+// Optional segments: {?condition}...{?else}...{/?} and {%30}...{/%}
+// Text transforms: {creature|a_an|capitalize}
+// Automatic articles: {a/an} {creature}
 
+use crate::core::models::{Fallback, Reference};
+use crate::rules::expression::Expr;
+use indexmap::IndexMap;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -13,6 +19,15 @@ pub enum ParseError {
 
     #[error("Invalid reference format: {0}")]
     InvalidFormat(String),
+
+    #[error("Invalid condition '{condition}': {reason}")]
+    InvalidCondition { condition: String, reason: String },
+
+    #[error("Unclosed segment at position {0}")]
+    UnclosedSegment(usize),
+
+    #[error("Unexpected '{tag}' at position {pos}")]
+    UnexpectedSegmentTag { tag: String, pos: usize },
 }
 
 pub type Result<T> = std::result::Result<T, ParseError>;
@@ -25,14 +40,79 @@ pub enum TemplateToken {
 
     /// Reference to a datatype or promptsection
     /// M5 Phase 3+4: Added min/max/separator/unique parameters
+    /// Inline parameters are None when not given in the template
+    /// (see `Template::effective_references` for how they combine with YAML)
     Reference {
         name: String,
         filter: Option<String>,
-        min: usize,                // M5: Default 1
-        max: usize,                // M5: Default 1
+        min: Option<usize>,        // M5: Inline min
+        max: Option<usize>,        // M5: Inline max
         separator: Option<String>, // M5: Optional separator set reference
-        unique: bool,              // M5: Default false
+        unique: Option<bool>,      // M5: Inline unique flag
+        transforms: Vec<String>,   // Pipe transforms, applied left to right
     },
+
+    /// Optional segment: `{?condition}then{?else}otherwise{/?}`
+    /// The condition is a rule expression over `ref:` paths and context keys
+    Conditional {
+        condition: Expr,
+        then: Vec<TemplateToken>,
+        otherwise: Vec<TemplateToken>,
+    },
+
+    /// Automatic article: `{a/an}` (or `{A/An}`) becomes "a" or "an" to
+    /// match the next rendered word
+    Article { capitalized: bool },
+
+    /// Probability-gated segment: `{%30}tokens{/%}` renders 30% of the time
+    /// `index` numbers the gates in template order (each draws its own seed)
+    Chance {
+        percent: u32,
+        index: usize,
+        tokens: Vec<TemplateToken>,
+    },
+}
+
+/// A segment opened while parsing and not yet closed
+struct OpenSegment {
+    kind: SegmentKind,
+    pos: usize,
+    /// Tokens before `{?else}`, once it has been seen
+    then: Option<Vec<TemplateToken>>,
+    tokens: Vec<TemplateToken>,
+}
+
+enum SegmentKind {
+    Conditional(Expr),
+    Chance { percent: u32, index: usize },
+}
+
+/// Token list that new tokens go to: the innermost open segment, or the template
+fn current<'t>(
+    tokens: &'t mut Vec<TemplateToken>,
+    open: &'t mut [OpenSegment],
+) -> &'t mut Vec<TemplateToken> {
+    match open.last_mut() {
+        Some(segment) => &mut segment.tokens,
+        None => tokens,
+    }
+}
+
+/// Collect reference tokens in template order, including those inside segments
+fn collect_references<'t>(tokens: &'t [TemplateToken], out: &mut Vec<&'t TemplateToken>) {
+    for token in tokens {
+        match token {
+            TemplateToken::Text(_) | TemplateToken::Article { .. } => {}
+            TemplateToken::Reference { .. } => out.push(token),
+            TemplateToken::Conditional {
+                then, otherwise, ..
+            } => {
+                collect_references(then, out);
+                collect_references(otherwise, out);
+            }
+            TemplateToken::Chance { tokens, .. } => collect_references(tokens, out),
+        }
+    }
 }
 
 /// Parsed template
@@ -51,10 +131,17 @@ impl Template {
     /// - References: "{color}", "{namespace:datatype}"
     /// - Escaped braces: "{{" becomes "{", "}}" becomes "}"
     ///
-    /// M3 Scope: Simple {reference} only, no parameters
-    /// M5 will add: {ref?min=1,max=3&sep=comma}
+    /// - Parameters: "{ref?min=1,max=3&sep=comma&unique}"
+    /// - Inline filters: "{animal#{tags.can_fly}}"
+    /// - Optional segments: "{?ref:weather} in the {weather}{/?}",
+    ///   "{?ref:creature.tags.can_fly}wings{?else}legs{/?}"
+    /// - Probability gates: "{%30}, at dusk{/%}"
+    /// - Transforms: "{creature|capitalize}", "{adj|a_an}"
+    /// - Automatic articles: "{a/an} {adjective} {creature}", "{A/An} ..."
     pub fn parse(template: &str) -> Result<Self> {
         let mut tokens = Vec::new();
+        let mut open: Vec<OpenSegment> = Vec::new();
+        let mut chances = 0;
         let mut current_text = String::new();
         let mut chars = template.char_indices().peekable();
 
@@ -72,10 +159,36 @@ impl Template {
 
                     // Save accumulated text
                     if !current_text.is_empty() {
-                        tokens.push(TemplateToken::Text(current_text.clone()));
+                        current(&mut tokens, &mut open)
+                            .push(TemplateToken::Text(current_text.clone()));
                         current_text.clear();
                     }
 
+                    // Segment tags: {?condition}, {?else}, {/?}, {%30}, {/%}
+                    if let Some(&(_, '?' | '%' | '/')) = chars.peek() {
+                        let mut tag = String::new();
+                        let mut found_close = false;
+                        for (_, ch) in chars.by_ref() {
+                            if ch == '}' {
+                                found_close = true;
+                                break;
+                            }
+                            tag.push(ch);
+                        }
+                        if !found_close {
+                            return Err(ParseError::UnclosedReference(pos));
+                        }
+
+                        Self::parse_segment_tag(
+                            tag.trim(),
+                            pos,
+                            &mut tokens,
+                            &mut open,
+                            &mut chances,
+                        )?;
+                        continue;
+                    }
+
                     // Parse reference name and parameters
                     let mut ref_text = String::new();
                     let mut filter_expr = None;
@@ -129,23 +242,48 @@ impl Template {
                         return Err(ParseError::UnclosedReference(pos));
                     }
 
-                    let ref_text = ref_text.trim();
+                    // Transforms follow the name and parameters: name?max=2|upper
+                    let mut pipes = ref_text.split('|');
+                    let ref_text = pipes.next().unwrap_or_default().trim();
                     if ref_text.is_empty() {
                         return Err(ParseError::EmptyReference(pos));
                     }
+                    let transforms = pipes
+                        .map(|t| match t.trim() {
+                            "" => Err(ParseError::InvalidFormat(format!(
+                                "Empty transform in reference '{}'",
+                                ref_text
+                            ))),
+                            t => Ok(t.to_string()),
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+
+                    if ref_text.eq_ignore_ascii_case("a/an") {
+                        if filter_expr.is_some() || !transforms.is_empty() {
+                            return Err(ParseError::InvalidFormat(format!(
+                                "'{{{}}}' takes no filter or transforms",
+                                ref_text
+                            )));
+                        }
+                        current(&mut tokens, &mut open).push(TemplateToken::Article {
+                            capitalized: ref_text.starts_with('A'),
+                        });
+                        continue;
+                    }
 
                     // M5 Phase 3+4: Parse name and parameters
                     // Format: name?min=2,max=3&sep=comma_and&unique=true
                     let (name, min, max, separator, unique) =
                         Self::parse_reference_params(ref_text)?;
 
-                    tokens.push(TemplateToken::Reference {
+                    current(&mut tokens, &mut open).push(TemplateToken::Reference {
                         name,
                         filter: filter_expr,
                         min,
                         max,
                         separator,
                         unique,
+                        transforms,
                     });
                 }
 
@@ -172,7 +310,11 @@ impl Template {
 
         // Add remaining text
         if !current_text.is_empty() {
-            tokens.push(TemplateToken::Text(current_text));
+            current(&mut tokens, &mut open).push(TemplateToken::Text(current_text));
+        }
+
+        if let Some(segment) = open.last() {
+            return Err(ParseError::UnclosedSegment(segment.pos));
         }
 
         Ok(Template {
@@ -181,12 +323,108 @@ impl Template {
         })
     }
 
+    /// Handle a segment tag (the text between the braces)
+    fn parse_segment_tag(
+        tag: &str,
+        pos: usize,
+        tokens: &mut Vec<TemplateToken>,
+        open: &mut Vec<OpenSegment>,
+        chances: &mut usize,
+    ) -> Result<()> {
+        let unexpected = || ParseError::UnexpectedSegmentTag {
+            tag: format!("{{{}}}", tag),
+            pos,
+        };
+
+        match tag {
+            "?else" => {
+                let segment = open
+                    .last_mut()
+                    .filter(|s| matches!(s.kind, SegmentKind::Conditional(_)) && s.then.is_none())
+                    .ok_or_else(unexpected)?;
+                segment.then = Some(std::mem::take(&mut segment.tokens));
+            }
+            "/?" | "/%" => {
+                let segment = open.pop().ok_or_else(unexpected)?;
+                let token = match (tag, segment.kind) {
+                    ("/?", SegmentKind::Conditional(condition)) => {
+                        let (then, otherwise) = match segment.then {
+                            Some(then) => (then, segment.tokens),
+                            None => (segment.tokens, Vec::new()),
+                        };
+                        TemplateToken::Conditional {
+                            condition,
+                            then,
+                            otherwise,
+                        }
+                    }
+                    ("/%", SegmentKind::Chance { percent, index }) => TemplateToken::Chance {
+                        percent,
+                        index,
+                        tokens: segment.tokens,
+                    },
+                    _ => return Err(unexpected()),
+                };
+                current(tokens, open).push(token);
+            }
+            _ => {
+                let kind = if let Some(condition) = tag.strip_prefix('?') {
+                    let condition = condition.trim();
+                    let invalid = |reason: String| ParseError::InvalidCondition {
+                        condition: condition.to_string(),
+                        reason,
+                    };
+                    if condition.is_empty() {
+                        return Err(invalid("empty condition".to_string()));
+                    }
+                    SegmentKind::Conditional(
+                        Expr::parse(condition).map_err(|e| invalid(e.to_string()))?,
+                    )
+                } else if let Some(percent) = tag.strip_prefix('%') {
+                    let percent = percent
+                        .trim()
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|p| *p <= 100)
+                        .ok_or_else(|| {
+                            ParseError::InvalidFormat(format!(
+                                "Invalid probability '{}': expected 0-100",
+                                percent.trim()
+                            ))
+                        })?;
+                    *chances += 1;
+                    SegmentKind::Chance {
+                        percent,
+                        index: *chances - 1,
+                    }
+                } else {
+                    return Err(unexpected());
+                };
+
+                open.push(OpenSegment {
+                    kind,
+                    pos,
+                    then: None,
+                    tokens: Vec::new(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reference tokens in template order (including those inside segments)
+    fn reference_tokens(&self) -> Vec<&TemplateToken> {
+        let mut references = Vec::new();
+        collect_references(&self.tokens, &mut references);
+        references
+    }
+
     /// Get list of all reference names in this template
-    /// Extract all reference names from the template
-    #[allow(dead_code)] // Public API method for future use
+    /// Extract all reference names from the template (including optional segments)
     pub fn get_references(&self) -> Vec<String> {
-        self.tokens
-            .iter()
+        self.reference_tokens()
+            .into_iter()
             .filter_map(|token| {
                 if let TemplateToken::Reference { name, .. } = token {
                     Some(name.clone())
@@ -197,29 +435,153 @@ impl Template {
             .collect()
     }
 
+    /// Reference names that only appear inside optional segments
+    ///
+    /// Such a reference may never render, so a failed selection for it only
+    /// fails the render if it is actually rendered.
+    pub fn optional_references(&self) -> Vec<String> {
+        let always: Vec<&str> = self
+            .tokens
+            .iter()
+            .filter_map(|token| match token {
+                TemplateToken::Reference { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        let mut optional = Vec::new();
+        for name in self.get_references() {
+            if !always.contains(&name.as_str()) && !optional.contains(&name) {
+                optional.push(name);
+            }
+        }
+        optional
+    }
+
+    /// Pipe transforms used in this template, as (reference name, transform)
+    pub fn get_transforms(&self) -> Vec<(&str, &str)> {
+        self.reference_tokens()
+            .into_iter()
+            .flat_map(|token| match token {
+                TemplateToken::Reference {
+                    name, transforms, ..
+                } => transforms
+                    .iter()
+                    .map(|t| (name.as_str(), t.as_str()))
+                    .collect(),
+                _ => Vec::new(),
+            })
+            .collect()
+    }
+
+    /// Effective reference definitions for this template
+    ///
+    /// Starts from the promptsection's YAML `references` and applies inline
+    /// parameters and filters on top (the first occurrence of a name carries
+    /// its inline parameters). A name without a YAML definition becomes an
+    /// implicit reference whose target is the name itself; bare names are
+    /// qualified with `namespace`. A lone inline `min` or `max` keeps the
+    /// other bound consistent (e.g. `{x?min=3}` also raises max to 3).
+    pub fn effective_references(
+        &self,
+        defined: &IndexMap<String, Reference>,
+        namespace: &str,
+    ) -> IndexMap<String, Reference> {
+        let mut references = defined.clone();
+        let mut seen: Vec<&str> = Vec::new();
+
+        for token in self.reference_tokens() {
+            let TemplateToken::Reference {
+                name,
+                filter,
+                min,
+                max,
+                separator,
+                unique,
+                ..
+            } = token
+            else {
+                continue;
+            };
+
+            if seen.contains(&name.as_str()) {
+                continue;
+            }
+            seen.push(name);
+
+            let reference = references.entry(name.clone()).or_insert_with(|| Reference {
+                target: if name.contains(':') {
+                    name.clone()
+                } else {
+                    format!("{}:{}", namespace, name)
+                },
+                filter: None,
+                min: 1,
+                max: 1,
+                separator: None,
+                unique: false,
+                fallback: Fallback::Error,
+            });
+
+            if let Some(filter) = filter {
+                reference.filter = Some(filter.clone());
+            }
+            match (*min, *max) {
+                (Some(min), Some(max)) => {
+                    reference.min = min;
+                    reference.max = max;
+                }
+                (Some(min), None) => {
+                    reference.min = min;
+                    reference.max = reference.max.max(min);
+                }
+                (None, Some(max)) => {
+                    reference.min = reference.min.min(max);
+                    reference.max = max;
+                }
+                (None, None) => {}
+            }
+            if let Some(separator) = separator {
+                reference.separator = Some(separator.clone());
+            }
+            if let Some(unique) = unique {
+                reference.unique = *unique;
+            }
+        }
+
+        references
+    }
+
     /// M5 Phase 3+4: Parse reference parameters
     /// Format: name?min=2,max=3&sep=comma_and&unique=true
-    /// Returns: (name, min, max, separator, unique)
+    /// Returns: (name, min, max, separator, unique); parameters not given are None
+    #[allow(clippy::type_complexity)]
     fn parse_reference_params(
         ref_text: &str,
-    ) -> Result<(String, usize, usize, Option<String>, bool)> {
+    ) -> Result<(
+        String,
+        Option<usize>,
+        Option<usize>,
+        Option<String>,
+        Option<bool>,
+    )> {
         // Split on '?' to separate name from parameters
         let parts: Vec<&str> = ref_text.splitn(2, '?').collect();
-        let name = parts[0].to_string();
+        let name = parts[0].trim().to_string();
 
-        // Default values
-        let mut min = 1;
-        let mut max = 1;
+        // Not given unless present inline
+        let mut min = None;
+        let mut max = None;
         let mut separator = None;
-        let mut unique = false;
+        let mut unique = None;
 
         // Parse parameters if present
         if parts.len() > 1 {
             let params_text = parts[1];
 
-            // Split on '&' for multiple parameter groups
+            // Split on '&' between parameter groups and ',' within a group
             // Example: "min=2,max=3&sep=comma_and&unique=true"
-            for param_group in params_text.split('&') {
+            for param_group in params_text.split(['&', ',']) {
                 // Check if it's a key=value pair or a boolean flag
                 if param_group.contains('=') {
                     let kv: Vec<&str> = param_group.splitn(2, '=').collect();
@@ -229,31 +591,31 @@ impl Template {
 
                         match key {
                             "min" => {
-                                min = value.parse::<usize>().map_err(|_| {
+                                min = Some(value.parse::<usize>().map_err(|_| {
                                     ParseError::InvalidFormat(format!(
                                         "Invalid min value: {}",
                                         value
                                     ))
-                                })?;
+                                })?);
                             }
                             "max" => {
-                                max = value.parse::<usize>().map_err(|_| {
+                                max = Some(value.parse::<usize>().map_err(|_| {
                                     ParseError::InvalidFormat(format!(
                                         "Invalid max value: {}",
                                         value
                                     ))
-                                })?;
+                                })?);
                             }
                             "sep" => {
                                 separator = Some(value.to_string());
                             }
                             "unique" => {
-                                unique = value.parse::<bool>().map_err(|_| {
+                                unique = Some(value.parse::<bool>().map_err(|_| {
                                     ParseError::InvalidFormat(format!(
                                         "Invalid unique value: {}",
                                         value
                                     ))
-                                })?;
+                                })?);
                             }
                             _ => {
                                 // Unknown parameter - ignore for forward compatibility
@@ -264,17 +626,19 @@ impl Template {
                     // Boolean flag without value (e.g., "unique")
                     let flag = param_group.trim();
                     if flag == "unique" {
-                        unique = true;
+                        unique = Some(true);
                     }
                 }
             }
 
             // Validate min <= max
-            if min > max {
-                return Err(ParseError::InvalidFormat(format!(
-                    "min ({}) must be <= max ({})",
-                    min, max
-                )));
+            if let (Some(min), Some(max)) = (min, max) {
+                if min > max {
+                    return Err(ParseError::InvalidFormat(format!(
+                        "min ({}) must be <= max ({})",
+                        min, max
+                    )));
+                }
             }
         }
 
@@ -305,10 +669,11 @@ mod tests {
             TemplateToken::Reference {
                 name: "color".to_string(),
                 filter: None,
-                min: 1,
-                max: 1,
+                min: None,
+                max: None,
                 separator: None,
-                unique: false,
+                unique: None,
+                transforms: Vec::new(),
             }
         );
     }
@@ -323,10 +688,11 @@ mod tests {
             TemplateToken::Reference {
                 name: "color".to_string(),
                 filter: None,
-                min: 1,
-                max: 1,
+                min: None,
+                max: None,
                 separator: None,
-                unique: false,
+                unique: None,
+                transforms: Vec::new(),
             }
         );
         assert_eq!(template.tokens[2], TemplateToken::Text(" ".to_string()));
@@ -335,10 +701,11 @@ mod tests {
             TemplateToken::Reference {
                 name: "object".to_string(),
                 filter: None,
-                min: 1,
-                max: 1,
+                min: None,
+                max: None,
                 separator: None,
-                unique: false,
+                unique: None,
+                transforms: Vec::new(),
             }
         );
     }
@@ -352,10 +719,11 @@ mod tests {
             TemplateToken::Reference {
                 name: "test:color".to_string(),
                 filter: None,
-                min: 1,
-                max: 1,
+                min: None,
+                max: None,
                 separator: None,
-                unique: false,
+                unique: None,
+                transforms: Vec::new(),
             }
         );
     }
@@ -396,10 +764,11 @@ mod tests {
             TemplateToken::Reference {
                 name: "color".to_string(),
                 filter: None,
-                min: 1,
-                max: 1,
+                min: None,
+                max: None,
                 separator: None,
-                unique: false,
+                unique: None,
+                transforms: Vec::new(),
             }
         );
     }
@@ -437,10 +806,11 @@ mod tests {
             TemplateToken::Reference {
                 name: "animal".to_string(),
                 filter: Some("tags.can_fly".to_string()),
-                min: 1,
-                max: 1,
+                min: None,
+                max: None,
                 separator: None,
-                unique: false,
+                unique: None,
+                transforms: Vec::new(),
             }
         );
     }
@@ -473,10 +843,11 @@ mod tests {
             TemplateToken::Reference {
                 name: "color".to_string(),
                 filter: None,
-                min: 1,
-                max: 1,
+                min: None,
+                max: None,
                 separator: None,
-                unique: false,
+                unique: None,
+                transforms: Vec::new(),
             }
         );
         assert_eq!(template.tokens[2], TemplateToken::Text(" ".to_string()));
@@ -485,10 +856,11 @@ mod tests {
             TemplateToken::Reference {
                 name: "animal".to_string(),
                 filter: Some("tags.can_fly".to_string()),
-                min: 1,
-                max: 1,
+                min: None,
+                max: None,
                 separator: None,
-                unique: false,
+                unique: None,
+                transforms: Vec::new(),
             }
         );
         assert_eq!(
@@ -508,4 +880,216 @@ mod tests {
             panic!("Expected Reference token");
         }
     }
+
+    #[test]
+    fn test_parse_reference_params() {
+        let template = Template::parse("{colors?min=2,max=3&sep=comma_and&unique}").unwrap();
+        assert_eq!(
+            template.tokens[0],
+            TemplateToken::Reference {
+                name: "colors".to_string(),
+                filter: None,
+                min: Some(2),
+                max: Some(3),
+                separator: Some("comma_and".to_string()),
+                unique: Some(true),
+                transforms: Vec::new(),
+            }
+        );
+
+        assert!(Template::parse("{colors?min=3,max=2}").is_err());
+        assert!(Template::parse("{colors?min=x}").is_err());
+    }
+
+    #[test]
+    fn test_parse_params_with_filter() {
+        let template = Template::parse("{animal#{tags.can_fly}?max=2}").unwrap();
+        if let TemplateToken::Reference {
+            name, filter, max, ..
+        } = &template.tokens[0]
+        {
+            assert_eq!(name, "animal");
+            assert_eq!(filter.as_deref(), Some("tags.can_fly"));
+            assert_eq!(*max, Some(2));
+        } else {
+            panic!("Expected Reference token");
+        }
+    }
+
+    #[test]
+    fn test_effective_references_overrides_yaml() {
+        let mut defined = IndexMap::new();
+        defined.insert(
+            "colors".to_string(),
+            Reference {
+                target: "test:colors".to_string(),
+                filter: Some("tags.warm".to_string()),
+                min: 1,
+                max: 1,
+                separator: None,
+                unique: false,
+                fallback: Fallback::Error,
+            },
+        );
+
+        let template = Template::parse("{colors?min=2,max=3&sep=comma_and&unique}").unwrap();
+        let refs = template.effective_references(&defined, "test");
+        let colors = &refs["colors"];
+        assert_eq!(colors.target, "test:colors");
+        assert_eq!(colors.filter.as_deref(), Some("tags.warm"));
+        assert_eq!((colors.min, colors.max), (2, 3));
+        assert_eq!(colors.separator.as_deref(), Some("comma_and"));
+        assert!(colors.unique);
+
+        // A lone min raises max; an inline filter replaces the YAML one
+        let template = Template::parse("{colors#{tags.cool}?min=4}").unwrap();
+        let colors = &template.effective_references(&defined, "test")["colors"];
+        assert_eq!((colors.min, colors.max), (4, 4));
+        assert_eq!(colors.filter.as_deref(), Some("tags.cool"));
+    }
+
+    #[test]
+    fn test_effective_references_implicit() {
+        let template = Template::parse("{colors?max=2} {other:shapes}").unwrap();
+        let refs = template.effective_references(&IndexMap::new(), "test");
+
+        assert_eq!(refs["colors"].target, "test:colors");
+        assert_eq!((refs["colors"].min, refs["colors"].max), (1, 2));
+        assert_eq!(refs["other:shapes"].target, "other:shapes");
+        assert_eq!((refs["other:shapes"].min, refs["other:shapes"].max), (1, 1));
+    }
+
+    #[test]
+    fn test_parse_conditional_segments() {
+        let template = Template::parse(
+            "A {creature}{?ref:creature.tags.can_fly} with {wings}{?else} on foot{/?}",
+        )
+        .unwrap();
+        assert_eq!(template.tokens.len(), 3);
+
+        match &template.tokens[2] {
+            TemplateToken::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                assert_eq!(
+                    *condition,
+                    Expr::Identifier("ref:creature.tags.can_fly".to_string())
+                );
+                assert_eq!(then.len(), 2);
+                assert_eq!(
+                    otherwise,
+                    &vec![TemplateToken::Text(" on foot".to_string())]
+                );
+            }
+            other => panic!("Expected conditional, got {:?}", other),
+        }
+
+        // References inside segments are still references of the template
+        assert_eq!(template.get_references(), vec!["creature", "wings"]);
+    }
+
+    #[test]
+    fn test_parse_transforms() {
+        let template = Template::parse("{animal#{tags.can_fly}?max=2|plural|capitalize}").unwrap();
+        if let TemplateToken::Reference {
+            name,
+            max,
+            transforms,
+            ..
+        } = &template.tokens[0]
+        {
+            assert_eq!(name, "animal");
+            assert_eq!(*max, Some(2));
+            assert_eq!(
+                transforms,
+                &vec!["plural".to_string(), "capitalize".to_string()]
+            );
+        } else {
+            panic!("Expected Reference token");
+        }
+        assert_eq!(
+            template.get_transforms(),
+            vec![("animal", "plural"), ("animal", "capitalize")]
+        );
+
+        assert!(Template::parse("{animal|}").is_err());
+        assert!(Template::parse("{|upper}").is_err());
+    }
+
+    #[test]
+    fn test_parse_articles() {
+        let template = Template::parse("{A/An} {creature} and {a/an} {item}").unwrap();
+        assert_eq!(
+            template.tokens[0],
+            TemplateToken::Article { capitalized: true }
+        );
+        assert_eq!(
+            template.tokens[4],
+            TemplateToken::Article { capitalized: false }
+        );
+        assert_eq!(template.get_references(), vec!["creature", "item"]);
+
+        assert!(Template::parse("{a/an|upper}").is_err());
+    }
+
+    #[test]
+    fn test_parse_chance_segments() {
+        let template = Template::parse("{%30}a{%50}b{/%}{/%}{%100}c{/%}").unwrap();
+
+        match &template.tokens[..] {
+            [TemplateToken::Chance {
+                percent: 30,
+                index: 0,
+                tokens,
+            }, TemplateToken::Chance {
+                percent: 100,
+                index: 2,
+                ..
+            }] => {
+                assert!(matches!(
+                    tokens[1],
+                    TemplateToken::Chance {
+                        percent: 50,
+                        index: 1,
+                        ..
+                    }
+                ));
+            }
+            other => panic!("Unexpected tokens: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_segment_errors() {
+        assert!(matches!(
+            Template::parse("{?ref:x}open"),
+            Err(ParseError::UnclosedSegment(0))
+        ));
+        assert!(matches!(
+            Template::parse("text{/?}"),
+            Err(ParseError::UnexpectedSegmentTag { pos: 4, .. })
+        ));
+        assert!(matches!(
+            Template::parse("{%30}x{/?}"),
+            Err(ParseError::UnexpectedSegmentTag { .. })
+        ));
+        assert!(matches!(
+            Template::parse("{?a}x{?else}y{?else}z{/?}"),
+            Err(ParseError::UnexpectedSegmentTag { .. })
+        ));
+        assert!(matches!(
+            Template::parse("{?}x{/?}"),
+            Err(ParseError::InvalidCondition { .. })
+        ));
+        assert!(matches!(
+            Template::parse("{?ref:x ==}x{/?}"),
+            Err(ParseError::InvalidCondition { .. })
+        ));
+        assert!(matches!(
+            Template::parse("{%150}x{/%}"),
+            Err(ParseError::InvalidFormat(_))
+        ));
+    }
 }
@@ -0,0 +1,92 @@
+// Render Trace
+// Structured record of how a prompt was produced (opt-in via Renderer::with_trace)
+//
+// The trace is a tree: each promptsection records its references (with any
+// nested promptsection's own trace), the rules that were considered and the
+// context writes made while enriching it.
+
+use crate::core::Fallback;
+use crate::rules::processor::RuleOutcome;
+use indexmap::IndexMap;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+/// Trace of a single promptsection render
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SectionTrace {
+    /// The promptsection reference as requested (e.g. "test:scene")
+    pub promptsection: String,
+
+    /// Seed used for this promptsection (nested sections use derived seeds)
+    pub seed: u64,
+
+    /// References in selection order
+    pub references: Vec<ReferenceTrace>,
+
+    /// Rules considered during enrichment, in execution order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<RuleTrace>,
+
+    /// Context values written (rulebook defaults, rules, decisions)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub context_writes: Vec<ContextWrite>,
+
+    /// Rendered output of this promptsection
+    pub output: String,
+}
+
+/// Trace of one reference's selection
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferenceTrace {
+    pub name: String,
+    pub target: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+
+    /// Number of values left after filtering (1 for nested promptsections)
+    pub candidates: usize,
+
+    /// The chosen value(s), in selection order
+    pub chosen: Vec<ChosenValue>,
+
+    /// Fallback applied because the filter matched no values
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<Fallback>,
+
+    /// Trace of the nested promptsection, if the target is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub section: Option<Box<SectionTrace>>,
+}
+
+/// A selected value as seen in the trace
+#[derive(Debug, Clone, Serialize)]
+pub struct ChosenValue {
+    pub text: String,
+
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    pub tags: IndexMap<String, JsonValue>,
+
+    pub weight: f32,
+}
+
+/// What happened to a rule during enrichment
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleTrace {
+    /// Qualified rule id ("namespace:rule_id")
+    pub rule: String,
+
+    #[serde(flatten)]
+    pub outcome: RuleOutcome,
+}
+
+/// A single context write
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextWrite {
+    /// Context key as written (e.g. "article" or "global:season")
+    pub key: String,
+    pub value: String,
+
+    /// What wrote it: "rulebook", "rule namespace:id" or "decision name"
+    pub source: String,
+}
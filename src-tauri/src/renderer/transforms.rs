@@ -0,0 +1,216 @@
+// Text Transforms
+// Named text transforms applied to rendered references: {creature|capitalize}
+//
+// Transforms run left to right on the reference's rendered text (after list
+// values are joined): "{adj|a_an|capitalize}" -> "An old". The renderer starts
+// with the built-ins below; embedders can register more by name.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// A text transform
+pub type TransformFn = fn(&str) -> String;
+
+/// Built-in transforms, available to every package
+const BUILTINS: &[(&str, TransformFn)] = &[
+    ("capitalize", capitalize),
+    ("upper", upper),
+    ("lower", lower),
+    ("plural", plural),
+    ("a_an", a_an),
+];
+
+/// Registry of named transforms
+#[derive(Clone)]
+pub struct TransformRegistry {
+    transforms: HashMap<String, TransformFn>,
+}
+
+impl Default for TransformRegistry {
+    fn default() -> Self {
+        TransformRegistry {
+            transforms: BUILTINS
+                .iter()
+                .map(|(name, transform)| (name.to_string(), *transform))
+                .collect(),
+        }
+    }
+}
+
+impl TransformRegistry {
+    /// Shared registry of the built-in transforms
+    pub fn builtin() -> &'static TransformRegistry {
+        static BUILTIN: OnceLock<TransformRegistry> = OnceLock::new();
+        BUILTIN.get_or_init(TransformRegistry::default)
+    }
+
+    /// Register (or replace) a transform
+    #[allow(dead_code)] // Part of public API
+    pub fn register(&mut self, name: &str, transform: TransformFn) {
+        self.transforms.insert(name.to_string(), transform);
+    }
+
+    /// Apply a transform by name; None if it isn't registered
+    pub fn apply(&self, name: &str, text: &str) -> Option<String> {
+        self.transforms.get(name).map(|transform| transform(text))
+    }
+
+    /// Whether a transform of this name is registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.transforms.contains_key(name)
+    }
+
+    /// Registered transform names, sorted
+    #[allow(dead_code)] // Used by the Tauri validation command
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.transforms.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+}
+
+/// "red dragon" -> "Red dragon"
+pub fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn upper(text: &str) -> String {
+    text.to_uppercase()
+}
+
+fn lower(text: &str) -> String {
+    text.to_lowercase()
+}
+
+/// English plural of the last word: "box" -> "boxes", "city" -> "cities"
+fn plural(text: &str) -> String {
+    let lower = text.to_lowercase();
+    if lower.is_empty() {
+        return String::new();
+    }
+
+    if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|suffix| lower.ends_with(suffix))
+    {
+        return format!("{}es", text);
+    }
+
+    let mut tail = lower.chars().rev();
+    if let (Some('y'), Some(before)) = (tail.next(), tail.next()) {
+        if !"aeiou".contains(before) {
+            return format!("{}ies", &text[..text.len() - 1]);
+        }
+    }
+
+    format!("{}s", text)
+}
+
+/// Prefix the indefinite article: "apple" -> "an apple", "unicorn" -> "a unicorn"
+fn a_an(text: &str) -> String {
+    if text.trim().is_empty() {
+        return text.to_string();
+    }
+    format!("{} {}", indefinite_article(text), text)
+}
+
+/// Indefinite article for the first word of `text` ("a" or "an")
+///
+/// Goes by sound where spelling misleads: "an hour", "a unicorn", "a one-off".
+pub fn indefinite_article(text: &str) -> &'static str {
+    let word: String = text
+        .trim_start_matches(|c: char| !c.is_alphanumeric())
+        .chars()
+        .take_while(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    const VOWEL_SOUND: &[&str] = &["hour", "honest", "honor", "honour", "heir"];
+    const CONSONANT_SOUND: &[&str] = &[
+        "unic", "unif", "unio", "uniq", "unit", "univ", "use", "usu", "uti", "eu", "one", "once",
+    ];
+
+    if VOWEL_SOUND.iter().any(|prefix| word.starts_with(prefix)) {
+        return "an";
+    }
+    if CONSONANT_SOUND
+        .iter()
+        .any(|prefix| word.starts_with(prefix))
+    {
+        return "a";
+    }
+
+    // Numbers go by how they are read: "an 8", "an 11th", "an 18000"
+    let digits: String = word.chars().take_while(char::is_ascii_digit).collect();
+    if !digits.is_empty() {
+        let eleven_or_eighteen = digits.starts_with("11") || digits.starts_with("18");
+        return if digits.starts_with('8') || (eleven_or_eighteen && digits.len() % 3 == 2) {
+            "an"
+        } else {
+            "a"
+        };
+    }
+
+    match word.chars().next() {
+        Some('a' | 'e' | 'i' | 'o' | 'u') => "an",
+        _ => "a",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtins() {
+        let registry = TransformRegistry::default();
+        let apply = |name, text| registry.apply(name, text).unwrap();
+
+        assert_eq!(apply("capitalize", "red dragon"), "Red dragon");
+        assert_eq!(apply("upper", "red"), "RED");
+        assert_eq!(apply("lower", "RED"), "red");
+        assert_eq!(apply("plural", "dragon"), "dragons");
+        assert_eq!(apply("plural", "box"), "boxes");
+        assert_eq!(apply("plural", "city"), "cities");
+        assert_eq!(apply("plural", "day"), "days");
+        assert_eq!(apply("a_an", "apple"), "an apple");
+        assert_eq!(apply("a_an", "red apple"), "a red apple");
+        assert_eq!(apply("a_an", ""), "");
+        assert!(registry.apply("missing", "x").is_none());
+    }
+
+    #[test]
+    fn test_indefinite_article() {
+        for word in [
+            "apple",
+            "Elf",
+            "hour",
+            "honest knight",
+            "8-bit",
+            "11th",
+            "18000",
+        ] {
+            assert_eq!(indefinite_article(word), "an", "{}", word);
+        }
+        assert_eq!(indefinite_article("uninvited guest"), "an");
+        assert_eq!(indefinite_article("\"owl\""), "an");
+        for word in [
+            "dragon", "unicorn", "uniform", "user", "European", "one-eyed", "1", "1100", "hat",
+        ] {
+            assert_eq!(indefinite_article(word), "a", "{}", word);
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        let mut registry = TransformRegistry::default();
+        registry.register("reverse", |text| text.chars().rev().collect());
+
+        assert_eq!(registry.apply("reverse", "abc").as_deref(), Some("cba"));
+        assert!(registry.names().contains(&"reverse"));
+    }
+}
@@ -0,0 +1,523 @@
+// Decision Processor
+// Executes author-defined decisions (M1 Pattern 3) after rules during enrichment
+//
+// A decision binds typed inputs from selected references and context,
+// runs its processor, and writes typed outputs back into the context.
+
+use crate::context::Context;
+use crate::core::models::{Decision, Processor};
+use crate::renderer::selector::SelectedValue;
+use crate::rules::expression::{Expr, Value};
+use crate::rules::processor::{context_key_for, lookup_path, Result, RuleError};
+use std::collections::HashMap;
+
+/// Declared type of a decision input or output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamType {
+    Text,
+    Number,
+    Boolean,
+    List,
+    Any,
+}
+
+/// Parse a declared type such as "text", "number" or "boolean?"
+///
+/// Returns the type and whether it is optional (trailing `?`).
+pub fn parse_param_type(declared: &str) -> Option<(ParamType, bool)> {
+    let declared = declared.trim();
+    let (name, optional) = match declared.strip_suffix('?') {
+        Some(name) => (name.trim(), true),
+        None => (declared, false),
+    };
+
+    let param_type = match name.to_lowercase().as_str() {
+        "text" | "string" => ParamType::Text,
+        "number" | "int" | "integer" | "float" => ParamType::Number,
+        "boolean" | "bool" => ParamType::Boolean,
+        "list" | "array" => ParamType::List,
+        "any" | "" => ParamType::Any,
+        _ => return None,
+    };
+
+    Some((param_type, optional))
+}
+
+impl ParamType {
+    /// Coerce a value to this type (None if it cannot be represented)
+    pub fn coerce(&self, value: Value) -> Option<Value> {
+        if value == Value::Null {
+            return None;
+        }
+
+        match self {
+            ParamType::Any => Some(value),
+            ParamType::Text => Some(Value::Text(value.to_string())),
+            ParamType::Number => value.as_number().map(Value::Number),
+            ParamType::Boolean => match &value {
+                Value::Text(s) if s.eq_ignore_ascii_case("false") => Some(Value::Bool(false)),
+                Value::Text(s) if s.eq_ignore_ascii_case("true") => Some(Value::Bool(true)),
+                _ => Some(Value::Bool(value.is_truthy())),
+            },
+            ParamType::List => match value {
+                Value::List(_) => Some(value),
+                other => Some(Value::List(vec![other])),
+            },
+        }
+    }
+}
+
+/// Decision processor - executes decisions during Phase 2 (Enrichment)
+pub struct DecisionProcessor<'a> {
+    context: &'a mut Context,
+    selected: &'a HashMap<String, Vec<SelectedValue>>,
+}
+
+impl<'a> DecisionProcessor<'a> {
+    /// Create a new decision processor
+    pub fn new(
+        context: &'a mut Context,
+        selected: &'a HashMap<String, Vec<SelectedValue>>,
+    ) -> Self {
+        DecisionProcessor { context, selected }
+    }
+
+    /// Execute decisions in declaration order
+    ///
+    /// Later decisions can read outputs of earlier ones from the context.
+    /// Returns (decision name, context key) for every output written.
+    pub fn execute_decisions(&mut self, decisions: &[Decision]) -> Result<Vec<(String, String)>> {
+        let mut written = Vec::new();
+        for decision in decisions {
+            for key in self.execute_decision(decision)? {
+                written.push((decision.name.clone(), key));
+            }
+        }
+        Ok(written)
+    }
+
+    /// Execute a single decision
+    ///
+    /// A decision whose required inputs cannot be bound is skipped,
+    /// just like a rule whose reference was not selected.
+    fn execute_decision(&mut self, decision: &Decision) -> Result<Vec<String>> {
+        let inputs = match self.bind_inputs(decision)? {
+            Some(inputs) => inputs,
+            None => return Ok(Vec::new()),
+        };
+
+        let lookup = |name: &str| -> Option<Value> {
+            inputs
+                .get(name)
+                .cloned()
+                .or_else(|| lookup_path(name, self.context, self.selected))
+        };
+
+        let outputs: Vec<(String, Value)> = match &decision.processor {
+            Processor::Expression { formula } => {
+                let expr = parse(decision, formula)?;
+                let output_name = match decision.outputs.keys().next() {
+                    Some(name) if decision.outputs.len() == 1 => name.clone(),
+                    _ => {
+                        return Err(RuleError::InvalidDecision {
+                            decision: decision.name.clone(),
+                            reason: "expression processor requires exactly one output".to_string(),
+                        })
+                    }
+                };
+                vec![(output_name, expr.evaluate(&lookup))]
+            }
+            Processor::RuleSet { rules } => {
+                let mut matched = Vec::new();
+                for rule in rules {
+                    let condition = rule.condition.trim();
+                    if !condition.is_empty()
+                        && !parse(decision, condition)?.evaluate(&lookup).is_truthy()
+                    {
+                        continue;
+                    }
+
+                    for (name, value) in &rule.output {
+                        if !decision.outputs.contains_key(name) {
+                            return Err(RuleError::InvalidDecision {
+                                decision: decision.name.clone(),
+                                reason: format!("rule output '{}' is not a declared output", name),
+                            });
+                        }
+                        let value = match value {
+                            serde_json::Value::String(s) => Value::Text(interpolate(s, &inputs)),
+                            other => Value::from(other),
+                        };
+                        matched.push((name.clone(), value));
+                    }
+                    break;
+                }
+                matched
+            }
+            Processor::Script { language, .. } => {
+                return Err(RuleError::InvalidDecision {
+                    decision: decision.name.clone(),
+                    reason: format!("script processors ({}) are not supported", language),
+                })
+            }
+        };
+
+        let mut written = Vec::new();
+        for (name, value) in outputs {
+            if let Some(key) = self.write_output(decision, &name, value)? {
+                written.push(key);
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// Bind declared inputs (None if a required input is unavailable)
+    ///
+    /// An input is bound from `bindings` when present (any expression, e.g.
+    /// "ref:creature.tags.size"), otherwise from the selected reference with
+    /// the same name, otherwise from the context key with the same name.
+    fn bind_inputs(&self, decision: &Decision) -> Result<Option<HashMap<String, Value>>> {
+        let mut inputs = HashMap::new();
+
+        for (name, declared) in &decision.inputs {
+            let (param_type, optional) = param_type(decision, declared)?;
+
+            let raw = match decision.bindings.get(name) {
+                Some(source) => {
+                    let expr = parse(decision, source)?;
+                    expr.evaluate(&|path| lookup_path(path, self.context, self.selected))
+                }
+                None => self
+                    .selected
+                    .get(name)
+                    .and_then(|values| match param_type {
+                        // List inputs bind every selected value of a multi-select reference
+                        ParamType::List => Some(Value::List(
+                            values.iter().map(|v| Value::Text(v.text.clone())).collect(),
+                        )),
+                        _ => values.first().map(|v| Value::Text(v.text.clone())),
+                    })
+                    .or_else(|| self.context.get(name).ok().map(Value::from))
+                    .unwrap_or(Value::Null),
+            };
+
+            match param_type.coerce(raw) {
+                Some(value) => {
+                    inputs.insert(name.clone(), value);
+                }
+                None if optional => {
+                    inputs.insert(name.clone(), Value::Null);
+                }
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(inputs))
+    }
+
+    /// Write a typed output to the context (first contribution wins)
+    ///
+    /// Returns the context key if a value was written.
+    fn write_output(
+        &mut self,
+        decision: &Decision,
+        name: &str,
+        value: Value,
+    ) -> Result<Option<String>> {
+        let declared = decision
+            .outputs
+            .get(name)
+            .map(String::as_str)
+            .unwrap_or("any");
+        let (param_type, _) = param_type(decision, declared)?;
+
+        let key = context_key_for(name);
+        if self.context.has(&key) {
+            return Ok(None);
+        }
+
+        // Null (or uncoercible) output means "no contribution"
+        match param_type.coerce(value).and_then(|v| v.to_context_value()) {
+            Some(value) => {
+                self.context.set(&key, value)?;
+                Ok(Some(key))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+fn parse(decision: &Decision, source: &str) -> Result<Expr> {
+    Expr::parse(source).map_err(|e| RuleError::EvaluationError {
+        expr: source.to_string(),
+        reason: format!("{} (in decision '{}')", e, decision.name),
+    })
+}
+
+fn param_type(decision: &Decision, declared: &str) -> Result<(ParamType, bool)> {
+    parse_param_type(declared).ok_or_else(|| RuleError::InvalidDecision {
+        decision: decision.name.clone(),
+        reason: format!("unknown type '{}'", declared),
+    })
+}
+
+/// Replace `{input}` placeholders in rule-set output text
+fn interpolate(text: &str, inputs: &HashMap<String, Value>) -> String {
+    let mut result = text.to_string();
+    for (name, value) in inputs {
+        result = result.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::models::ConditionalRule;
+    use indexmap::IndexMap;
+    use serde_json::json;
+
+    fn create_test_selected() -> HashMap<String, Vec<SelectedValue>> {
+        let mut selected = HashMap::new();
+
+        let mut tags = IndexMap::new();
+        tags.insert("size".to_string(), json!(3));
+        tags.insert("article".to_string(), json!("a"));
+
+        selected.insert(
+            "creature".to_string(),
+            vec![SelectedValue {
+                text: "dragon".to_string(),
+                tags,
+            }],
+        );
+
+        selected
+    }
+
+    fn decision(
+        inputs: &[(&str, &str)],
+        outputs: &[(&str, &str)],
+        bindings: &[(&str, &str)],
+        processor: Processor,
+    ) -> Decision {
+        let to_map = |pairs: &[(&str, &str)]| -> IndexMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        Decision {
+            name: "test:decision".to_string(),
+            inputs: to_map(inputs),
+            outputs: to_map(outputs),
+            bindings: to_map(bindings),
+            processor,
+        }
+    }
+
+    #[test]
+    fn test_expression_decision() {
+        let selected = create_test_selected();
+        let mut ctx = Context::new();
+
+        let d = decision(
+            &[("size", "number")],
+            &[("scale", "text")],
+            &[("size", "ref:creature.tags.size")],
+            Processor::Expression {
+                formula: "size > 2 ? \"huge\" : \"small\"".to_string(),
+            },
+        );
+
+        DecisionProcessor::new(&mut ctx, &selected)
+            .execute_decisions(&[d])
+            .unwrap();
+
+        assert_eq!(ctx.get_text("scale").unwrap(), "huge");
+    }
+
+    #[test]
+    fn test_input_bound_by_name() {
+        let selected = create_test_selected();
+        let mut ctx = Context::new();
+
+        let d = decision(
+            &[("creature", "text")],
+            &[("title", "text")],
+            &[],
+            Processor::Expression {
+                formula: "\"the \" + creature".to_string(),
+            },
+        );
+
+        DecisionProcessor::new(&mut ctx, &selected)
+            .execute_decisions(&[d])
+            .unwrap();
+
+        assert_eq!(ctx.get_text("title").unwrap(), "the dragon");
+    }
+
+    #[test]
+    fn test_input_bound_from_context() {
+        let selected = create_test_selected();
+        let mut ctx = Context::new();
+        ctx.set("mood", "dark").unwrap();
+
+        let d = decision(
+            &[("mood", "text")],
+            &[("lighting", "text")],
+            &[],
+            Processor::RuleSet {
+                rules: vec![
+                    ConditionalRule {
+                        condition: "mood == \"dark\"".to_string(),
+                        output: IndexMap::from([("lighting".to_string(), json!("dim {mood}"))]),
+                    },
+                    ConditionalRule {
+                        condition: String::new(),
+                        output: IndexMap::from([("lighting".to_string(), json!("bright"))]),
+                    },
+                ],
+            },
+        );
+
+        DecisionProcessor::new(&mut ctx, &selected)
+            .execute_decisions(&[d])
+            .unwrap();
+
+        assert_eq!(ctx.get_text("lighting").unwrap(), "dim dark");
+    }
+
+    #[test]
+    fn test_rule_set_default_branch_and_typed_output() {
+        let selected = create_test_selected();
+        let mut ctx = Context::new();
+
+        let d = decision(
+            &[("mood", "text?")],
+            &[("intensity", "number")],
+            &[],
+            Processor::RuleSet {
+                rules: vec![
+                    ConditionalRule {
+                        condition: "mood == \"dark\"".to_string(),
+                        output: IndexMap::from([("intensity".to_string(), json!(9))]),
+                    },
+                    ConditionalRule {
+                        condition: String::new(),
+                        output: IndexMap::from([("intensity".to_string(), json!("2"))]),
+                    },
+                ],
+            },
+        );
+
+        DecisionProcessor::new(&mut ctx, &selected)
+            .execute_decisions(&[d])
+            .unwrap();
+
+        assert_eq!(ctx.get_number("intensity").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_missing_required_input_skips_decision() {
+        let selected = create_test_selected();
+        let mut ctx = Context::new();
+
+        let d = decision(
+            &[("weather", "text")],
+            &[("sky", "text")],
+            &[],
+            Processor::Expression {
+                formula: "weather".to_string(),
+            },
+        );
+
+        DecisionProcessor::new(&mut ctx, &selected)
+            .execute_decisions(&[d])
+            .unwrap();
+
+        assert!(!ctx.has("sky"));
+    }
+
+    #[test]
+    fn test_first_contribution_wins() {
+        let selected = create_test_selected();
+        let mut ctx = Context::new();
+        ctx.set("article", "an").unwrap();
+
+        let d = decision(
+            &[],
+            &[("article", "text")],
+            &[],
+            Processor::Expression {
+                formula: "ref:creature.tags.article".to_string(),
+            },
+        );
+
+        DecisionProcessor::new(&mut ctx, &selected)
+            .execute_decisions(&[d])
+            .unwrap();
+
+        assert_eq!(ctx.get_text("article").unwrap(), "an");
+    }
+
+    #[test]
+    fn test_chained_decisions() {
+        let selected = create_test_selected();
+        let mut ctx = Context::new();
+
+        let first = decision(
+            &[],
+            &[("context.global.size", "number")],
+            &[],
+            Processor::Expression {
+                formula: "ref:creature.tags.size * 2".to_string(),
+            },
+        );
+        let second = decision(
+            &[],
+            &[("big", "boolean")],
+            &[],
+            Processor::Expression {
+                formula: "context.global.size >= 6".to_string(),
+            },
+        );
+
+        DecisionProcessor::new(&mut ctx, &selected)
+            .execute_decisions(&[first, second])
+            .unwrap();
+
+        assert_eq!(ctx.get_number("global:size").unwrap(), 6);
+        assert!(ctx.get_boolean("big").unwrap());
+    }
+
+    #[test]
+    fn test_script_processor_unsupported() {
+        let selected = create_test_selected();
+        let mut ctx = Context::new();
+
+        let d = decision(
+            &[],
+            &[("x", "text")],
+            &[],
+            Processor::Script {
+                language: "lua".to_string(),
+                code: "return 1".to_string(),
+            },
+        );
+
+        let result = DecisionProcessor::new(&mut ctx, &selected).execute_decisions(&[d]);
+        assert!(matches!(result, Err(RuleError::InvalidDecision { .. })));
+    }
+
+    #[test]
+    fn test_parse_param_type() {
+        assert_eq!(parse_param_type("text"), Some((ParamType::Text, false)));
+        assert_eq!(parse_param_type("number?"), Some((ParamType::Number, true)));
+        assert_eq!(parse_param_type("bool"), Some((ParamType::Boolean, false)));
+        assert_eq!(parse_param_type("widget"), None);
+    }
+}
@@ -0,0 +1,711 @@
+// Expression Language
+// Small expression language shared by decisions and rule conditions
+//
+// Supports:
+// - Literals: "text", 'text', 42, 3.5, true, false, null, ["a", "b"]
+// - Identifiers: count, ref:creature.tags.size, context.prompt.mood
+// - Comparison: ==, !=, <, <=, >, >=, in, not in
+// - Logic: && / and, || / or, ! / not
+// - Arithmetic: +, -, *, / (+ concatenates when either side is text)
+// - Conditional: condition ? then : otherwise
+
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ExpressionError {
+    #[error("Unexpected end of expression")]
+    UnexpectedEnd,
+
+    #[error("Unexpected token '{0}'")]
+    UnexpectedToken(String),
+
+    #[error("Unterminated string literal")]
+    UnterminatedString,
+
+    #[error("Invalid character '{0}'")]
+    InvalidCharacter(char),
+}
+
+pub type Result<T> = std::result::Result<T, ExpressionError>;
+
+/// Runtime value produced by evaluating an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Text(String),
+    List(Vec<Value>),
+}
+
+impl Value {
+    /// Truthiness used by conditions and logical operators
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0.0,
+            Value::Text(s) => !s.is_empty(),
+            Value::List(items) => !items.is_empty(),
+        }
+    }
+
+    /// Numeric view of the value, parsing text when possible
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Text(s) => s.trim().parse().ok(),
+            Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            _ => None,
+        }
+    }
+
+    /// Loose equality: numbers compare numerically, everything else by text
+    pub fn loose_eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Null, _) | (_, Value::Null) => false,
+            (Value::List(a), Value::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.loose_eq(y))
+            }
+            (Value::Number(_), _) | (_, Value::Number(_)) => {
+                match (self.as_number(), other.as_number()) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => false,
+                }
+            }
+            _ => self.to_string() == other.to_string(),
+        }
+    }
+
+    /// Membership: `needle` is an element of this list, or a substring of this text
+    pub fn contains(&self, needle: &Value) -> bool {
+        match self {
+            Value::List(items) => items.iter().any(|item| item.loose_eq(needle)),
+            Value::Text(s) => !matches!(needle, Value::Null) && s.contains(&needle.to_string()),
+            _ => false,
+        }
+    }
+
+    /// Ordering: numeric when both sides are numeric, otherwise lexicographic
+    pub fn compare(&self, other: &Value) -> Option<std::cmp::Ordering> {
+        if matches!(self, Value::Null | Value::List(_))
+            || matches!(other, Value::Null | Value::List(_))
+        {
+            return None;
+        }
+        match (self.as_number(), other.as_number()) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => Some(self.to_string().cmp(&other.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => Ok(()),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => {
+                if n.fract() == 0.0 && n.abs() < 1e15 {
+                    write!(f, "{}", *n as i64)
+                } else {
+                    write!(f, "{}", n)
+                }
+            }
+            Value::Text(s) => write!(f, "{}", s),
+            Value::List(items) => {
+                let parts: Vec<String> = items.iter().map(|v| v.to_string()).collect();
+                write!(f, "{}", parts.join(", "))
+            }
+        }
+    }
+}
+
+impl From<&serde_json::Value> for Value {
+    fn from(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(*b),
+            serde_json::Value::Number(n) => Value::Number(n.as_f64().unwrap_or(0.0)),
+            serde_json::Value::String(s) => Value::Text(s.clone()),
+            serde_json::Value::Array(items) => Value::List(items.iter().map(Value::from).collect()),
+            serde_json::Value::Object(_) => Value::Text(value.to_string()),
+        }
+    }
+}
+
+impl From<&crate::context::ContextValue> for Value {
+    fn from(value: &crate::context::ContextValue) -> Self {
+        use crate::context::ContextValue;
+        match value {
+            ContextValue::Text(s) => Value::Text(s.clone()),
+            ContextValue::Number(n) => Value::Number(*n as f64),
+            ContextValue::Boolean(b) => Value::Bool(*b),
+            ContextValue::List(items) => {
+                Value::List(items.iter().map(|s| Value::Text(s.clone())).collect())
+            }
+        }
+    }
+}
+
+impl Value {
+    /// Convert into a context value; null has no context representation
+    pub fn to_context_value(&self) -> Option<crate::context::ContextValue> {
+        use crate::context::ContextValue;
+        match self {
+            Value::Null => None,
+            Value::Bool(b) => Some(ContextValue::Boolean(*b)),
+            Value::Number(n) => {
+                if n.fract() == 0.0 && *n >= i32::MIN as f64 && *n <= i32::MAX as f64 {
+                    Some(ContextValue::Number(*n as i32))
+                } else {
+                    Some(ContextValue::Text(n.to_string()))
+                }
+            }
+            Value::Text(s) => Some(ContextValue::Text(s.clone())),
+            Value::List(items) => Some(ContextValue::List(
+                items.iter().map(|v| v.to_string()).collect(),
+            )),
+        }
+    }
+}
+
+/// Binary operators
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    In,
+    NotIn,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+/// A parsed expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    List(Vec<Expr>),
+
+    /// Identifier resolved at evaluation time (input name, ref: path, context key)
+    Identifier(String),
+
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Parse an expression string
+    pub fn parse(input: &str) -> Result<Expr> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let expr = parser.parse_conditional()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(ExpressionError::UnexpectedToken(token.to_string())),
+        }
+    }
+
+    /// Evaluate the expression, resolving identifiers through `lookup`
+    ///
+    /// Unknown identifiers resolve to null rather than failing, so conditions
+    /// over optional values read naturally (`mood == "dark"` is false when unset).
+    pub fn evaluate(&self, lookup: &dyn Fn(&str) -> Option<Value>) -> Value {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::List(items) => Value::List(items.iter().map(|e| e.evaluate(lookup)).collect()),
+            Expr::Identifier(name) => lookup(name).unwrap_or(Value::Null),
+            Expr::Not(inner) => Value::Bool(!inner.evaluate(lookup).is_truthy()),
+            Expr::Negate(inner) => match inner.evaluate(lookup).as_number() {
+                Some(n) => Value::Number(-n),
+                None => Value::Null,
+            },
+            Expr::And(left, right) => {
+                Value::Bool(left.evaluate(lookup).is_truthy() && right.evaluate(lookup).is_truthy())
+            }
+            Expr::Or(left, right) => {
+                Value::Bool(left.evaluate(lookup).is_truthy() || right.evaluate(lookup).is_truthy())
+            }
+            Expr::Conditional(condition, then, otherwise) => {
+                if condition.evaluate(lookup).is_truthy() {
+                    then.evaluate(lookup)
+                } else {
+                    otherwise.evaluate(lookup)
+                }
+            }
+            Expr::Binary(left, op, right) => {
+                let left = left.evaluate(lookup);
+                let right = right.evaluate(lookup);
+                evaluate_binary(&left, *op, &right)
+            }
+        }
+    }
+}
+
+fn evaluate_binary(left: &Value, op: BinaryOp, right: &Value) -> Value {
+    use std::cmp::Ordering;
+
+    match op {
+        BinaryOp::Equal => Value::Bool(left.loose_eq(right)),
+        BinaryOp::NotEqual => Value::Bool(!left.loose_eq(right)),
+        BinaryOp::Less => Value::Bool(left.compare(right) == Some(Ordering::Less)),
+        BinaryOp::LessEqual => Value::Bool(matches!(
+            left.compare(right),
+            Some(Ordering::Less | Ordering::Equal)
+        )),
+        BinaryOp::Greater => Value::Bool(left.compare(right) == Some(Ordering::Greater)),
+        BinaryOp::GreaterEqual => Value::Bool(matches!(
+            left.compare(right),
+            Some(Ordering::Greater | Ordering::Equal)
+        )),
+        BinaryOp::In => Value::Bool(contains(right, left)),
+        BinaryOp::NotIn => Value::Bool(!contains(right, left)),
+        BinaryOp::Add => match (left, right) {
+            (Value::Text(_), _) | (_, Value::Text(_)) => Value::Text(format!("{}{}", left, right)),
+            _ => arithmetic(left, right, |a, b| Some(a + b)),
+        },
+        BinaryOp::Subtract => arithmetic(left, right, |a, b| Some(a - b)),
+        BinaryOp::Multiply => arithmetic(left, right, |a, b| Some(a * b)),
+        BinaryOp::Divide => arithmetic(left, right, |a, b| (b != 0.0).then(|| a / b)),
+    }
+}
+
+fn arithmetic(left: &Value, right: &Value, f: impl Fn(f64, f64) -> Option<f64>) -> Value {
+    match (left.as_number(), right.as_number()) {
+        (Some(a), Some(b)) => f(a, b).map(Value::Number).unwrap_or(Value::Null),
+        _ => Value::Null,
+    }
+}
+
+/// Membership: element of a list, or substring of a text
+fn contains(haystack: &Value, needle: &Value) -> bool {
+    haystack.contains(needle)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Text(String),
+    Number(f64),
+    Word(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Text(s) => write!(f, "\"{}\"", s),
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Word(w) => write!(f, "{}", w),
+            Token::Symbol(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// Characters allowed inside identifiers (after the first character)
+fn is_identifier_char(ch: char) -> bool {
+    ch.is_alphanumeric() || matches!(ch, '_' | '.' | ':' | '[' | ']')
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    const SYMBOLS: [&str; 19] = [
+        "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "(", ")", "[", "]", ",", "+", "-", "*",
+        "/", "?",
+    ];
+
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = input.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let ch = chars[i];
+
+        if ch.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        // String literals
+        if ch == '"' || ch == '\'' {
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(ExpressionError::UnterminatedString),
+                    Some(&c) if c == ch => break,
+                    Some('\\') if chars.get(i + 1).is_some() => {
+                        value.push(chars[i + 1]);
+                        i += 2;
+                        continue;
+                    }
+                    Some(&c) => value.push(c),
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push(Token::Text(value));
+            continue;
+        }
+
+        // Numbers
+        if ch.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let literal: String = chars[start..i].iter().collect();
+            let number = literal
+                .parse()
+                .map_err(|_| ExpressionError::UnexpectedToken(literal.clone()))?;
+            tokens.push(Token::Number(number));
+            continue;
+        }
+
+        // Identifiers and keywords
+        if ch.is_alphabetic() || ch == '_' {
+            let start = i;
+            while i < chars.len() && is_identifier_char(chars[i]) {
+                // Index brackets are consumed whole so "ref:colors[*].text" stays one word
+                if chars[i] == '[' {
+                    while i < chars.len() && chars[i] != ']' {
+                        i += 1;
+                    }
+                }
+                i += 1;
+            }
+            i = i.min(chars.len());
+            tokens.push(Token::Word(chars[start..i].iter().collect()));
+            continue;
+        }
+
+        // The ternary ':' never appears inside identifiers (those consume their own ':')
+        if ch == ':' {
+            tokens.push(Token::Symbol(":"));
+            i += 1;
+            continue;
+        }
+
+        let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+        match SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+            Some(symbol) => {
+                tokens.push(Token::Symbol(symbol));
+                i += symbol.len();
+            }
+            None => return Err(ExpressionError::InvalidCharacter(ch)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        if token.is_some() {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn peek_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+
+    fn peek_word(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w == word)
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<()> {
+        match self.next() {
+            Some(Token::Symbol(s)) if s == symbol => Ok(()),
+            Some(other) => Err(ExpressionError::UnexpectedToken(other.to_string())),
+            None => Err(ExpressionError::UnexpectedEnd),
+        }
+    }
+
+    /// condition ? then : otherwise (lowest precedence, right-associative)
+    fn parse_conditional(&mut self) -> Result<Expr> {
+        let condition = self.parse_or()?;
+        if self.peek_symbol("?") {
+            self.next();
+            let then = self.parse_conditional()?;
+            self.expect_symbol(":")?;
+            let otherwise = self.parse_conditional()?;
+            return Ok(Expr::Conditional(
+                Box::new(condition),
+                Box::new(then),
+                Box::new(otherwise),
+            ));
+        }
+        Ok(condition)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut left = self.parse_and()?;
+        while self.peek_symbol("||") || self.peek_word("or") {
+            self.next();
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut left = self.parse_not()?;
+        while self.peek_symbol("&&") || self.peek_word("and") {
+            self.next();
+            let right = self.parse_not()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr> {
+        if self.peek_symbol("!") || self.peek_word("not") {
+            self.next();
+            let inner = self.parse_not()?;
+            return Ok(Expr::Not(Box::new(inner)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr> {
+        let left = self.parse_additive()?;
+
+        let op = match self.peek() {
+            Some(Token::Symbol("==")) => BinaryOp::Equal,
+            Some(Token::Symbol("!=")) => BinaryOp::NotEqual,
+            Some(Token::Symbol("<")) => BinaryOp::Less,
+            Some(Token::Symbol("<=")) => BinaryOp::LessEqual,
+            Some(Token::Symbol(">")) => BinaryOp::Greater,
+            Some(Token::Symbol(">=")) => BinaryOp::GreaterEqual,
+            Some(Token::Word(w)) if w == "in" => BinaryOp::In,
+            Some(Token::Word(w)) if w == "not" => {
+                // "not in" - only valid directly after an operand
+                if matches!(self.tokens.get(self.position + 1), Some(Token::Word(w)) if w == "in") {
+                    self.next();
+                    BinaryOp::NotIn
+                } else {
+                    return Ok(left);
+                }
+            }
+            _ => return Ok(left),
+        };
+        self.next();
+
+        let right = self.parse_additive()?;
+        Ok(Expr::Binary(Box::new(left), op, Box::new(right)))
+    }
+
+    fn parse_additive(&mut self) -> Result<Expr> {
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            let op = if self.peek_symbol("+") {
+                BinaryOp::Add
+            } else if self.peek_symbol("-") {
+                BinaryOp::Subtract
+            } else {
+                return Ok(left);
+            };
+            self.next();
+            let right = self.parse_multiplicative()?;
+            left = Expr::Binary(Box::new(left), op, Box::new(right));
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expr> {
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = if self.peek_symbol("*") {
+                BinaryOp::Multiply
+            } else if self.peek_symbol("/") {
+                BinaryOp::Divide
+            } else {
+                return Ok(left);
+            };
+            self.next();
+            let right = self.parse_unary()?;
+            left = Expr::Binary(Box::new(left), op, Box::new(right));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if self.peek_symbol("-") {
+            self.next();
+            let inner = self.parse_unary()?;
+            return Ok(Expr::Negate(Box::new(inner)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        match self.next().ok_or(ExpressionError::UnexpectedEnd)? {
+            Token::Text(s) => Ok(Expr::Literal(Value::Text(s))),
+            Token::Number(n) => Ok(Expr::Literal(Value::Number(n))),
+            Token::Word(word) => Ok(match word.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                "null" => Expr::Literal(Value::Null),
+                "and" | "or" | "not" | "in" => {
+                    return Err(ExpressionError::UnexpectedToken(word));
+                }
+                _ => Expr::Identifier(word),
+            }),
+            Token::Symbol("(") => {
+                let expr = self.parse_conditional()?;
+                self.expect_symbol(")")?;
+                Ok(expr)
+            }
+            Token::Symbol("[") => {
+                let mut items = Vec::new();
+                if !self.peek_symbol("]") {
+                    loop {
+                        items.push(self.parse_conditional()?);
+                        if self.peek_symbol(",") {
+                            self.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                self.expect_symbol("]")?;
+                Ok(Expr::List(items))
+            }
+            other => Err(ExpressionError::UnexpectedToken(other.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn eval(input: &str, vars: &[(&str, Value)]) -> Value {
+        let vars: HashMap<String, Value> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        Expr::parse(input)
+            .unwrap()
+            .evaluate(&|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(eval("42", &[]), Value::Number(42.0));
+        assert_eq!(eval("\"hello\"", &[]), Value::Text("hello".to_string()));
+        assert_eq!(eval("'single'", &[]), Value::Text("single".to_string()));
+        assert_eq!(eval("true", &[]), Value::Bool(true));
+        assert_eq!(eval("null", &[]), Value::Null);
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let count = [("count", Value::Number(3.0))];
+        assert_eq!(eval("count == 3", &count), Value::Bool(true));
+        assert_eq!(eval("count != 3", &count), Value::Bool(false));
+        assert_eq!(eval("count > 1", &count), Value::Bool(true));
+        assert_eq!(eval("count <= 2", &count), Value::Bool(false));
+        assert_eq!(eval("count >= 3", &count), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_numeric_text_equality() {
+        let size = [("size", Value::Text("5".to_string()))];
+        assert_eq!(eval("size == 5", &size), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_logic_words_and_symbols() {
+        let vars = [("a", Value::Bool(true)), ("b", Value::Bool(false))];
+        assert_eq!(eval("a and not b", &vars), Value::Bool(true));
+        assert_eq!(eval("a && b || !b", &vars), Value::Bool(true));
+        assert_eq!(eval("(a or b) and b", &vars), Value::Bool(false));
+    }
+
+    #[test]
+    fn test_in_operator() {
+        let mood = [("mood", Value::Text("dark".to_string()))];
+        assert_eq!(
+            eval("mood in [\"dark\", \"grim\"]", &mood),
+            Value::Bool(true)
+        );
+        assert_eq!(eval("mood not in [\"calm\"]", &mood), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_conditional_and_concat() {
+        let vars = [
+            ("count", Value::Number(2.0)),
+            ("item", Value::Text("cat".to_string())),
+        ];
+        assert_eq!(
+            eval("count == 1 ? item : item + \"s\"", &vars),
+            Value::Text("cats".to_string())
+        );
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let n = [("n", Value::Number(4.0))];
+        assert_eq!(eval("n * 2 + 1", &n), Value::Number(9.0));
+        assert_eq!(eval("-n", &n), Value::Number(-4.0));
+        assert_eq!(eval("n / 0", &n), Value::Null);
+    }
+
+    #[test]
+    fn test_indexed_identifiers() {
+        let vars = [
+            ("ref:colors[*].text", Value::Text("all".to_string())),
+            ("ref:colors[1].text", Value::Text("second".to_string())),
+        ];
+        assert_eq!(
+            eval("ref:colors[*].text", &vars),
+            Value::Text("all".to_string())
+        );
+        assert_eq!(
+            eval("ref:colors[1].text == \"second\"", &vars),
+            Value::Bool(true)
+        );
+    }
+
+    #[test]
+    fn test_missing_identifier_is_null() {
+        assert_eq!(eval("missing", &[]), Value::Null);
+        assert_eq!(eval("missing == \"x\"", &[]), Value::Bool(false));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Expr::parse("count ==").is_err());
+        assert!(Expr::parse("(a && b").is_err());
+        assert!(Expr::parse("\"unterminated").is_err());
+        assert!(Expr::parse("a b").is_err());
+        assert!(Expr::parse("a ? b").is_err());
+        assert!(Expr::parse("a # b").is_err());
+    }
+}
@@ -1,6 +1,9 @@
 // M4: Rules Module
-// Execute coordination rules during enrichment phase
+// Execute coordination rules and decisions during enrichment phase
 
+pub mod decision;
+pub mod expression;
 pub mod processor;
 
+pub use decision::DecisionProcessor;
 pub use processor::{RuleError, RulesProcessor};
@@ -2,14 +2,16 @@
 // Executes coordination rules during enrichment phase
 
 use crate::context::{Context, ContextValue};
-use crate::core::models::Rule;
+use crate::core::models::{Namespace, Rule};
+use crate::renderer::ref_path::{RefPath, RefPathError};
 use crate::renderer::selector::SelectedValue;
+use crate::rules::expression::{Expr, ExpressionError, Value};
+use indexmap::IndexMap;
 use std::collections::HashMap;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum RuleError {
-    #[allow(dead_code)] // Reserved for future rule evaluation
     #[error("Failed to evaluate expression '{expr}': {reason}")]
     EvaluationError { expr: String, reason: String },
 
@@ -22,79 +24,121 @@ pub enum RuleError {
     #[error("Invalid expression format: {0}")]
     InvalidExpression(String),
 
+    #[error("Circular rule order: {0}")]
+    CircularOrder(String),
+
+    #[error("Invalid decision '{decision}': {reason}")]
+    InvalidDecision { decision: String, reason: String },
+
     #[error("Context error: {0}")]
     ContextError(#[from] crate::context::ContextError),
 }
 
 pub type Result<T> = std::result::Result<T, RuleError>;
 
+/// What a rule did when executed (recorded in render traces)
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum RuleOutcome {
+    /// The rule wrote its value to `key`
+    Applied { key: String },
+
+    /// The condition held, but `key` already had a value (first contribution wins)
+    Shadowed { key: String },
+
+    /// The `logic` condition did not hold
+    ConditionFalse,
+
+    /// The `when` reference was not selected
+    ReferenceMissing,
+}
+
 /// Rules processor - executes rules during Phase 2 (Enrichment)
 pub struct RulesProcessor<'a> {
     context: &'a mut Context,
-    selected: &'a HashMap<String, SelectedValue>,
+    selected: &'a HashMap<String, Vec<SelectedValue>>,
 }
 
 impl<'a> RulesProcessor<'a> {
     /// Create a new rules processor
-    pub fn new(context: &'a mut Context, selected: &'a HashMap<String, SelectedValue>) -> Self {
+    pub fn new(
+        context: &'a mut Context,
+        selected: &'a HashMap<String, Vec<SelectedValue>>,
+    ) -> Self {
         RulesProcessor { context, selected }
     }
 
-    /// Execute all rules (from HashMap)
-    pub fn execute_rules(&mut self, rules: &HashMap<String, Rule>) -> Result<()> {
-        // Execute rules (order not guaranteed with HashMap, but that's acceptable for now)
-        for rule in rules.values() {
-            self.execute_rule(rule)?;
-        }
+    /// Execute all rules of a namespace
+    ///
+    /// Rules run in a stable order: by rule id, adjusted by `priority` and `after`
+    #[allow(dead_code)] // Engine orders rules across namespaces and uses execute_ordered
+    pub fn execute_rules(&mut self, rules: &IndexMap<String, Rule>) -> Result<()> {
+        let mut scoped: Vec<ScopedRule> = rules
+            .iter()
+            .map(|(id, rule)| ScopedRule {
+                namespace: "",
+                id,
+                rule,
+            })
+            .collect();
+        scoped.sort_by(|a, b| a.id.cmp(b.id));
+
+        self.execute_ordered(&order_rules(scoped)?)?;
         Ok(())
     }
 
+    /// Execute rules in exactly the given order (see `order_rules`)
+    ///
+    /// Returns one outcome per rule, in the same order.
+    pub fn execute_ordered(&mut self, rules: &[ScopedRule]) -> Result<Vec<RuleOutcome>> {
+        rules
+            .iter()
+            .map(|scoped| self.execute_rule(scoped.rule))
+            .collect()
+    }
+
     /// Execute a single rule
     ///
     /// New simplified structure:
     /// - when: Field to check (e.g., "ref:creature.tags.article")
-    /// - logic: Optional condition (empty = exists check)
+    /// - logic: Optional condition (empty = exists check), see `parse_logic`
     /// - set: Context field to write to (e.g., "context.prompt.article")
     /// - value: Value to write (e.g., "ref:creature.tags.article")
-    fn execute_rule(&mut self, rule: &Rule) -> Result<()> {
+    fn execute_rule(&mut self, rule: &Rule) -> Result<RuleOutcome> {
         // Check if the "when" condition is met
         let _when_value = match self.evaluate_expression(&rule.when) {
             Ok(val) => val,
             Err(RuleError::ReferenceNotFound(_)) => {
                 // Reference doesn't exist, skip this rule
-                return Ok(());
+                return Ok(RuleOutcome::ReferenceMissing);
             }
             Err(e) => return Err(e),
         };
 
-        // If logic is specified, evaluate it
-        // For now, logic is simplified - just existence check means when_value exists
-        // Future: could parse expressions like "== value", "> 5", etc.
-        // If when_value was successfully retrieved, the condition is met
-
-        // Extract the key from "context.prompt.article" -> "article"
-        // or "context.global.time" -> "global:time"
-        let key = if rule.set.starts_with("context.prompt.") {
-            rule.set
-                .strip_prefix("context.prompt.")
-                .unwrap()
-                .to_string()
-        } else if rule.set.starts_with("context.global.") {
-            format!(
-                "global:{}",
-                rule.set.strip_prefix("context.global.").unwrap()
-            )
-        } else if rule.set.starts_with("context.") {
-            // Generic context.section.key format
-            rule.set.strip_prefix("context.").unwrap().replace(".", ":")
-        } else {
-            // Assume it's already in the right format
-            rule.set.clone()
-        };
+        // If logic is specified, the rule only fires when it holds
+        // Empty logic = existence check (when_value was resolved)
+        if let Some(condition) =
+            parse_logic(&rule.when, &rule.logic).map_err(|e| RuleError::EvaluationError {
+                expr: rule.logic.clone(),
+                reason: e.to_string(),
+            })?
+        {
+            let holds = condition
+                .evaluate(&|name| {
+                    lookup_path(name, self.context, self.selected)
+                        .or_else(|| self.context.get(name).ok().map(Value::from))
+                })
+                .is_truthy();
+            if !holds {
+                return Ok(RuleOutcome::ConditionFalse);
+            }
+        }
+
+        let key = context_key_for(&rule.set);
 
         // Skip if value already exists (first contribution wins)
         if self.context.has(&key) {
-            return Ok(());
+            return Ok(RuleOutcome::Shadowed { key });
         }
 
         // Evaluate the value expression
@@ -103,7 +147,7 @@ impl<'a> RulesProcessor<'a> {
         // Set in context
         self.context.set(&key, value)?;
 
-        Ok(())
+        Ok(RuleOutcome::Applied { key })
     }
 
     /// Evaluate an expression
@@ -111,6 +155,8 @@ impl<'a> RulesProcessor<'a> {
     /// Supported formats:
     /// - "ref:color.tags.article" - Get tag from selected value
     /// - "ref:color.text" - Get text from selected value
+    /// - "ref:colors[1].text", "ref:colors.count", "ref:colors.any.tags.plural" - Multi-select values
+    /// - "context.prompt.mood" - Get a context value
     /// - Literal values: "a", "an", "true", "42"
     /// - "literal_value" - Just a string literal
     fn evaluate_expression(&self, expr: &str) -> Result<ContextValue> {
@@ -119,6 +165,11 @@ impl<'a> RulesProcessor<'a> {
         // Check if it's a reference expression
         if let Some(ref_path) = expr.strip_prefix("ref:") {
             self.evaluate_reference(ref_path)
+        } else if expr.starts_with("context.") {
+            self.context
+                .get(context_key_for(expr))
+                .cloned()
+                .map_err(|_| RuleError::ReferenceNotFound(expr.to_string()))
         } else {
             // Literal string value
             Ok(ContextValue::Text(expr.to_string()))
@@ -127,68 +178,19 @@ impl<'a> RulesProcessor<'a> {
 
     /// Evaluate a reference expression
     ///
-    /// Format: "reference_name.property" or "reference_name.tags.tag_name"
+    /// Format: any `RefPath`, e.g. "color.tags.article", "colors[1].text",
+    /// "colors.count" or "colors.any.tags.plural"
     fn evaluate_reference(&self, path: &str) -> Result<ContextValue> {
-        // Split into parts: e.g., "color.tags.article" -> ["color", "tags", "article"]
-        let parts: Vec<&str> = path.split('.').collect();
-
-        if parts.is_empty() {
-            return Err(RuleError::InvalidExpression(format!(
-                "Empty reference path: ref:{}",
-                path
-            )));
-        }
-
-        // First part is the reference name
-        let ref_name = parts[0];
-
-        // Get the selected value
-        let selected_value = self
-            .selected
-            .get(ref_name)
-            .ok_or_else(|| RuleError::ReferenceNotFound(ref_name.to_string()))?;
-
-        // If only one part, return the text
-        if parts.len() == 1 {
-            return Ok(ContextValue::Text(selected_value.text.clone()));
-        }
-
-        // Second part should be "text" or "tags"
-        match parts[1] {
-            "text" => {
-                if parts.len() != 2 {
-                    return Err(RuleError::InvalidExpression(format!(
-                        "Invalid path after .text: ref:{}",
-                        path
-                    )));
-                }
-                Ok(ContextValue::Text(selected_value.text.clone()))
+        let ref_path = RefPath::parse(path)
+            .map_err(|_| RuleError::InvalidExpression(format!("ref:{}", path)))?;
+
+        match ref_path.resolve(self.selected) {
+            Ok(value) => self.json_to_context_value(&value),
+            Err(RefPathError::ReferenceNotFound(name)) => Err(RuleError::ReferenceNotFound(name)),
+            Err(RefPathError::FieldNotFound(path)) => Err(RuleError::TagNotFound { path }),
+            Err(RefPathError::InvalidPath(path)) => {
+                Err(RuleError::InvalidExpression(format!("ref:{}", path)))
             }
-            "tags" => {
-                // Third part should be the tag name
-                if parts.len() != 3 {
-                    return Err(RuleError::InvalidExpression(format!(
-                        "Invalid tags path (expected ref:name.tags.tag_name): ref:{}",
-                        path
-                    )));
-                }
-
-                let tag_name = parts[2];
-                let tag_value =
-                    selected_value
-                        .tags
-                        .get(tag_name)
-                        .ok_or_else(|| RuleError::TagNotFound {
-                            path: format!("{}.tags.{}", ref_name, tag_name),
-                        })?;
-
-                // Convert serde_json::Value to ContextValue
-                self.json_to_context_value(tag_value)
-            }
-            _ => Err(RuleError::InvalidExpression(format!(
-                "Unknown property '{}' (expected 'text' or 'tags'): ref:{}",
-                parts[1], path
-            ))),
         }
     }
 
@@ -223,25 +225,178 @@ impl<'a> RulesProcessor<'a> {
     }
 }
 
+/// A rule together with the namespace it is defined in
+#[derive(Debug, Clone, Copy)]
+pub struct ScopedRule<'r> {
+    pub namespace: &'r str,
+    pub id: &'r str,
+    pub rule: &'r Rule,
+}
+
+impl ScopedRule<'_> {
+    /// Whether an `after` entry ("rule_id" or "namespace:rule_id") names this rule
+    fn is_named_by(&self, entry: &str, from_namespace: &str) -> bool {
+        match entry.split_once(':') {
+            Some((namespace, id)) => namespace == self.namespace && id == self.id,
+            None => from_namespace == self.namespace && entry == self.id,
+        }
+    }
+}
+
+/// Collect a package's rules in the stable default order (namespace id, then rule id)
+pub fn scoped_rules(namespaces: &IndexMap<String, Namespace>) -> Vec<ScopedRule<'_>> {
+    let mut namespace_ids: Vec<&String> = namespaces.keys().collect();
+    namespace_ids.sort();
+
+    let mut scoped = Vec::new();
+    for namespace_id in namespace_ids {
+        let mut rule_ids: Vec<(&String, &Rule)> = namespaces[namespace_id].rules.iter().collect();
+        rule_ids.sort_by(|a, b| a.0.cmp(b.0));
+
+        scoped.extend(rule_ids.into_iter().map(|(id, rule)| ScopedRule {
+            namespace: namespace_id,
+            id,
+            rule,
+        }));
+    }
+    scoped
+}
+
+/// Order rules for execution
+///
+/// The input order is the default order. Rules are then stably sorted by
+/// descending `priority`, and finally each rule is moved after the rules
+/// named in its `after` list. Unknown `after` entries are ignored here
+/// (the validator reports them); cycles are an error.
+pub fn order_rules(rules: Vec<ScopedRule<'_>>) -> Result<Vec<ScopedRule<'_>>> {
+    let mut ranked = rules;
+    ranked.sort_by_key(|scoped| std::cmp::Reverse(scoped.rule.priority));
+
+    let prerequisites: Vec<Vec<usize>> = ranked
+        .iter()
+        .enumerate()
+        .map(|(i, scoped)| {
+            (0..ranked.len())
+                .filter(|&j| {
+                    j != i
+                        && scoped
+                            .rule
+                            .after
+                            .iter()
+                            .any(|entry| ranked[j].is_named_by(entry, scoped.namespace))
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut done = vec![false; ranked.len()];
+    let mut ordered = Vec::with_capacity(ranked.len());
+
+    while ordered.len() < ranked.len() {
+        // Earliest-ranked rule whose prerequisites have all run
+        let next =
+            (0..ranked.len()).find(|&i| !done[i] && prerequisites[i].iter().all(|&j| done[j]));
+
+        match next {
+            Some(i) => {
+                done[i] = true;
+                ordered.push(ranked[i]);
+            }
+            None => {
+                let remaining: Vec<String> = (0..ranked.len())
+                    .filter(|&i| !done[i])
+                    .map(|i| format!("{}:{}", ranked[i].namespace, ranked[i].id))
+                    .collect();
+                return Err(RuleError::CircularOrder(remaining.join(", ")));
+            }
+        }
+    }
+
+    Ok(ordered)
+}
+
+/// Parse a rule's logic into a condition (None = existence check only)
+///
+/// Logic is either a full expression over `ref:` paths and context keys
+/// (`ref:color.tags.article == "an" and not context.prompt.dark`), or starts
+/// with an operator and compares the `when` value implicitly (`== "an"`,
+/// `> 5`, `in ["a", "an"]`).
+pub fn parse_logic(when: &str, logic: &str) -> std::result::Result<Option<Expr>, ExpressionError> {
+    let logic = logic.trim();
+    if logic.is_empty() {
+        return Ok(None);
+    }
+
+    let implicit = ["==", "!=", "<", ">", "in ", "in[", "not in "]
+        .iter()
+        .any(|op| logic.starts_with(op));
+
+    if implicit {
+        Expr::parse(&format!("{} {}", when.trim(), logic)).map(Some)
+    } else {
+        Expr::parse(logic).map(Some)
+    }
+}
+
+/// Map a context path to a storage key
+///
+/// "context.prompt.article" -> "article", "context.global.time" -> "global:time",
+/// "context.scene.mood" -> "scene:mood"; anything else is used as-is.
+pub(crate) fn context_key_for(path: &str) -> String {
+    if let Some(key) = path.strip_prefix("context.prompt.") {
+        key.to_string()
+    } else if let Some(key) = path.strip_prefix("context.global.") {
+        format!("global:{}", key)
+    } else if let Some(rest) = path.strip_prefix("context.") {
+        // Generic context.section.key format
+        rest.replace('.', ":")
+    } else {
+        // Assume it's already in the right format
+        path.to_string()
+    }
+}
+
+/// Resolve an expression identifier against selected values and context
+///
+/// Supports any "ref:" path (see `RefPath`) and "context.scope.key" paths.
+/// Returns None for anything unresolved.
+pub(crate) fn lookup_path(
+    path: &str,
+    context: &Context,
+    selected: &HashMap<String, Vec<SelectedValue>>,
+) -> Option<Value> {
+    if let Some(ref_path) = path.strip_prefix("ref:") {
+        let value = RefPath::parse(ref_path).ok()?.resolve(selected).ok()?;
+        return Some(Value::from(&value));
+    }
+
+    if path.starts_with("context.") {
+        return context.get(context_key_for(path)).ok().map(Value::from);
+    }
+
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use indexmap::IndexMap;
     use serde_json::json;
 
-    fn create_test_selected() -> HashMap<String, SelectedValue> {
+    fn create_test_selected() -> HashMap<String, Vec<SelectedValue>> {
         let mut selected = HashMap::new();
 
-        let mut tags = HashMap::new();
+        let mut tags = IndexMap::new();
         tags.insert("article".to_string(), json!("an"));
         tags.insert("phonetic".to_string(), json!("vowel"));
         tags.insert("count".to_string(), json!(5));
 
         selected.insert(
             "color".to_string(),
-            SelectedValue {
+            vec![SelectedValue {
                 text: "orange".to_string(),
                 tags,
-            },
+            }],
         );
 
         selected
@@ -302,6 +457,8 @@ mod tests {
             logic: String::new(), // Empty = existence check
             set: "context.prompt.article".to_string(),
             value: "ref:color.tags.article".to_string(),
+            priority: 0,
+            after: Vec::new(),
         };
 
         processor.execute_rule(&rule).unwrap();
@@ -315,7 +472,7 @@ mod tests {
         let mut ctx = Context::new();
         let mut processor = RulesProcessor::new(&mut ctx, &selected);
 
-        let mut rules = HashMap::new();
+        let mut rules = IndexMap::new();
         rules.insert(
             "rule1".to_string(),
             Rule {
@@ -323,6 +480,8 @@ mod tests {
                 logic: String::new(),
                 set: "context.prompt.article".to_string(),
                 value: "ref:color.tags.article".to_string(),
+                priority: 0,
+                after: Vec::new(),
             },
         );
         rules.insert(
@@ -332,6 +491,8 @@ mod tests {
                 logic: String::new(),
                 set: "context.prompt.test_color".to_string(),
                 value: "ref:color.text".to_string(),
+                priority: 0,
+                after: Vec::new(),
             },
         );
 
@@ -360,4 +521,298 @@ mod tests {
         let result = processor.evaluate_expression("ref:color.tags.nonexistent");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_multi_select_rules() {
+        let mut selected = create_test_selected();
+        selected.insert(
+            "colors".to_string(),
+            vec![
+                SelectedValue {
+                    text: "red".to_string(),
+                    tags: IndexMap::from([("article".to_string(), json!("a"))]),
+                },
+                SelectedValue {
+                    text: "orange".to_string(),
+                    tags: IndexMap::from([("article".to_string(), json!("an"))]),
+                },
+            ],
+        );
+        let mut ctx = Context::new();
+        let mut processor = RulesProcessor::new(&mut ctx, &selected);
+
+        assert_eq!(
+            processor
+                .evaluate_expression("ref:colors[1].tags.article")
+                .unwrap(),
+            ContextValue::Text("an".to_string())
+        );
+        assert_eq!(
+            processor.evaluate_expression("ref:colors.count").unwrap(),
+            ContextValue::Number(2)
+        );
+
+        // Plural verb agreement driven by the number of selected values
+        processor
+            .execute_rule(&rule(
+                "ref:colors.count",
+                "> 1",
+                "context.prompt.verb",
+                "are",
+            ))
+            .unwrap();
+        processor
+            .execute_rule(&rule(
+                "ref:colors",
+                "ref:colors.all.tags.article and \"orange\" in ref:colors[*].text",
+                "context.prompt.has_orange",
+                "true",
+            ))
+            .unwrap();
+
+        assert_eq!(ctx.get_text("verb").unwrap(), "are");
+        assert!(ctx.has("has_orange"));
+    }
+
+    fn rule(when: &str, logic: &str, set: &str, value: &str) -> Rule {
+        Rule {
+            when: when.to_string(),
+            logic: logic.to_string(),
+            set: set.to_string(),
+            value: value.to_string(),
+            priority: 0,
+            after: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_implicit_logic_against_when_value() {
+        let selected = create_test_selected();
+        let mut ctx = Context::new();
+        let mut processor = RulesProcessor::new(&mut ctx, &selected);
+
+        processor
+            .execute_rule(&rule(
+                "ref:color.tags.article",
+                "== \"an\"",
+                "context.prompt.vowel",
+                "true",
+            ))
+            .unwrap();
+        processor
+            .execute_rule(&rule(
+                "ref:color.tags.count",
+                "> 10",
+                "context.prompt.many",
+                "true",
+            ))
+            .unwrap();
+        processor
+            .execute_rule(&rule(
+                "ref:color.tags.count",
+                "in [4, 5, 6]",
+                "context.prompt.some",
+                "true",
+            ))
+            .unwrap();
+
+        assert!(ctx.has("vowel"));
+        assert!(!ctx.has("many"));
+        assert!(ctx.has("some"));
+    }
+
+    #[test]
+    fn test_full_logic_expression() {
+        let selected = create_test_selected();
+        let mut ctx = Context::new();
+        ctx.set("mood", "dark").unwrap();
+        ctx.set("global:season", "winter").unwrap();
+        let mut processor = RulesProcessor::new(&mut ctx, &selected);
+
+        processor
+            .execute_rule(&rule(
+                "ref:color",
+                "ref:color.text == \"orange\" and (mood == \"dark\" or not context.global.season)",
+                "context.prompt.lighting",
+                "dim",
+            ))
+            .unwrap();
+        processor
+            .execute_rule(&rule(
+                "ref:color",
+                "context.global.season != \"winter\"",
+                "context.prompt.warm",
+                "yes",
+            ))
+            .unwrap();
+
+        assert_eq!(ctx.get_text("lighting").unwrap(), "dim");
+        assert!(!ctx.has("warm"));
+    }
+
+    #[test]
+    fn test_when_context_key() {
+        let selected = create_test_selected();
+        let mut ctx = Context::new();
+        ctx.set("mood", "dark").unwrap();
+        let mut processor = RulesProcessor::new(&mut ctx, &selected);
+
+        processor
+            .execute_rule(&rule(
+                "context.prompt.mood",
+                "!= \"calm\"",
+                "context.prompt.tone",
+                "context.prompt.mood",
+            ))
+            .unwrap();
+        processor
+            .execute_rule(&rule(
+                "context.prompt.missing",
+                "",
+                "context.prompt.never",
+                "x",
+            ))
+            .unwrap();
+
+        assert_eq!(ctx.get_text("tone").unwrap(), "dark");
+        assert!(!ctx.has("never"));
+    }
+
+    #[test]
+    fn test_malformed_logic_is_error() {
+        let selected = create_test_selected();
+        let mut ctx = Context::new();
+        let mut processor = RulesProcessor::new(&mut ctx, &selected);
+
+        let result =
+            processor.execute_rule(&rule("ref:color.text", "== ", "context.prompt.x", "y"));
+        assert!(matches!(result, Err(RuleError::EvaluationError { .. })));
+    }
+
+    #[test]
+    fn test_parse_logic() {
+        assert!(parse_logic("ref:a", "").unwrap().is_none());
+        assert!(parse_logic("ref:a", "   ").unwrap().is_none());
+        assert!(parse_logic("ref:a", "== 1").unwrap().is_some());
+        assert!(parse_logic("ref:a", "not in [1]").unwrap().is_some());
+        assert!(parse_logic("ref:a", "ref:a.tags.x && y").unwrap().is_some());
+        assert!(parse_logic("ref:a", "&& y").is_err());
+    }
+
+    fn ordered_ids(namespaces: &IndexMap<String, Namespace>) -> Result<Vec<String>> {
+        Ok(order_rules(scoped_rules(namespaces))?
+            .iter()
+            .map(|r| format!("{}:{}", r.namespace, r.id))
+            .collect())
+    }
+
+    fn namespace_with_rules(id: &str, rules: Vec<(&str, Rule)>) -> Namespace {
+        Namespace {
+            id: id.to_string(),
+            datatypes: IndexMap::new(),
+            prompt_sections: IndexMap::new(),
+            separator_sets: IndexMap::new(),
+            rules: rules
+                .into_iter()
+                .map(|(rule_id, rule)| (rule_id.to_string(), rule))
+                .collect(),
+            decisions: Vec::new(),
+            rulebooks: IndexMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_default_order_is_namespace_then_id() {
+        let mut namespaces = IndexMap::new();
+        for ns in ["zeta", "alpha"] {
+            namespaces.insert(
+                ns.to_string(),
+                namespace_with_rules(
+                    ns,
+                    vec![
+                        ("b", rule("x", "", "context.prompt.x", "1")),
+                        ("a", rule("x", "", "context.prompt.x", "1")),
+                    ],
+                ),
+            );
+        }
+
+        assert_eq!(
+            ordered_ids(&namespaces).unwrap(),
+            vec!["alpha:a", "alpha:b", "zeta:a", "zeta:b"]
+        );
+    }
+
+    #[test]
+    fn test_priority_and_after() {
+        let mut high = rule("x", "", "context.prompt.x", "1");
+        high.priority = 10;
+        let mut late = rule("x", "", "context.prompt.x", "1");
+        late.after = vec!["other:z".to_string()];
+        let mut needs_c = rule("x", "", "context.prompt.x", "1");
+        needs_c.after = vec!["c".to_string()];
+
+        let mut namespaces = IndexMap::new();
+        namespaces.insert(
+            "main".to_string(),
+            namespace_with_rules(
+                "main",
+                vec![
+                    ("a", late),
+                    ("b", needs_c),
+                    ("c", rule("x", "", "context.prompt.x", "1")),
+                    ("d", high),
+                ],
+            ),
+        );
+        namespaces.insert(
+            "other".to_string(),
+            namespace_with_rules("other", vec![("z", rule("x", "", "context.prompt.x", "1"))]),
+        );
+
+        assert_eq!(
+            ordered_ids(&namespaces).unwrap(),
+            vec!["main:d", "main:c", "main:b", "other:z", "main:a"]
+        );
+    }
+
+    #[test]
+    fn test_after_cycle_is_error() {
+        let mut a = rule("x", "", "context.prompt.x", "1");
+        a.after = vec!["b".to_string()];
+        let mut b = rule("x", "", "context.prompt.x", "1");
+        b.after = vec!["test:a".to_string()];
+
+        let mut namespaces = IndexMap::new();
+        namespaces.insert(
+            "test".to_string(),
+            namespace_with_rules("test", vec![("a", a), ("b", b)]),
+        );
+
+        assert!(matches!(
+            ordered_ids(&namespaces),
+            Err(RuleError::CircularOrder(_))
+        ));
+    }
+
+    #[test]
+    fn test_first_contribution_follows_order() {
+        let selected = create_test_selected();
+        let mut ctx = Context::new();
+
+        let mut rules = IndexMap::new();
+        rules.insert(
+            "a_default".to_string(),
+            rule("ref:color", "", "context.prompt.tone", "plain"),
+        );
+        let mut preferred = rule("ref:color", "", "context.prompt.tone", "vivid");
+        preferred.priority = 1;
+        rules.insert("z_preferred".to_string(), preferred);
+
+        RulesProcessor::new(&mut ctx, &selected)
+            .execute_rules(&rules)
+            .unwrap();
+
+        assert_eq!(ctx.get_text("tone").unwrap(), "vivid");
+    }
 }
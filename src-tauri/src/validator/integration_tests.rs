@@ -52,6 +52,21 @@ mod integration_tests {
         );
     }
 
+    #[test]
+    fn test_validate_inheritance_test() {
+        let content = fs::read_to_string("../test-packages/inheritance-test.yaml")
+            .expect("Failed to read inheritance-test.yaml");
+        let package = parse_yaml(&content).expect("Failed to parse inheritance-test.yaml");
+
+        let result = PackageValidator::validate(&package);
+
+        assert!(
+            result.is_valid(),
+            "inheritance-test.yaml should be valid. Errors: {:?}",
+            result.errors
+        );
+    }
+
     #[test]
     fn test_validate_missing_reference() {
         let content = fs::read_to_string("../test-packages/invalid/missing-reference.yaml")
@@ -113,4 +128,160 @@ mod integration_tests {
             result.errors
         );
     }
+
+    #[test]
+    fn test_validate_circular_extends() {
+        let content = fs::read_to_string("../test-packages/invalid/circular-extends.yaml")
+            .expect("Failed to read circular-extends.yaml");
+        let package = parse_yaml(&content).expect("Failed to parse circular-extends.yaml");
+
+        let result = PackageValidator::validate(&package);
+
+        let cycles = result
+            .errors
+            .iter()
+            .filter(|e| matches!(e, crate::validator::ValidationError::CircularExtends { .. }))
+            .count();
+        assert_eq!(
+            cycles, 1,
+            "Should report the extends cycle once. Got: {:?}",
+            result.errors
+        );
+    }
+
+    /// Key order of every author-ordered map in a package document
+    /// (struct fields are excluded: their order is fixed by the model)
+    fn map_key_orders(package: &serde_yaml::Value) -> Vec<(String, Vec<String>)> {
+        fn keys(path: &str, value: &serde_yaml::Value, out: &mut Vec<(String, Vec<String>)>) {
+            // Empty maps may be omitted in the source and written on save
+            if let Some(map) = value.as_mapping().filter(|m| !m.is_empty()) {
+                let names = map
+                    .keys()
+                    .filter_map(|k| k.as_str().map(str::to_string))
+                    .collect();
+                out.push((path.to_string(), names));
+            }
+        }
+
+        let mut out = Vec::new();
+        let Some(namespaces) = package.get("namespaces").and_then(|n| n.as_mapping()) else {
+            return out;
+        };
+        keys("namespaces", &package["namespaces"], &mut out);
+
+        for (ns_id, namespace) in namespaces {
+            let ns = ns_id.as_str().unwrap_or_default();
+            for field in [
+                "datatypes",
+                "prompt_sections",
+                "separator_sets",
+                "rules",
+                "rulebooks",
+            ] {
+                keys(&format!("{}.{}", ns, field), &namespace[field], &mut out);
+            }
+
+            if let Some(datatypes) = namespace["datatypes"].as_mapping() {
+                for (name, datatype) in datatypes {
+                    let name = name.as_str().unwrap_or_default();
+                    keys(
+                        &format!("{}.datatypes.{}.override_tags", ns, name),
+                        &datatype["override_tags"],
+                        &mut out,
+                    );
+                    for (i, value) in datatype["values"]
+                        .as_sequence()
+                        .into_iter()
+                        .flatten()
+                        .enumerate()
+                    {
+                        keys(
+                            &format!("{}.datatypes.{}.values[{}].tags", ns, name, i),
+                            &value["tags"],
+                            &mut out,
+                        );
+                    }
+                }
+            }
+
+            if let Some(sections) = namespace["prompt_sections"].as_mapping() {
+                for (name, section) in sections {
+                    keys(
+                        &format!(
+                            "{}.prompt_sections.{}.references",
+                            ns,
+                            name.as_str().unwrap_or_default()
+                        ),
+                        &section["references"],
+                        &mut out,
+                    );
+                }
+            }
+
+            if let Some(rulebooks) = namespace["rulebooks"].as_mapping() {
+                for (name, rulebook) in rulebooks {
+                    keys(
+                        &format!(
+                            "{}.rulebooks.{}.context_defaults",
+                            ns,
+                            name.as_str().unwrap_or_default()
+                        ),
+                        &rulebook["context_defaults"],
+                        &mut out,
+                    );
+                }
+            }
+        }
+
+        out
+    }
+
+    #[test]
+    fn test_round_trip_preserves_key_order() {
+        // Fixtures in an outdated format (e.g. `dependencies` without `package`)
+        const UNPARSEABLE: &[&str] = &["base.yaml", "dependent.yaml"];
+
+        let mut fixtures = 0;
+        let mut checked = 0;
+        for entry in fs::read_dir("../test-packages").expect("Failed to read test-packages") {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|e| e.to_str()) != Some("yaml") {
+                continue;
+            }
+
+            fixtures += 1;
+            let name = path.file_name().unwrap().to_str().unwrap();
+            let content = fs::read_to_string(&path).unwrap();
+            if UNPARSEABLE.contains(&name) {
+                assert!(parse_yaml(&content).is_err(), "{} parses now", name);
+                continue;
+            }
+            let package = parse_yaml(&content)
+                .unwrap_or_else(|e| panic!("Failed to parse {}: {}", path.display(), e));
+
+            // Load -> edit -> save, the way the editor does it
+            let mut edited = package.clone();
+            edited.version = "9.9.9".to_string();
+            let saved = serde_yaml::to_string(&edited).expect("Failed to serialize package");
+
+            let original: serde_yaml::Value = serde_yaml::from_str(&content).unwrap();
+            let saved: serde_yaml::Value = serde_yaml::from_str(&saved).unwrap();
+            assert_eq!(
+                map_key_orders(&saved),
+                map_key_orders(&original),
+                "Key order changed on save: {}",
+                path.display()
+            );
+            assert_eq!(
+                parse_yaml(&serde_yaml::to_string(&saved).unwrap())
+                    .unwrap()
+                    .version,
+                "9.9.9"
+            );
+            checked += 1;
+        }
+
+        assert!(checked > 0, "Expected to round-trip the test packages");
+        assert_eq!(checked, fixtures - UNPARSEABLE.len());
+    }
 }
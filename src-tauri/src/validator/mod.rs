@@ -1,7 +1,13 @@
 // M6 Phase 1: Package Validator
 // Comprehensive validation with helpful error messages
 
-use crate::core::models::Package;
+use crate::core::lookup::{find_namespace, split_package};
+use crate::core::models::{Package, PromptSection, Reference};
+use crate::parser::qualify_references;
+use crate::renderer::template_parser::Template;
+use crate::renderer::transforms::TransformRegistry;
+use indexmap::IndexMap;
+use serde::Serialize;
 use std::collections::HashMap;
 use thiserror::Error;
 
@@ -9,7 +15,10 @@ use thiserror::Error;
 mod integration_tests;
 
 /// Validation error types
-#[derive(Error, Debug, Clone)]
+///
+/// Serializes with the variant name as `kind`, next to its fields.
+#[derive(Error, Debug, Clone, Serialize)]
+#[serde(tag = "kind")]
 pub enum ValidationError {
     #[error("Reference not found: '{reference}' in {defined_in}")]
     ReferenceNotFound {
@@ -18,9 +27,32 @@ pub enum ValidationError {
         suggestion: Option<String>,
     },
 
+    #[error("Reference '{reference}' in {defined_in} can't be resolved: {reason}")]
+    UnresolvableReference {
+        reference: String,
+        defined_in: String,
+        reason: String,
+    },
+
     #[error("Circular reference detected: {chain}")]
     CircularReference { chain: String },
 
+    #[error("Circular extends detected: {chain}")]
+    CircularExtends { chain: String },
+
+    #[error("Invalid template in {promptsection}: {reason}")]
+    InvalidTemplate {
+        promptsection: String,
+        reason: String,
+    },
+
+    #[error("Unknown transform '{transform}' on '{reference}' in {promptsection}")]
+    UnknownTransform {
+        transform: String,
+        reference: String,
+        promptsection: String,
+    },
+
     #[error("Invalid tag filter: {expression} - {reason}")]
     InvalidTagFilter { expression: String, reason: String },
 
@@ -44,10 +76,12 @@ pub enum ValidationError {
         datatype: String,
     },
 
-    #[allow(dead_code)] // Reserved for future validation
     #[error("Invalid rule: {reason} in rule '{rule_name}'")]
     InvalidRule { rule_name: String, reason: String },
 
+    #[error("Invalid decision: {reason} in decision '{decision}'")]
+    InvalidDecision { decision: String, reason: String },
+
     #[allow(dead_code)] // Reserved for future validation
     #[error("Duplicate ID: '{id}' defined multiple times in namespace '{namespace}'")]
     DuplicateId { id: String, namespace: String },
@@ -68,7 +102,8 @@ pub enum ValidationError {
 }
 
 /// Validation warning types
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind")]
 pub enum ValidationWarning {
     UnusedDatatype {
         datatype: String,
@@ -236,6 +271,18 @@ impl PackageValidator {
     ) -> ValidationResult {
         let mut result = ValidationResult::new();
 
+        // Check what the renderer sees: dependencies resolve their own components
+        // first. Ambiguous references are left as written and reported below.
+        let qualified = if dependencies.is_empty() {
+            None
+        } else {
+            qualify_references(package, dependencies).ok()
+        };
+        let (package, dependencies) = match &qualified {
+            Some((package, dependencies)) => (package, dependencies),
+            None => (package, dependencies),
+        };
+
         // Schema validation (already done by serde, but we can add more)
         Self::validate_schema(package, &mut result);
 
@@ -263,6 +310,9 @@ impl PackageValidator {
         // M9 Phase 2.4: Validate dependencies first
         Self::validate_dependencies(package, result);
 
+        // Declared default namespace must exist
+        Self::validate_default_namespace(package, result);
+
         // Validate all references resolve (with dependencies)
         Self::validate_references_with_deps(package, dependencies, result);
 
@@ -272,20 +322,26 @@ impl PackageValidator {
         // Check for circular references in nested promptsections
         Self::validate_no_circular_references(package, result);
 
+        // Validate datatype extends targets exist and don't form cycles
+        Self::validate_datatype_extends(package, dependencies, result);
+
         // Validate tag filters
         Self::validate_tag_filters(package, result);
 
         // Validate separator sets exist
-        Self::validate_separator_sets(package, result);
+        Self::validate_separator_sets(package, dependencies, result);
 
         // Validate min/max constraints
         Self::validate_min_max(package, result);
 
         // Validate unique constraints are feasible
-        Self::validate_unique_constraints(package, result);
+        Self::validate_unique_constraints(package, dependencies, result);
 
         // Validate rules
-        Self::validate_rules(package, result);
+        Self::validate_rules(package, dependencies, result);
+
+        // Validate decisions
+        Self::validate_decisions(package, result);
 
         // M9 Task 1.4: Validate rulebooks
         Self::validate_rulebooks(package, result);
@@ -303,6 +359,36 @@ impl PackageValidator {
         Self::check_weight_sums(package, result);
     }
 
+    // Validate the declared default namespace exists
+    fn validate_default_namespace(package: &Package, result: &mut ValidationResult) {
+        let Some(declared) = &package.default_namespace else {
+            return;
+        };
+
+        if !package.namespaces.contains_key(declared) {
+            let mut available: Vec<&str> = package.namespaces.keys().map(String::as_str).collect();
+            available.sort();
+            result.add_error(ValidationError::ReferenceNotFound {
+                reference: declared.clone(),
+                defined_in: "default_namespace".to_string(),
+                suggestion: Some(format!("Use one of: {}", available.join(", "))),
+            });
+        }
+    }
+
+    /// References as the renderer sees them: YAML definitions plus inline
+    /// template parameters and implicit references (YAML only if the template
+    /// doesn't parse; validate_template_references reports that)
+    fn effective_references(
+        ns_id: &str,
+        promptsection: &PromptSection,
+    ) -> IndexMap<String, Reference> {
+        match Template::parse(&promptsection.template) {
+            Ok(template) => template.effective_references(&promptsection.references, ns_id),
+            Err(_) => promptsection.references.clone(),
+        }
+    }
+
     // M9 Phase 2.7: Validate all references resolve (with dependencies support)
     fn validate_references_with_deps(
         package: &Package,
@@ -312,7 +398,7 @@ impl PackageValidator {
         for (ns_id, namespace) in &package.namespaces {
             // Check all promptsection references
             for (ps_name, promptsection) in &namespace.prompt_sections {
-                for (ref_name, reference) in &promptsection.references {
+                for (ref_name, reference) in &Self::effective_references(ns_id, promptsection) {
                     // Skip empty targets (user is still editing)
                     if reference.target.is_empty() {
                         continue;
@@ -353,20 +439,26 @@ impl PackageValidator {
                         (ns_id.clone(), reference.target.clone())
                     };
 
-                    // Check if it exists in the main package
-                    let found_in_package =
-                        if let Some(target_namespace) = package.namespaces.get(&target_ns) {
-                            target_namespace.datatypes.contains_key(&target_name)
-                                || target_namespace.prompt_sections.contains_key(&target_name)
-                        } else {
-                            false
-                        };
-
-                    // M9: If not found in main package, check dependencies
-                    let found = if found_in_package {
-                        true
-                    } else {
-                        Self::find_in_dependencies(dependencies, &target_ns, &target_name)
+                    // Check the main package, then dependencies (M9)
+                    let found = match find_namespace(
+                        package,
+                        Some(dependencies),
+                        &target_ns,
+                        &target_name,
+                        |ns| {
+                            ns.datatypes.contains_key(&target_name)
+                                || ns.prompt_sections.contains_key(&target_name)
+                        },
+                    ) {
+                        Ok(found) => found.is_some(),
+                        Err(e) => {
+                            result.add_error(ValidationError::UnresolvableReference {
+                                reference: reference.target.clone(),
+                                defined_in: format!("{}:{}", ns_id, ps_name),
+                                reason: e.to_string(),
+                            });
+                            continue;
+                        }
                     };
 
                     if !found {
@@ -376,7 +468,16 @@ impl PackageValidator {
                             dependencies,
                             &target_ns,
                             &target_name,
-                        );
+                        )
+                        .or_else(|| {
+                            // Implicit reference (used in the template, not defined in YAML)
+                            (!promptsection.references.contains_key(ref_name)).then(|| {
+                                format!(
+                                    "Add reference definition for '{}' in the references section",
+                                    ref_name
+                                )
+                            })
+                        });
 
                         result.add_error(ValidationError::ReferenceNotFound {
                             reference: reference.target.clone(),
@@ -389,24 +490,6 @@ impl PackageValidator {
         }
     }
 
-    /// M9 Phase 2.7: Check if a component exists in any dependency
-    fn find_in_dependencies(
-        dependencies: &HashMap<String, Package>,
-        target_ns: &str,
-        target_name: &str,
-    ) -> bool {
-        for dep_package in dependencies.values() {
-            if let Some(target_namespace) = dep_package.namespaces.get(target_ns) {
-                if target_namespace.datatypes.contains_key(target_name)
-                    || target_namespace.prompt_sections.contains_key(target_name)
-                {
-                    return true;
-                }
-            }
-        }
-        false
-    }
-
     /// M9 Phase 2.7: Find similar names including dependencies
     fn find_similar_name_with_deps(
         package: &Package,
@@ -414,6 +497,8 @@ impl PackageValidator {
         target_ns: &str,
         target_name: &str,
     ) -> Option<String> {
+        let (_, target_ns) = split_package(target_ns);
+
         // First try in main package
         if let Some(suggestion) = Self::find_similar_name(package, target_ns, target_name) {
             return Some(suggestion);
@@ -432,23 +517,32 @@ impl PackageValidator {
         None
     }
 
-    // Validate that references used in templates are defined in the promptsection
+    // Validate that templates parse and that defined references are used
+    // (template references without a definition are implicit references,
+    // checked by validate_references_with_deps)
     fn validate_template_references(package: &Package, result: &mut ValidationResult) {
         for (ns_id, namespace) in &package.namespaces {
             for (ps_name, promptsection) in &namespace.prompt_sections {
-                // Extract references from template
-                let template_refs = Self::extract_template_references(&promptsection.template);
-
-                // Check each reference in template is defined (ERROR if missing)
-                for ref_name in &template_refs {
-                    if !promptsection.references.contains_key(ref_name) {
-                        result.add_error(ValidationError::ReferenceNotFound {
-                            reference: ref_name.clone(),
-                            defined_in: format!("{}:{}", ns_id, ps_name),
-                            suggestion: Some(format!(
-                                "Add reference definition for '{}' in the references section",
-                                ref_name
-                            )),
+                let template = match Template::parse(&promptsection.template) {
+                    Ok(template) => template,
+                    Err(e) => {
+                        result.add_error(ValidationError::InvalidTemplate {
+                            promptsection: format!("{}:{}", ns_id, ps_name),
+                            reason: e.to_string(),
+                        });
+                        continue;
+                    }
+                };
+                let template_refs = template.get_references();
+
+                // Pipe transforms must be known to the renderer
+                let transforms = TransformRegistry::builtin();
+                for (reference, transform) in template.get_transforms() {
+                    if !transforms.contains(transform) {
+                        result.add_error(ValidationError::UnknownTransform {
+                            transform: transform.to_string(),
+                            reference: reference.to_string(),
+                            promptsection: format!("{}:{}", ns_id, ps_name),
                         });
                     }
                 }
@@ -466,33 +560,6 @@ impl PackageValidator {
         }
     }
 
-    // Extract reference names from template (finds {ref_name} patterns)
-    fn extract_template_references(template: &str) -> Vec<String> {
-        let mut refs = Vec::new();
-        let mut chars = template.chars().peekable();
-
-        while let Some(ch) = chars.next() {
-            if ch == '{' {
-                let mut ref_name = String::new();
-
-                // Collect characters until }
-                while let Some(&next_ch) = chars.peek() {
-                    if next_ch == '}' {
-                        chars.next(); // consume }
-                        break;
-                    }
-                    ref_name.push(chars.next().unwrap());
-                }
-
-                if !ref_name.is_empty() && !ref_name.starts_with("context.") {
-                    refs.push(ref_name);
-                }
-            }
-        }
-
-        refs
-    }
-
     // Find similar names for helpful suggestions
     fn find_similar_name(package: &Package, namespace: &str, target: &str) -> Option<String> {
         if let Some(ns) = package.namespaces.get(namespace) {
@@ -560,7 +627,7 @@ impl PackageValidator {
 
                 if let Some(chain) = Self::find_circular_ref(package, &full_name, &mut visited) {
                     result.add_error(ValidationError::CircularReference {
-                        chain: chain.join(" â†’ "),
+                        chain: chain.join(" -> "),
                     });
                 }
             }
@@ -595,17 +662,18 @@ impl PackageValidator {
         let promptsection = namespace.prompt_sections.get(ps_name)?;
 
         // Check all references
-        for reference in promptsection.references.values() {
+        for reference in Self::effective_references(ns_id, promptsection).values() {
             // Skip context references
             if reference.target.starts_with("context:") {
                 continue;
             }
 
-            // Parse the target
-            let target = if reference.target.contains(':') {
-                reference.target.clone()
-            } else {
-                format!("{}:{}", ns_id, reference.target)
+            // Parse the target (only this package's promptsections can form a cycle here)
+            let target = match reference.target.split_once('/') {
+                Some((package_id, target)) if package_id == package.id => target.to_string(),
+                Some(_) => continue,
+                None if reference.target.contains(':') => reference.target.clone(),
+                None => format!("{}:{}", ns_id, reference.target),
             };
 
             // Check if this target is a promptsection
@@ -628,13 +696,64 @@ impl PackageValidator {
         None
     }
 
+    // Validate datatype inheritance (extends targets resolve, no cycles)
+    fn validate_datatype_extends(
+        package: &Package,
+        dependencies: &HashMap<String, Package>,
+        result: &mut ValidationResult,
+    ) {
+        use crate::core::inheritance::{resolve_datatype_values, InheritanceError};
+
+        let mut reported_cycles: Vec<Vec<String>> = Vec::new();
+
+        for (ns_id, namespace) in &package.namespaces {
+            for (dt_name, datatype) in &namespace.datatypes {
+                let Some(parent) = &datatype.extends else {
+                    continue;
+                };
+
+                match resolve_datatype_values(package, Some(dependencies), ns_id, dt_name) {
+                    Ok(_) => {}
+                    Err(InheritanceError::NotFound(missing)) => {
+                        result.add_error(ValidationError::ReferenceNotFound {
+                            reference: parent.clone(),
+                            defined_in: format!("{}:{} (extends)", ns_id, dt_name),
+                            suggestion: Some(format!(
+                                "Check that datatype '{}' exists in this package or a dependency",
+                                missing
+                            )),
+                        });
+                    }
+                    Err(InheritanceError::Lookup(e)) => {
+                        result.add_error(ValidationError::UnresolvableReference {
+                            reference: parent.clone(),
+                            defined_in: format!("{}:{} (extends)", ns_id, dt_name),
+                            reason: e.to_string(),
+                        });
+                    }
+                    Err(InheritanceError::Cycle(chain)) => {
+                        // Report each cycle once, not once per member
+                        let mut members = chain[..chain.len() - 1].to_vec();
+                        members.sort();
+                        if !reported_cycles.contains(&members) {
+                            reported_cycles.push(members);
+                            result.add_error(ValidationError::CircularExtends {
+                                chain: chain.join(" -> "),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     // Validate tag filters are parseable
     fn validate_tag_filters(package: &Package, result: &mut ValidationResult) {
         use crate::renderer::tag_expression::ExpressionParser;
 
-        for namespace in package.namespaces.values() {
+        for (ns_id, namespace) in &package.namespaces {
             for promptsection in namespace.prompt_sections.values() {
-                for reference in promptsection.references.values() {
+                for reference in Self::effective_references(ns_id, promptsection).values() {
                     if let Some(filter) = &reference.filter {
                         // Try to parse the expression
                         match ExpressionParser::parse(filter) {
@@ -655,13 +774,28 @@ impl PackageValidator {
     }
 
     // Validate separator sets exist
-    fn validate_separator_sets(package: &Package, result: &mut ValidationResult) {
+    // Bare names resolve in the same namespace; "namespace:name" also searches dependencies
+    fn validate_separator_sets(
+        package: &Package,
+        dependencies: &HashMap<String, Package>,
+        result: &mut ValidationResult,
+    ) {
         for (ns_id, namespace) in &package.namespaces {
             for (ps_name, promptsection) in &namespace.prompt_sections {
-                for (ref_name, reference) in &promptsection.references {
+                for (ref_name, reference) in &Self::effective_references(ns_id, promptsection) {
                     if let Some(sep_ref) = &reference.separator {
                         // Check if separator set exists
-                        let found = namespace.separator_sets.contains_key(sep_ref);
+                        let found = match sep_ref.split_once(':') {
+                            Some((target_ns, target_name)) => find_namespace(
+                                package,
+                                Some(dependencies),
+                                target_ns,
+                                target_name,
+                                |ns| ns.separator_sets.contains_key(target_name),
+                            )
+                            .is_ok_and(|found| found.is_some()),
+                            None => namespace.separator_sets.contains_key(sep_ref),
+                        };
 
                         if !found {
                             result.add_error(ValidationError::SeparatorNotFound {
@@ -679,7 +813,7 @@ impl PackageValidator {
     fn validate_min_max(package: &Package, result: &mut ValidationResult) {
         for (ns_id, namespace) in &package.namespaces {
             for (ps_name, promptsection) in &namespace.prompt_sections {
-                for (ref_name, reference) in &promptsection.references {
+                for (ref_name, reference) in &Self::effective_references(ns_id, promptsection) {
                     if reference.min > reference.max {
                         result.add_error(ValidationError::MinMaxInvalid {
                             min: reference.min,
@@ -693,10 +827,16 @@ impl PackageValidator {
     }
 
     // Validate unique constraints are feasible
-    fn validate_unique_constraints(package: &Package, result: &mut ValidationResult) {
+    fn validate_unique_constraints(
+        package: &Package,
+        dependencies: &HashMap<String, Package>,
+        result: &mut ValidationResult,
+    ) {
+        use crate::core::inheritance::resolve_datatype_values;
+
         for (ns_id, namespace) in &package.namespaces {
             for promptsection in namespace.prompt_sections.values() {
-                for reference in promptsection.references.values() {
+                for reference in Self::effective_references(ns_id, promptsection).values() {
                     // Only check if unique is true and max > 1
                     if reference.unique && reference.max > 1 {
                         // Find the target datatype
@@ -707,18 +847,21 @@ impl PackageValidator {
                             (ns_id.clone(), reference.target.clone())
                         };
 
-                        // Get the datatype
-                        if let Some(target_namespace) = package.namespaces.get(&target_ns) {
-                            if let Some(datatype) = target_namespace.datatypes.get(&target_name) {
-                                let available = datatype.values.len();
-
-                                if available < reference.max {
-                                    result.add_error(ValidationError::UniqueConstraintInfeasible {
-                                        requested: reference.max,
-                                        available,
-                                        datatype: reference.target.clone(),
-                                    });
-                                }
+                        // Count the datatype's values, including inherited ones
+                        if let Ok(values) = resolve_datatype_values(
+                            package,
+                            Some(dependencies),
+                            &target_ns,
+                            &target_name,
+                        ) {
+                            let available = values.len();
+
+                            if available < reference.max {
+                                result.add_error(ValidationError::UniqueConstraintInfeasible {
+                                    requested: reference.max,
+                                    available,
+                                    datatype: reference.target.clone(),
+                                });
                             }
                         }
                     }
@@ -727,13 +870,150 @@ impl PackageValidator {
         }
     }
 
-    // Validate rules (M4)
-    fn validate_rules(_package: &Package, _result: &mut ValidationResult) {
-        // Rule validation is complex and would check:
-        // - Condition expressions are valid
-        // - Actions reference valid context keys
-        // - Rule dependencies don't create cycles
-        // TODO: Implement comprehensive rule validation
+    // Validate rules (M4): required fields present, logic parses, ordering is satisfiable
+    fn validate_rules(
+        package: &Package,
+        dependencies: &HashMap<String, Package>,
+        result: &mut ValidationResult,
+    ) {
+        use crate::rules::processor::{order_rules, parse_logic, scoped_rules};
+        use crate::rules::RuleError;
+
+        for (ns_id, namespace) in &package.namespaces {
+            for (rule_name, rule) in &namespace.rules {
+                let location = format!("{}:{}", ns_id, rule_name);
+
+                if rule.when.trim().is_empty() {
+                    result.add_error(ValidationError::InvalidRule {
+                        rule_name: location.clone(),
+                        reason: "'when' must not be empty".to_string(),
+                    });
+                }
+
+                if rule.set.trim().is_empty() {
+                    result.add_error(ValidationError::InvalidRule {
+                        rule_name: location.clone(),
+                        reason: "'set' must name a context key (e.g. context.prompt.article)"
+                            .to_string(),
+                    });
+                }
+
+                if let Err(e) = parse_logic(&rule.when, &rule.logic) {
+                    result.add_error(ValidationError::InvalidRule {
+                        rule_name: location.clone(),
+                        reason: format!("malformed logic '{}': {}", rule.logic, e),
+                    });
+                }
+
+                for entry in &rule.after {
+                    let (target_ns, target_id) = entry.split_once(':').unwrap_or((ns_id, entry));
+                    let exists = std::iter::once(package)
+                        .chain(dependencies.values())
+                        .any(|pkg| {
+                            pkg.namespaces
+                                .get(target_ns)
+                                .is_some_and(|ns| ns.rules.contains_key(target_id))
+                        });
+
+                    if !exists {
+                        result.add_error(ValidationError::InvalidRule {
+                            rule_name: location.clone(),
+                            reason: format!("'after' names unknown rule '{}'", entry),
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut scoped = Vec::new();
+        for dep_package in dependencies.values() {
+            scoped.extend(scoped_rules(&dep_package.namespaces));
+        }
+        scoped.extend(scoped_rules(&package.namespaces));
+
+        if let Err(RuleError::CircularOrder(rules)) = order_rules(scoped) {
+            result.add_error(ValidationError::InvalidRule {
+                rule_name: rules,
+                reason: "'after' constraints form a cycle".to_string(),
+            });
+        }
+    }
+
+    // Validate decisions: types, expressions, and processor/output shape
+    fn validate_decisions(package: &Package, result: &mut ValidationResult) {
+        use crate::core::models::Processor;
+        use crate::rules::decision::parse_param_type;
+        use crate::rules::expression::Expr;
+
+        for (ns_id, namespace) in &package.namespaces {
+            for decision in &namespace.decisions {
+                let location = format!("{}:{}", ns_id, decision.name);
+                let mut report = |reason: String| {
+                    result.add_error(ValidationError::InvalidDecision {
+                        decision: location.clone(),
+                        reason,
+                    });
+                };
+
+                for (name, declared) in decision.inputs.iter().chain(&decision.outputs) {
+                    if parse_param_type(declared).is_none() {
+                        report(format!(
+                            "unknown type '{}' for '{}' (expected text, number, boolean, list or any)",
+                            declared, name
+                        ));
+                    }
+                }
+
+                for (input, source) in &decision.bindings {
+                    if !decision.inputs.contains_key(input) {
+                        report(format!("binding for undeclared input '{}'", input));
+                    }
+                    if let Err(e) = Expr::parse(source) {
+                        report(format!("invalid binding '{}': {}", source, e));
+                    }
+                }
+
+                match &decision.processor {
+                    Processor::Expression { formula } => {
+                        if decision.outputs.len() != 1 {
+                            report(format!(
+                                "expression processor requires exactly one output, found {}",
+                                decision.outputs.len()
+                            ));
+                        }
+                        if let Err(e) = Expr::parse(formula) {
+                            report(format!("invalid formula '{}': {}", formula, e));
+                        }
+                    }
+                    Processor::RuleSet { rules } => {
+                        for rule in rules {
+                            if !rule.condition.trim().is_empty() {
+                                if let Err(e) = Expr::parse(&rule.condition) {
+                                    report(format!(
+                                        "invalid condition '{}': {}",
+                                        rule.condition, e
+                                    ));
+                                }
+                            }
+                            for output in rule.output.keys() {
+                                if !decision.outputs.contains_key(output) {
+                                    report(format!(
+                                        "rule output '{}' is not a declared output",
+                                        output
+                                    ));
+                                }
+                            }
+                        }
+                    }
+                    Processor::Script { language, .. } => {
+                        report(format!(
+                            "script processors ({}) are not supported",
+                            language
+                        ));
+                    }
+                }
+            }
+        }
     }
 
     // M9 Task 1.4: Validate rulebooks
@@ -935,7 +1215,7 @@ impl PackageValidator {
         // First pass: Collect ALL usage from ALL namespaces
         for (_source_ns_id, source_namespace) in &package.namespaces {
             for promptsection in source_namespace.prompt_sections.values() {
-                for reference in promptsection.references.values() {
+                for reference in Self::effective_references(_source_ns_id, promptsection).values() {
                     // Skip context references
                     if reference.target.starts_with("context:") {
                         continue;
@@ -950,6 +1230,12 @@ impl PackageValidator {
                         (_source_ns_id.clone(), reference.target.clone())
                     };
 
+                    // Components of other packages don't count ("package-id/namespace")
+                    let target_ns = match split_package(&target_ns) {
+                        (Some(package_id), _) if package_id != package.id => continue,
+                        (_, target_ns) => target_ns.to_string(),
+                    };
+
                     // Mark as used in the target namespace (cross-namespace usage counts!)
                     if let Some(target_namespace) = package.namespaces.get(&target_ns) {
                         if target_namespace.datatypes.contains_key(&target_name) {
@@ -965,17 +1251,32 @@ impl PackageValidator {
                         }
                     }
 
-                    // Check separator usage (separators are same-namespace only)
+                    // Check separator usage (bare names are same-namespace)
                     if let Some(sep) = &reference.separator {
-                        used_separators
-                            .entry(_source_ns_id.clone())
-                            .or_default()
-                            .insert(sep.clone());
+                        let (sep_ns, sep_name) = match sep.split_once(':') {
+                            Some((ns, name)) => (ns.to_string(), name.to_string()),
+                            None => (_source_ns_id.clone(), sep.clone()),
+                        };
+                        used_separators.entry(sep_ns).or_default().insert(sep_name);
                     }
                 }
             }
         }
 
+        // Datatypes extended by another datatype count as used
+        for (ns_id, namespace) in &package.namespaces {
+            for datatype in namespace.datatypes.values() {
+                if let Some(parent) = &datatype.extends {
+                    let (parent_ns, parent_name) =
+                        crate::core::inheritance::parse_extends_target(parent, ns_id);
+                    used_datatypes
+                        .entry(parent_ns)
+                        .or_default()
+                        .insert(parent_name);
+                }
+            }
+        }
+
         // Second pass: Warn about unused components in each namespace
         for (ns_id, namespace) in &package.namespaces {
             let ns_used_datatypes = used_datatypes.get(ns_id).cloned().unwrap_or_default();
@@ -1085,19 +1386,20 @@ impl PackageValidator {
 mod tests {
     use super::*;
     use crate::core::models::*;
+    use indexmap::IndexMap;
     use std::collections::HashMap;
 
     fn create_test_package() -> Package {
-        let mut namespaces = HashMap::new();
+        let mut namespaces = IndexMap::new();
 
         let mut namespace = Namespace {
             id: "test".to_string(),
-            datatypes: HashMap::new(),
-            prompt_sections: HashMap::new(),
-            separator_sets: HashMap::new(),
-            rules: HashMap::new(), // Changed from Vec to HashMap
+            datatypes: IndexMap::new(),
+            prompt_sections: IndexMap::new(),
+            separator_sets: IndexMap::new(),
+            rules: IndexMap::new(),
             decisions: Vec::new(),
-            rulebooks: HashMap::new(), // M9
+            rulebooks: IndexMap::new(), // M9
         };
 
         // Add a datatype
@@ -1106,17 +1408,17 @@ mod tests {
             values: vec![
                 DatatypeValue {
                     text: "red".to_string(),
-                    tags: HashMap::new(),
+                    tags: IndexMap::new(),
                     weight: 1.0,
                 },
                 DatatypeValue {
                     text: "blue".to_string(),
-                    tags: HashMap::new(),
+                    tags: IndexMap::new(),
                     weight: 1.0,
                 },
             ],
             extends: None,
-            override_tags: HashMap::new(),
+            override_tags: IndexMap::new(),
         };
         namespace.datatypes.insert("colors".to_string(), datatype);
 
@@ -1143,6 +1445,7 @@ mod tests {
                 bypass_filters: false,
             },
             namespaces,
+            default_namespace: None,
             dependencies: Vec::new(),
         }
     }
@@ -1166,6 +1469,20 @@ mod tests {
         assert_eq!(result.errors.len(), 1);
     }
 
+    #[test]
+    fn test_validation_error_serializes_with_kind() {
+        let error = ValidationError::ReferenceNotFound {
+            reference: "test:colours".to_string(),
+            defined_in: "test:prompt".to_string(),
+            suggestion: Some("test:colors".to_string()),
+        };
+        let json = serde_json::to_value(&error).unwrap();
+
+        assert_eq!(json["kind"], "ReferenceNotFound");
+        assert_eq!(json["reference"], "test:colours");
+        assert_eq!(json["suggestion"], "test:colors");
+    }
+
     #[test]
     fn test_validation_result_add_warning() {
         let mut result = ValidationResult::new();
@@ -1186,7 +1503,7 @@ mod tests {
         let mut ps = PromptSection {
             name: "test_prompt".to_string(),
             template: "{color}".to_string(),
-            references: HashMap::new(),
+            references: IndexMap::new(),
         };
 
         ps.references.insert(
@@ -1198,6 +1515,7 @@ mod tests {
                 max: 1,
                 separator: None,
                 unique: false,
+                fallback: Fallback::Error,
             },
         );
 
@@ -1219,7 +1537,7 @@ mod tests {
         let mut ps = PromptSection {
             name: "test_prompt".to_string(),
             template: "{color}".to_string(),
-            references: HashMap::new(),
+            references: IndexMap::new(),
         };
 
         // Reference non-existent datatype
@@ -1232,6 +1550,7 @@ mod tests {
                 max: 1,
                 separator: None,
                 unique: false,
+                fallback: Fallback::Error,
             },
         );
 
@@ -1251,6 +1570,108 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_inline_template_references() {
+        let mut package = create_test_package();
+
+        // Implicit references resolve against the promptsection's namespace
+        package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .insert(
+                "compact".to_string(),
+                PromptSection {
+                    name: "compact".to_string(),
+                    template: "{colors?min=2,max=2&sep=comma_and&unique}".to_string(),
+                    references: IndexMap::new(),
+                },
+            );
+        let result = PackageValidator::validate(&package);
+        assert!(result.is_valid(), "errors: {:?}", result.errors);
+
+        // Inline parameters are validated like YAML ones
+        let section = package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .get_mut("compact")
+            .unwrap();
+        section.template = "{colors?max=3&sep=missing&unique} {shapes}".to_string();
+        let result = PackageValidator::validate(&package);
+        assert!(result
+            .errors
+            .iter()
+            .any(|e| matches!(e, ValidationError::SeparatorNotFound { .. })));
+        assert!(result.errors.iter().any(|e| matches!(
+            e,
+            ValidationError::UniqueConstraintInfeasible { requested: 3, .. }
+        )));
+        assert!(result.errors.iter().any(|e| matches!(
+            e,
+            ValidationError::ReferenceNotFound { reference, .. } if reference == "test:shapes"
+        )));
+
+        let section = package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .get_mut("compact")
+            .unwrap();
+        section.template = "{colors".to_string();
+        let result = PackageValidator::validate(&package);
+        assert!(result
+            .errors
+            .iter()
+            .any(|e| matches!(e, ValidationError::InvalidTemplate { .. })));
+    }
+
+    #[test]
+    fn test_unknown_transform() {
+        let mut package = create_test_package();
+        package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .insert(
+                "piped".to_string(),
+                PromptSection {
+                    name: "piped".to_string(),
+                    template: "{colors|a_an|capitalize} {colors|shout}".to_string(),
+                    references: IndexMap::new(),
+                },
+            );
+
+        let result = PackageValidator::validate(&package);
+        let unknown: Vec<&str> = result
+            .errors
+            .iter()
+            .filter_map(|e| match e {
+                ValidationError::UnknownTransform { transform, .. } => Some(transform.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(unknown, vec!["shout"]);
+    }
+
+    #[test]
+    fn test_default_namespace_must_exist() {
+        let mut package = create_test_package();
+        package.default_namespace = Some("test".to_string());
+        assert!(PackageValidator::validate(&package).is_valid());
+
+        package.default_namespace = Some("missing".to_string());
+        let result = PackageValidator::validate(&package);
+        assert!(result.errors.iter().any(|e| matches!(
+            e,
+            ValidationError::ReferenceNotFound { defined_in, .. } if defined_in == "default_namespace"
+        )));
+    }
+
     #[test]
     fn test_min_max_invalid() {
         let mut package = create_test_package();
@@ -1258,7 +1679,7 @@ mod tests {
         let mut ps = PromptSection {
             name: "test_prompt".to_string(),
             template: "{colors}".to_string(),
-            references: HashMap::new(),
+            references: IndexMap::new(),
         };
 
         // min > max
@@ -1271,6 +1692,7 @@ mod tests {
                 max: 2,
                 separator: None,
                 unique: false,
+                fallback: Fallback::Error,
             },
         );
 
@@ -1296,7 +1718,7 @@ mod tests {
         let mut ps = PromptSection {
             name: "test_prompt".to_string(),
             template: "{colors}".to_string(),
-            references: HashMap::new(),
+            references: IndexMap::new(),
         };
 
         // Reference non-existent separator
@@ -1309,6 +1731,7 @@ mod tests {
                 max: 3,
                 separator: Some("missing_separator".to_string()),
                 unique: false,
+                fallback: Fallback::Error,
             },
         );
 
@@ -1327,6 +1750,40 @@ mod tests {
             .any(|e| matches!(e, ValidationError::SeparatorNotFound { .. })));
     }
 
+    #[test]
+    fn test_qualified_separator_in_dependency() {
+        let mut package = create_test_package();
+        package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .prompt_sections
+            .insert(
+                "test_prompt".to_string(),
+                PromptSection {
+                    name: "test_prompt".to_string(),
+                    template: "{colors?max=2&sep=common:comma_and}".to_string(),
+                    references: IndexMap::new(),
+                },
+            );
+
+        let is_separator_error =
+            |e: &ValidationError| matches!(e, ValidationError::SeparatorNotFound { .. });
+
+        let result = PackageValidator::validate(&package);
+        assert!(result.errors.iter().any(is_separator_error));
+
+        let mut common = create_test_package();
+        common.id = "common.package".to_string();
+        let namespace = common.namespaces.shift_remove("test").unwrap();
+        common.namespaces.insert("common".to_string(), namespace);
+        let mut dependencies = HashMap::new();
+        dependencies.insert(common.id.clone(), common);
+
+        let result = PackageValidator::validate_with_dependencies(&package, &dependencies);
+        assert!(!result.errors.iter().any(is_separator_error));
+    }
+
     #[test]
     fn test_unique_constraint_infeasible() {
         let mut package = create_test_package();
@@ -1334,7 +1791,7 @@ mod tests {
         let mut ps = PromptSection {
             name: "test_prompt".to_string(),
             template: "{colors}".to_string(),
-            references: HashMap::new(),
+            references: IndexMap::new(),
         };
 
         // Request 5 unique values but only 2 available
@@ -1347,6 +1804,7 @@ mod tests {
                 max: 5,
                 separator: None,
                 unique: true,
+                fallback: Fallback::Error,
             },
         );
 
@@ -1387,7 +1845,7 @@ mod tests {
             name: "InvalidName".to_string(),
             values: vec![],
             extends: None,
-            override_tags: HashMap::new(),
+            override_tags: IndexMap::new(),
         };
         package
             .namespaces
@@ -1427,7 +1885,7 @@ mod tests {
         let mut package = create_test_package();
 
         // Add a promptsection
-        let mut refs = HashMap::new();
+        let mut refs = IndexMap::new();
         refs.insert(
             "color".to_string(),
             Reference {
@@ -1437,6 +1895,7 @@ mod tests {
                 max: 1,
                 separator: None,
                 unique: false,
+                fallback: Fallback::Error,
             },
         );
 
@@ -1463,7 +1922,7 @@ mod tests {
                 weight: 1.0,
             }],
             batch_variety: false,
-            context_defaults: HashMap::new(),
+            context_defaults: IndexMap::new(),
         };
 
         package
@@ -1492,7 +1951,7 @@ mod tests {
                 weight: 1.0,
             }],
             batch_variety: false,
-            context_defaults: HashMap::new(),
+            context_defaults: IndexMap::new(),
         };
 
         package
@@ -1528,12 +1987,12 @@ mod tests {
                 PromptSection {
                     name: "simple".to_string(),
                     template: "test".to_string(),
-                    references: HashMap::new(),
+                    references: IndexMap::new(),
                 },
             );
 
         // Add rulebook with invalid context key format
-        let mut context_defaults = HashMap::new();
+        let mut context_defaults = IndexMap::new();
         context_defaults.insert("invalid::key".to_string(), "value".to_string()); // Double colon is invalid
 
         let rulebook = Rulebook {
@@ -1580,12 +2039,12 @@ mod tests {
                 PromptSection {
                     name: "simple".to_string(),
                     template: "test".to_string(),
-                    references: HashMap::new(),
+                    references: IndexMap::new(),
                 },
             );
 
         // Add rulebook with empty context key
-        let mut context_defaults = HashMap::new();
+        let mut context_defaults = IndexMap::new();
         context_defaults.insert("".to_string(), "value".to_string());
 
         let rulebook = Rulebook {
@@ -1624,12 +2083,12 @@ mod tests {
         // Add another namespace
         let mut namespace2 = Namespace {
             id: "other".to_string(),
-            datatypes: HashMap::new(),
-            prompt_sections: HashMap::new(),
-            separator_sets: HashMap::new(),
-            rules: HashMap::new(),
+            datatypes: IndexMap::new(),
+            prompt_sections: IndexMap::new(),
+            separator_sets: IndexMap::new(),
+            rules: IndexMap::new(),
             decisions: Vec::new(),
-            rulebooks: HashMap::new(),
+            rulebooks: IndexMap::new(),
         };
 
         // Add a promptsection in other namespace
@@ -1638,7 +2097,7 @@ mod tests {
             PromptSection {
                 name: "scene".to_string(),
                 template: "test scene".to_string(),
-                references: HashMap::new(),
+                references: IndexMap::new(),
             },
         );
 
@@ -1653,7 +2112,7 @@ mod tests {
                 weight: 1.0,
             }],
             batch_variety: false,
-            context_defaults: HashMap::new(),
+            context_defaults: IndexMap::new(),
         };
 
         package
@@ -1682,7 +2141,7 @@ mod tests {
                 weight: 1.0,
             }],
             batch_variety: false,
-            context_defaults: HashMap::new(),
+            context_defaults: IndexMap::new(),
         };
 
         package
@@ -1700,4 +2159,117 @@ mod tests {
             if suggestion.as_ref().map(|s| s.contains("namespace:name")).unwrap_or(false)
         )));
     }
+
+    #[test]
+    fn test_invalid_decision() {
+        use crate::core::models::{Decision, Processor};
+
+        let mut package = create_test_package();
+
+        package
+            .namespaces
+            .get_mut("test")
+            .unwrap()
+            .decisions
+            .push(Decision {
+                name: "broken".to_string(),
+                inputs: IndexMap::from([("size".to_string(), "widget".to_string())]),
+                outputs: IndexMap::new(),
+                bindings: IndexMap::new(),
+                processor: Processor::Expression {
+                    formula: "size >".to_string(),
+                },
+            });
+
+        let result = PackageValidator::validate(&package);
+        assert!(!result.is_valid());
+
+        let reasons: Vec<String> = result
+            .errors
+            .iter()
+            .filter_map(|e| match e {
+                ValidationError::InvalidDecision { reason, .. } => Some(reason.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(reasons.len(), 3);
+        assert!(reasons.iter().any(|r| r.contains("unknown type 'widget'")));
+        assert!(reasons.iter().any(|r| r.contains("exactly one output")));
+        assert!(reasons.iter().any(|r| r.contains("invalid formula")));
+    }
+
+    #[test]
+    fn test_invalid_rule_logic() {
+        use crate::core::models::Rule;
+
+        let mut package = create_test_package();
+        let rules = &mut package.namespaces.get_mut("test").unwrap().rules;
+        rules.insert(
+            "good".to_string(),
+            Rule {
+                when: "ref:color.tags.article".to_string(),
+                logic: "== \"an\"".to_string(),
+                set: "context.prompt.article".to_string(),
+                value: "ref:color.tags.article".to_string(),
+                priority: 0,
+                after: Vec::new(),
+            },
+        );
+        rules.insert(
+            "bad".to_string(),
+            Rule {
+                when: "ref:color.tags.article".to_string(),
+                logic: "== (\"an\"".to_string(),
+                set: "context.prompt.article".to_string(),
+                value: "ref:color.tags.article".to_string(),
+                priority: 0,
+                after: Vec::new(),
+            },
+        );
+
+        let result = PackageValidator::validate(&package);
+        let invalid: Vec<&ValidationError> = result
+            .errors
+            .iter()
+            .filter(|e| matches!(e, ValidationError::InvalidRule { .. }))
+            .collect();
+        assert_eq!(invalid.len(), 1, "Got: {:?}", result.errors);
+        assert!(matches!(
+            invalid[0],
+            ValidationError::InvalidRule { rule_name, .. } if rule_name == "test:bad"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_rule_order() {
+        use crate::core::models::Rule;
+
+        let make = |after: &str| Rule {
+            when: "ref:color".to_string(),
+            logic: String::new(),
+            set: "context.prompt.x".to_string(),
+            value: "x".to_string(),
+            priority: 0,
+            after: vec![after.to_string()],
+        };
+
+        let mut package = create_test_package();
+        let rules = &mut package.namespaces.get_mut("test").unwrap().rules;
+        rules.insert("a".to_string(), make("b"));
+        rules.insert("b".to_string(), make("test:a"));
+        rules.insert("c".to_string(), make("missing"));
+
+        let result = PackageValidator::validate(&package);
+        let reasons: Vec<String> = result
+            .errors
+            .iter()
+            .filter_map(|e| match e {
+                ValidationError::InvalidRule { reason, .. } => Some(reason.clone()),
+                _ => None,
+            })
+            .collect();
+
+        assert!(reasons.iter().any(|r| r.contains("unknown rule 'missing'")));
+        assert!(reasons.iter().any(|r| r.contains("form a cycle")));
+    }
 }